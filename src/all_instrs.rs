@@ -1,24 +1,24 @@
-use crate::{TestError, TestableCpu};
+use crate::{RomFailure, TestableCpu, ROM_ALL_INSTR};
 
-pub(crate) fn all_instrs_status_code(cpu: &impl TestableCpu) -> Result<(), TestError> {
+pub(crate) fn all_instrs_status_code(cpu: &impl TestableCpu) -> Result<(), RomFailure> {
     let status = cpu.memory_read(0x6000);
     let m1 = cpu.memory_read(0x6001);
     let m2 = cpu.memory_read(0x6002);
     let m3 = cpu.memory_read(0x6003);
 
     if m1 != 0xde || m2 != 0xb0 || m3 != 0x61 {
-        return Err(TestError::String(format!(
-            "invalid magic sequence: {m1:x}{m2:x}{m3:x}. the test output was corrupted"
-        )));
+        return Err(RomFailure::InvalidMagic {
+            magic: [m1, m2, m3],
+        });
     }
 
     if status == 0 {
         Ok(())
     } else {
-        Err(TestError::String(format!(
-            "exited with status {status}:\n {}",
-            read_status_string(cpu)
-        )))
+        Err(RomFailure::Blargg {
+            status,
+            text: read_status_string(cpu),
+        })
     }
 }
 
@@ -35,3 +35,445 @@ pub(crate) fn read_status_string(cpu: &impl TestableCpu) -> String {
 
     res
 }
+
+/// The state of a rom that reports its result at $6000, like blargg's roms do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BlarggStatus {
+    /// The signature at $6001-$6003 isn't written yet, so $6000 doesn't mean anything
+    NotStarted,
+    /// $80: the test is still running
+    Running,
+    /// $81: the rom wants the cpu to be reset
+    ResetRequested,
+    /// Any other value is the result of the test, where 0 means it passed
+    Done(u8),
+}
+
+pub(crate) fn blargg_status(cpu: &impl TestableCpu) -> BlarggStatus {
+    let magic = [0x6001, 0x6002, 0x6003].map(|address| cpu.memory_read(address));
+    if magic != [0xde, 0xb0, 0x61] {
+        return BlarggStatus::NotStarted;
+    }
+
+    match cpu.memory_read(0x6000) {
+        0x80 => BlarggStatus::Running,
+        0x81 => BlarggStatus::ResetRequested,
+        status => BlarggStatus::Done(status),
+    }
+}
+
+/// The roms that make up all_instrs.nes and official_only.nes, in the order they run
+const SUBTESTS: [&str; 16] = [
+    "01-basics",
+    "02-implied",
+    "03-immediate",
+    "04-zero_page",
+    "05-zp_xy",
+    "06-absolute",
+    "07-abs_xy",
+    "08-ind_x",
+    "09-ind_y",
+    "10-branches",
+    "11-stack",
+    "12-jmp_jsr",
+    "13-rts",
+    "14-rti",
+    "15-brk",
+    "16-special",
+];
+
+/// Where the roms of instr_test-v5 count how many of them ran
+const COUNT: [u8; 2] = [0x24, 0x02];
+
+/// Builds the rom of one of the 16 parts of instr_test-v5, like the single roms it comes with, from "all_instrs.nes".
+///
+/// "all_instrs.nes" is a mapper 1 rom, with every part in its own 16 KiB bank, which holds the same code as the single rom
+/// of that part. A bank starts by setting the count at $0224 to $FF and setting up the mapper, and continues at
+/// the address at $FFF8. That code counts the part, and switches to the bank of the next part until 16 of them ran.
+/// The bank of a single part is turned into an NROM rom that sets the count to the part before it,
+/// and runs only that part without touching the mapper.
+pub(crate) fn single_instr_rom(number: u8) -> Vec<u8> {
+    let start = 16 + usize::from(number - 1) * 0x4000;
+    let mut prg = ROM_ALL_INSTR[start..start + 0x4000].to_vec();
+
+    let reset = usize::from(u16::from_le_bytes([prg[0x3FFC], prg[0x3FFD]]) - 0xC000);
+    #[rustfmt::skip]
+    let reset_handler = [
+        0xA9, number.wrapping_sub(2),       // LDA #number-2
+        0x8D, COUNT[0], COUNT[1],           // STA $0224
+        0xA9, 0x00,                         // LDA #$00
+        0x6C, 0xF8, 0xFF,                   // JMP ($FFF8)
+    ];
+    prg[reset..reset + reset_handler.len()].copy_from_slice(&reset_handler);
+
+    // LDA $0224, CMP #$10: stop after this part
+    let check = find(&prg, &[0xAD, COUNT[0], COUNT[1], 0xC9, 0x10]);
+    prg[check + 4] = number;
+
+    // JMP $0700, which switches to the bank of the next part: start it with JMP ($FFF6) instead
+    let next = check + find(&prg[check..], &[0x4C, 0x00, 0x07]);
+    prg[next..next + 3].copy_from_slice(&[0x6C, 0xF6, 0xFF]);
+
+    let mut rom = b"NES\x1A\x01\x01\x00\x00".to_vec();
+    rom.resize(16, 0);
+    rom.extend(prg);
+    rom.resize(rom.len() + 0x2000, 0);
+    rom
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .expect("every bank of all_instrs.nes has the same code to switch between parts")
+}
+
+/// What one of the roms that make up instr_test-v5 reported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtestResult {
+    /// The number of the rom, starting at 1
+    pub number: u8,
+    /// The name of the rom, like `05-zp_xy`
+    pub name: String,
+    /// Whether the cpu passed the rom
+    pub passed: bool,
+    /// The amount of cycles after which the rom was done.
+    /// The status of the rom is only checked every 200 000 cycles, so this is rounded up to that.
+    pub finished_at: u64,
+    /// The instructions the rom reported the cpu got wrong
+    pub failures: Vec<FailedInstruction>,
+    /// The other lines the rom printed, like "BRK should push address BRK + 2"
+    pub messages: Vec<String>,
+}
+
+/// An instruction instr_test-v5 reported the cpu got wrong, printed like `B6 LDX z,Y`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedInstruction {
+    /// The opcode of the instruction
+    pub opcode: u8,
+    /// The mnemonic of the instruction, like `LDX`
+    pub mnemonic: String,
+    /// The addressing mode as the rom prints it, like `z,Y`, or `None` for implied instructions
+    pub mode: Option<String>,
+    /// The line the rom printed right after the instruction, if any
+    pub message: Option<String>,
+}
+
+/// Keeps track of the cycles at which the roms of instr_test-v5 started,
+/// using the "Running test 5 of 16" line the rom writes while it runs
+#[derive(Debug, Default)]
+pub(crate) struct SubtestTimes {
+    started: Vec<u64>,
+}
+
+impl SubtestTimes {
+    /// Called with the first line of the status text whenever it changes
+    pub(crate) fn status_changed(&mut self, cycles: u64, status: &str) {
+        if let Some(number) = test_number(status, "Running test ") {
+            if number > self.started.len() {
+                self.started.resize(number, cycles);
+            }
+        }
+    }
+
+    /// Parses the text instr_test-v5 wrote when it was done after `cycles` cycles into the results of the roms that ran
+    pub(crate) fn results(&self, text: &str, cycles: u64) -> Vec<SubtestResult> {
+        let lines: Vec<&str> = text.lines().map(str::trim).collect();
+
+        if lines
+            .iter()
+            .any(|line| line.starts_with("All ") && line.ends_with(" tests passed"))
+        {
+            return (1..=SUBTESTS.len())
+                .map(|number| self.passed(number, cycles))
+                .collect();
+        }
+
+        // the combined rom stops at the first rom that fails
+        let failed = lines
+            .iter()
+            .find_map(|line| test_number(line, "While running test "))
+            .or_else(|| {
+                let position = SUBTESTS.iter().position(|name| lines.contains(name))?;
+                Some(position + 1)
+            });
+        let Some(failed) = failed else {
+            return Vec::new();
+        };
+
+        let mut results: Vec<_> = (1..failed)
+            .map(|number| self.passed(number, cycles))
+            .collect();
+        let mut result = SubtestResult {
+            number: failed as u8,
+            name: subtest_name(failed),
+            passed: false,
+            finished_at: cycles,
+            failures: Vec::new(),
+            messages: Vec::new(),
+        };
+
+        let mut after_instruction = false;
+        for line in lines {
+            if let Some(instruction) = failed_instruction(line) {
+                result.failures.push(instruction);
+                after_instruction = true;
+                continue;
+            }
+
+            let noise = line.is_empty()
+                || line == "Failed"
+                || line.starts_with("Failed #")
+                || line.starts_with("While running test ")
+                || SUBTESTS.contains(&line);
+            if noise {
+                after_instruction = false;
+            } else if after_instruction {
+                result.failures.last_mut().unwrap().message = Some(line.to_string());
+                after_instruction = false;
+            } else {
+                result.messages.push(line.to_string());
+            }
+        }
+
+        results.push(result);
+        results
+    }
+
+    fn passed(&self, number: usize, cycles: u64) -> SubtestResult {
+        SubtestResult {
+            number: number as u8,
+            name: subtest_name(number),
+            passed: true,
+            finished_at: self.started.get(number).copied().unwrap_or(cycles),
+            failures: Vec::new(),
+            messages: Vec::new(),
+        }
+    }
+}
+
+fn subtest_name(number: usize) -> String {
+    SUBTESTS
+        .get(number.wrapping_sub(1))
+        .map_or_else(|| format!("test {number}"), |name| name.to_string())
+}
+
+/// Parses the number out of lines like "Running test 5 of 16", where `prefix` is "Running test "
+fn test_number(line: &str, prefix: &str) -> Option<usize> {
+    line.strip_prefix(prefix)?.split(' ').next()?.parse().ok()
+}
+
+/// Parses a line like `B6 LDX z,Y` or `40 RTI`
+fn failed_instruction(line: &str) -> Option<FailedInstruction> {
+    let mut parts = line.splitn(3, ' ');
+    let opcode = parts.next().filter(|opcode| opcode.len() == 2)?;
+    let opcode = u8::from_str_radix(opcode, 16).ok()?;
+    let mnemonic = parts.next().filter(|mnemonic| {
+        mnemonic.len() == 3 && mnemonic.bytes().all(|b| b.is_ascii_uppercase())
+    })?;
+
+    Some(FailedInstruction {
+        opcode,
+        mnemonic: mnemonic.to_string(),
+        mode: parts.next().map(str::to_string),
+        message: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What official_only.nes writes when LDX z,Y and STX z,Y are wrong
+    const ZP_Y_FAILED: &str =
+        "B6 LDX z,Y\n96 STX z,Y\n\n05-zp_xy\n\nFailed\n\nWhile running test 5 of 16\n\n\n";
+    /// What all_instrs.nes writes when LAX (z),Y is wrong
+    const LAX_FAILED: &str =
+        "B3 LAX (z),Y\n\n09-ind_y\n\nFailed\n\nWhile running test 9 of 16\n\n\n";
+    /// What a single rom of instr_test-v5 writes when RTI is wrong, which doesn't say which test ran
+    const RTI_FAILED: &str = "40 RTI\n\n14-rti\n\nFailed\n\n";
+    const PASSED: &str = "All 16 tests passed\n\n\n";
+
+    /// The status lines instr_test-v5 writes while it runs, with the cycle after which they were first seen
+    fn running(until: usize) -> SubtestTimes {
+        let mut times = SubtestTimes::default();
+        for number in 1..=until {
+            let cycles = number as u64 * 200_000;
+            times.status_changed(cycles, &format!("Running test {number} of 16"));
+            // the line is seen again while the rom runs
+            times.status_changed(cycles + 200_000, &format!("Running test {number} of 16"));
+        }
+        times
+    }
+
+    #[test]
+    fn failing_instructions() {
+        let results = running(5).results(ZP_Y_FAILED, 1_400_000);
+
+        assert_eq!(results.len(), 5);
+        for (number, result) in (1..).zip(&results[..4]) {
+            assert_eq!(result.number, number);
+            assert_eq!(result.name, SUBTESTS[usize::from(number) - 1]);
+            assert!(result.passed);
+            // a rom is done when the next one starts
+            assert_eq!(result.finished_at, u64::from(number + 1) * 200_000);
+        }
+
+        assert_eq!(
+            results[4],
+            SubtestResult {
+                number: 5,
+                name: "05-zp_xy".to_string(),
+                passed: false,
+                finished_at: 1_400_000,
+                failures: vec![
+                    FailedInstruction {
+                        opcode: 0xB6,
+                        mnemonic: "LDX".to_string(),
+                        mode: Some("z,Y".to_string()),
+                        message: None,
+                    },
+                    FailedInstruction {
+                        opcode: 0x96,
+                        mnemonic: "STX".to_string(),
+                        mode: Some("z,Y".to_string()),
+                        message: None,
+                    },
+                ],
+                messages: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn modes_with_spaces_and_parentheses() {
+        let results = running(9).results(LAX_FAILED, 2_000_000);
+        let failed = results.last().unwrap();
+
+        assert_eq!(failed.name, "09-ind_y");
+        assert_eq!(failed.failures[0].opcode, 0xB3);
+        assert_eq!(failed.failures[0].mode.as_deref(), Some("(z),Y"));
+    }
+
+    #[test]
+    fn single_roms_are_recognized_by_name() {
+        let results = SubtestTimes::default().results(RTI_FAILED, 900_000);
+
+        assert_eq!(results.len(), 14);
+        assert!(results[..13].iter().all(|result| result.passed));
+        assert_eq!(results[13].name, "14-rti");
+        assert_eq!(results[13].failures[0].mnemonic, "RTI");
+        assert_eq!(results[13].failures[0].mode, None);
+    }
+
+    #[test]
+    fn messages_after_an_instruction() {
+        let text = "00 BRK\nBRK should push address BRK + 2\n\nNot an instruction\n\n15-brk\n\nFailed #3\n\nWhile running test 15 of 16\n";
+        let failed = SubtestTimes::default().results(text, 0).pop().unwrap();
+
+        assert_eq!(
+            failed.failures[0].message.as_deref(),
+            Some("BRK should push address BRK + 2")
+        );
+        assert_eq!(failed.messages, ["Not an instruction"]);
+    }
+
+    #[test]
+    fn all_passed() {
+        let results = running(16).results(PASSED, 4_000_000);
+
+        assert_eq!(results.len(), 16);
+        assert!(results.iter().all(|result| result.passed));
+        assert_eq!(results[15].finished_at, 4_000_000);
+    }
+
+    #[test]
+    fn unknown_text() {
+        assert_eq!(SubtestTimes::default().results("Failed\n", 0), Vec::new());
+    }
+
+    #[test]
+    fn single_roms() {
+        for number in 1..=16 {
+            let rom = single_instr_rom(number);
+            assert_eq!(rom.len(), 16 + 0x4000 + 0x2000);
+            // NROM with one bank of PRG ROM and one of CHR ROM
+            assert_eq!(rom[..16], *b"NES\x1A\x01\x01\0\0\0\0\0\0\0\0\0\0");
+            assert!(rom[16 + 0x4000..].iter().all(|&byte| byte == 0));
+
+            let bank = &ROM_ALL_INSTR[16 + usize::from(number - 1) * 0x4000..][..0x4000];
+            let prg = &rom[16..16 + 0x4000];
+            let vector = |prg: &[u8], address: u16| {
+                let at = usize::from(address - 0xC000);
+                u16::from_le_bytes([prg[at], prg[at + 1]])
+            };
+            // the NMI, reset and IRQ vectors, and those of the next part and the code after the mapper setup
+            for address in [0xFFFA, 0xFFFC, 0xFFFE, 0xFFF6, 0xFFF8] {
+                assert_eq!(vector(prg, address), vector(bank, address));
+            }
+            for address in [0xFFFC, 0xFFF6, 0xFFF8] {
+                assert!(
+                    vector(prg, address) >= 0xC000,
+                    "${address:04X} of part {number}"
+                );
+            }
+
+            // only the reset handler, the count to stop at and the jump to the next part are patched
+            let reset = usize::from(vector(prg, 0xFFFC) - 0xC000);
+            assert_eq!(
+                prg[reset..reset + 10],
+                [
+                    0xA9,
+                    number.wrapping_sub(2),
+                    0x8D,
+                    0x24,
+                    0x02,
+                    0xA9,
+                    0x00,
+                    0x6C,
+                    0xF8,
+                    0xFF
+                ]
+            );
+            let check = find(prg, &[0xAD, 0x24, 0x02, 0xC9, number]);
+            let next = check + find(&prg[check..], &[0x6C, 0xF6, 0xFF]);
+            assert_eq!(bank[next..next + 3], [0x4C, 0x00, 0x07]);
+
+            let patched: Vec<_> = (0..0x4000).filter(|&i| prg[i] != bank[i]).collect();
+            let allowed = |i: usize| {
+                (reset..reset + 10).contains(&i) || i == check + 4 || (next..next + 3).contains(&i)
+            };
+            assert!(
+                patched.iter().all(|&i| allowed(i)),
+                "part {number} has unexpected changes at {patched:X?}"
+            );
+        }
+    }
+
+    #[cfg(feature = "reference")]
+    #[test]
+    fn reference_cpu_passes_every_single_rom() {
+        use crate::{run_tests_report, ReferenceCpu, TestSelector};
+
+        let report = run_tests_report::<ReferenceCpu>(TestSelector::INSTR_SINGLES);
+        assert_eq!(report.results.len(), 16);
+        assert!(report.passed(), "{report}");
+    }
+
+    #[test]
+    fn instruction_lines() {
+        assert_eq!(failed_instruction("Failed"), None);
+        assert_eq!(failed_instruction("While running test 5 of 16"), None);
+        assert_eq!(failed_instruction("05-zp_xy"), None);
+        assert_eq!(failed_instruction("0A lsr"), None);
+        assert_eq!(
+            failed_instruction("0A ASL A"),
+            Some(FailedInstruction {
+                opcode: 0x0A,
+                mnemonic: "ASL".to_string(),
+                mode: Some("A".to_string()),
+                message: None,
+            })
+        );
+    }
+}
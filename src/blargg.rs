@@ -0,0 +1,163 @@
+use crate::all_instrs::{all_instrs_status_code, blargg_status, read_status_string, BlarggStatus};
+use crate::watchdog::CpuStuck;
+use crate::{
+    cpu_error, run_on_cpu, run_on_thread, Capability, RomFailure, TestError, TestOutcome,
+    TestProgress, TestableCpu, Trace,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The amount of cycles the cpu runs before the status of the rom is checked again
+const CHUNK: u64 = 200_000;
+/// Blargg's roms want to be reset at least 100 ms after they ask for it, which is about this many cycles
+const RESET_DELAY: u64 = 180_000;
+
+/// Options for [`run_blargg_rom`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlarggOptions {
+    /// The most cycles the rom may run before it should have written its result
+    pub max_cycles: u64,
+    /// How long the rom may run before it should have written its result
+    pub timeout: Duration,
+    /// The size of the stack of the thread the rom runs on, see [`TestRunner::stack_size`](crate::TestRunner::stack_size)
+    pub stack_size: Option<usize>,
+    /// Writes a trace of the rom to a file named `blargg_rom.log`, see [`Trace`]
+    pub trace: Option<Trace>,
+}
+
+impl Default for BlarggOptions {
+    fn default() -> Self {
+        Self {
+            max_cycles: 100_000_000,
+            timeout: Duration::from_secs(600),
+            stack_size: None,
+            trace: None,
+        }
+    }
+}
+
+/// The result a rom wrote using blargg's $6000 protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlarggResult {
+    /// The final status code at $6000, where 0 means the rom passed
+    pub status: u8,
+    /// The text the rom wrote starting at $6004
+    pub text: String,
+    /// The amount of cycles after which the rom was done, rounded up to the next time its status was checked
+    pub cycles: u64,
+}
+
+impl BlarggResult {
+    /// Whether the rom passed
+    pub fn passed(&self) -> bool {
+        self.status == 0
+    }
+}
+
+/// Runs a rom that reports its result like blargg's test roms do, and returns the result it wrote.
+/// Most roms in the [nes-test-roms](https://github.com/christopherpow/nes-test-roms) collection work like this:
+/// they write $80 to $6000 while they're running, the signature $DE $B0 $61 to $6001-$6003 and text to $6004 and on.
+/// When they're done, they write the final status code to $6000.
+/// When a rom writes $81 to $6000 it is reset, which is skipped when your CPU doesn't implement [`CpuReset`](crate::CpuReset).
+///
+/// The rom runs on its own thread, like the tests of [`run_tests`](crate::run_tests) do. An error is returned when
+/// the rom doesn't write its result in time, or when your CPU returns an error or panics.
+pub fn run_blargg_rom<T: TestableCpu>(
+    rom: &[u8],
+    options: &BlarggOptions,
+) -> Result<BlarggResult, TestError> {
+    let rom = rom.to_vec();
+    let max_cycles = options.max_cycles;
+    let reported = Arc::new(Mutex::new(None));
+
+    let test = {
+        let reported = Arc::clone(&reported);
+        move |progress: &TestProgress| {
+            run_on_cpu::<T>(&rom, progress, |cpu| {
+                let result = run_blargg(cpu, progress, max_cycles, |_, _| {})?;
+                *reported.lock().unwrap() = Some(result);
+                Ok(())
+            })
+        }
+    };
+
+    run_on_thread(
+        "blargg rom",
+        test,
+        options.timeout,
+        options.stack_size,
+        options.trace.as_ref(),
+    )
+    .into_result()?;
+    let result = reported.lock().unwrap().take();
+    Ok(result.expect("a rom that ran successfully reported its result"))
+}
+
+/// Runs a rom following blargg's $6000 protocol until it writes its result, for at most `max_cycles` cycles.
+/// `status_changed` is called with the amount of cycles and the first line of the text whenever that line changes.
+pub(crate) fn run_blargg<T: TestableCpu>(
+    cpu: &mut T,
+    progress: &TestProgress,
+    max_cycles: u64,
+    mut status_changed: impl FnMut(u64, &str),
+) -> Result<BlarggResult, TestOutcome> {
+    let mut prev = String::new();
+    let mut cycles = 0;
+
+    while cycles < max_cycles {
+        let chunk = CHUNK.min(max_cycles - cycles);
+        run_chunk(cpu, progress, chunk)?;
+        cycles += chunk;
+
+        let status = read_status_string(cpu);
+        let status = status.split('\n').next().unwrap().trim().to_string();
+        if !status.is_empty() && status != prev {
+            log::info!("{:05}k cycles passed: {}", cycles / 1000, status);
+            status_changed(cycles, &status);
+        }
+        prev = status;
+
+        match blargg_status(cpu) {
+            BlarggStatus::NotStarted | BlarggStatus::Running => {}
+            BlarggStatus::ResetRequested => {
+                run_chunk(cpu, progress, RESET_DELAY)?;
+                cycles += RESET_DELAY;
+
+                cpu.resettable()
+                    .ok_or(TestOutcome::Skipped(Capability::Reset))?
+                    .reset();
+            }
+            BlarggStatus::Done(status) => {
+                return Ok(BlarggResult {
+                    status,
+                    text: read_status_string(cpu),
+                    cycles,
+                })
+            }
+        }
+    }
+
+    match all_instrs_status_code(cpu) {
+        Err(failure @ RomFailure::InvalidMagic { .. }) => Err(TestOutcome::Failed(failure)),
+        _ => Err(TestOutcome::TimedOut(format!(
+            "the test was still running:\n {}",
+            read_status_string(cpu)
+        ))),
+    }
+}
+
+/// Runs the rom for `cycles` cycles. The cpu getting stuck is fine when the rom is done,
+/// because blargg's roms end with an infinite loop.
+fn run_chunk<T: TestableCpu>(
+    cpu: &mut T,
+    progress: &TestProgress,
+    cycles: u64,
+) -> Result<(), TestOutcome> {
+    match progress.run(cpu, cycles as usize) {
+        Ok(()) => Ok(()),
+        Err(e) if e.is::<CpuStuck>() && matches!(blargg_status(cpu), BlarggStatus::Done(_)) => {
+            Ok(())
+        }
+        Err(e) => Err(cpu_error(e, all_instrs_status_code(cpu).err())),
+    }
+}
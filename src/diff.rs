@@ -0,0 +1,494 @@
+use crate::{
+    cpu_error, require, run_inline, run_on_thread, Capability, ExecutedInstruction, Isolation,
+    Registers, TestError, TestOutcome, TestProgress, TestRunner, TestableCpu,
+    MAX_INSTRUCTION_CYCLES,
+};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long [`diff_cpus`] may take by default
+const TIMEOUT: Duration = Duration::from_secs(600);
+
+/// The memory [`diff_cpus`] compares after every instruction: the 2 KiB of RAM of the NES
+const SAMPLED_MEMORY: std::ops::Range<u16> = 0x0000..0x0800;
+
+/// Where two cpus running the same rom first behaved differently, see [`diff_cpus`].
+/// Like the lines of `nestest.log`, the registers are those right before an instruction, when the cycle count
+/// is the cycle it starts in. A cpu that doesn't implement [`CpuStep`](crate::CpuStep) is considered to start an
+/// instruction whenever its program counter changes, so a divergence can be false when such a cpu moves its program
+/// counter in the middle of an instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The amount of instructions both cpus executed before their state differed
+    pub instructions: u64,
+    /// The registers of the first cpu before the last instruction, which the second cpu had as well
+    pub previous: Option<Registers>,
+    /// The registers of the first cpu. Its cycle count starts at 0 when the rom is loaded,
+    /// to make it comparable to the other cpu.
+    pub first: Registers,
+    /// The registers of the second cpu, with its cycle count starting at 0 as well
+    pub second: Registers,
+    /// The names of the registers that differ, like `A` and `CYC`
+    pub registers: Vec<&'static str>,
+    /// The memory locations that differ, with the value the first and the second cpu have there
+    pub memory: Vec<(u16, u8, u8)>,
+    /// The last instructions the first cpu executed, when it gives access to its registers after every tick
+    /// or executes one instruction at a time
+    pub first_history: Vec<ExecutedInstruction>,
+    /// The last instructions the second cpu executed
+    pub second_history: Vec<ExecutedInstruction>,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the cpus differ after {} instructions",
+            self.instructions
+        )?;
+        if !self.registers.is_empty() {
+            write!(f, " in {}", self.registers.join(", "))?;
+        }
+        match &self.previous {
+            Some(previous) => write!(f, "\n  previous: {previous}")?,
+            None => write!(f, "\n  previous: <none>")?,
+        }
+        write!(f, "\n  first:    {}", self.first)?;
+        write!(f, "\n  second:   {}", self.second)?;
+        for (address, first, second) in &self.memory {
+            write!(f, "\n  ${address:04X}: {first:02X} vs {second:02X}")?;
+        }
+        for (cpu, history) in [
+            ("first", &self.first_history),
+            ("second", &self.second_history),
+        ] {
+            if !history.is_empty() {
+                write!(f, "\nlast instructions of the {cpu} cpu:")?;
+                for instruction in history {
+                    write!(f, "\n  {instruction}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs `rom` on cpu `A` and cpu `B` side by side, for at most `budget` instructions, and returns the first
+/// instruction before which their registers, cycle counts or RAM differ. `None` is returned when they behave the same,
+/// which is useful to check that a refactor kept the behaviour of your CPU the same, or to compare it to
+/// [`ReferenceCpu`](crate::ReferenceCpu) when the `reference` feature is enabled.
+///
+/// Both cpus have to implement [`CpuRegisters`](crate::CpuRegisters), otherwise [`TestError::Skipped`] is returned.
+/// A cpu that implements [`CpuStep`](crate::CpuStep) executes one instruction per step. Other cpus are ticked, and
+/// every tick in which the program counter changes is considered to be the start of a new instruction,
+/// like [`run_nestest_log`](crate::run_nestest_log) does. A ticked cpu that moves its program counter as it fetches
+/// every byte of an instruction is reported to diverge right away, even when it is correct: implement `CpuStep` for it.
+/// Cycle counts are only compared when both cpus report them. The comparison ends early when both cpus stay at the same
+/// instruction, like at the loop most roms end in. The last instructions of both cpus are kept separately, and are
+/// part of the [`Divergence`]. When one of the cpus returns an error, the [`TestError`] shows its registers
+/// and last instructions.
+///
+/// The cpus run on their own thread, like the tests of [`run_tests`](crate::run_tests) do.
+/// Use [`TestRunner::diff_cpus`] to run them with other options.
+pub fn diff_cpus<A: TestableCpu, B: TestableCpu>(
+    rom: &[u8],
+    budget: u64,
+) -> Result<Option<Divergence>, TestError> {
+    TestRunner::default().diff_cpus::<A, B>(rom, budget)
+}
+
+/// Runs [`diff_cpus`] with the options of `runner`
+pub(crate) fn run_diff<A: TestableCpu, B: TestableCpu>(
+    runner: &TestRunner,
+    rom: &[u8],
+    budget: u64,
+) -> Result<Option<Divergence>, TestError> {
+    let rom = rom.to_vec();
+    let trace = runner.trace.clone();
+    let reported = Arc::new(Mutex::new(None));
+
+    let test = {
+        let reported = Arc::clone(&reported);
+        move |progress: &TestProgress| {
+            let mut first = A::get_cpu(&rom).map_err(|e| TestOutcome::LoadError(e.into()))?;
+            let mut second = B::get_cpu(&rom).map_err(|e| TestOutcome::LoadError(e.into()))?;
+            require(&mut first, Capability::Registers)?;
+            require(&mut second, Capability::Registers)?;
+
+            // the progress of the test is that of the first cpu, until the second one fails
+            let second_progress = TestProgress::new("cpu diff of the second cpu", trace.as_ref());
+            match lockstep(&mut first, &mut second, progress, &second_progress, budget) {
+                Ok(divergence) => {
+                    *reported.lock().unwrap() = Some(divergence);
+                    Ok(())
+                }
+                Err((Side::First, e)) => {
+                    *progress.registers.lock().unwrap() = Some(registers(&first));
+                    Err(cpu_error(e, None))
+                }
+                Err((Side::Second, e)) => {
+                    *progress.registers.lock().unwrap() = Some(registers(&second));
+                    std::mem::swap(
+                        &mut *progress.history.lock().unwrap(),
+                        &mut *second_progress.history.lock().unwrap(),
+                    );
+                    Err(cpu_error(e, None))
+                }
+            }
+        }
+    };
+
+    let name = "cpu diff";
+    let trace = runner.trace.as_ref();
+    let result = match runner.isolation {
+        Isolation::Inline => run_inline(name, test, trace),
+        Isolation::Thread | Isolation::Process => run_on_thread(
+            name,
+            test,
+            runner.timeout.unwrap_or(TIMEOUT),
+            runner.stack_size,
+            trace,
+        ),
+    };
+
+    result.into_result()?;
+    let divergence = reported.lock().unwrap().take();
+    Ok(divergence.expect("a diff that ran successfully reported its result"))
+}
+
+/// One of the cpus of [`diff_cpus`]
+enum Side {
+    First,
+    Second,
+}
+
+/// Executes instructions on both cpus until they differ, stop moving or ran `budget` instructions.
+/// Every cpu keeps track of its own progress. Returns the error of the cpu that failed.
+fn lockstep<A: TestableCpu, B: TestableCpu>(
+    first: &mut A,
+    second: &mut B,
+    first_progress: &TestProgress,
+    second_progress: &TestProgress,
+    budget: u64,
+) -> Result<Option<Divergence>, (Side, Box<dyn Error>)> {
+    let first_failed = |e| (Side::First, e);
+    let second_failed = |e| (Side::Second, e);
+    let mut first = Boundaries::new(first, first_progress).map_err(first_failed)?;
+    let mut second = Boundaries::new(second, second_progress).map_err(second_failed)?;
+    let mut previous = None;
+
+    for instructions in 0..=budget {
+        let different = differences(&first.registers, &second.registers);
+        let memory: Vec<_> = first
+            .memory
+            .iter()
+            .zip(&second.memory)
+            .zip(SAMPLED_MEMORY)
+            .filter(|((first, second), _)| first != second)
+            .map(|((&first, &second), address)| (address, first, second))
+            .collect();
+
+        if !different.is_empty() || !memory.is_empty() {
+            return Ok(Some(Divergence {
+                instructions,
+                previous,
+                first: first.registers,
+                second: second.registers,
+                registers: different,
+                memory,
+                first_history: first_progress.history.lock().unwrap().instructions(),
+                second_history: second_progress.history.lock().unwrap().instructions(),
+            }));
+        }
+
+        if instructions == budget {
+            break;
+        }
+
+        previous = Some(first.registers);
+        let first_moved = first.next().map_err(first_failed)?;
+        let second_moved = second.next().map_err(second_failed)?;
+        if !first_moved && !second_moved {
+            break;
+        }
+    }
+
+    Ok(None)
+}
+
+/// The state of a cpu right before an instruction
+struct Boundaries<'a, T> {
+    cpu: &'a mut T,
+    progress: &'a TestProgress,
+    /// The cycle count when the rom was loaded
+    start: Option<u64>,
+    registers: Registers,
+    memory: Vec<u8>,
+    /// The memory before the next instruction of a cpu that is ticked. Such a cpu executes an instruction in the first
+    /// tick of it, so its memory is read right after that tick, and its registers right before the next one.
+    next_memory: Option<Vec<u8>>,
+}
+
+impl<'a, T: TestableCpu> Boundaries<'a, T> {
+    fn new(cpu: &'a mut T, progress: &'a TestProgress) -> Result<Self, Box<dyn Error>> {
+        let start = registers(cpu).cycles;
+        let mut boundaries = Self {
+            registers: registers(cpu),
+            memory: sample_memory(cpu),
+            next_memory: None,
+            start,
+            cpu,
+            progress,
+        };
+
+        if boundaries.cpu.steppable().is_none() {
+            boundaries.tick_instruction()?;
+        }
+        boundaries.registers.cycles = since(boundaries.registers.cycles, start);
+
+        Ok(boundaries)
+    }
+
+    /// Executes one instruction, and returns whether the program counter changed
+    fn next(&mut self) -> Result<bool, Box<dyn Error>> {
+        let pc = self.registers.pc;
+
+        if self.cpu.steppable().is_some() {
+            // a stepped cpu isn't watched, so its instructions are kept here
+            let before = registers(self.cpu);
+            self.progress
+                .history
+                .lock()
+                .unwrap()
+                .before_step(self.cpu, before);
+            self.cpu.steppable().unwrap().step()?;
+            self.registers = registers(self.cpu);
+            self.memory = sample_memory(self.cpu);
+        } else {
+            self.memory = self.next_memory.take().unwrap();
+            self.tick_instruction()?;
+        }
+        self.registers.cycles = since(self.registers.cycles, self.start);
+
+        Ok(self.registers.pc != pc)
+    }
+
+    /// Ticks the cpu until its program counter changes, and keeps the registers from right before that tick
+    fn tick_instruction(&mut self) -> Result<(), Box<dyn Error>> {
+        for _ in 0..MAX_INSTRUCTION_CYCLES {
+            let before = registers(self.cpu);
+            self.progress.run(self.cpu, 1)?;
+
+            if registers(self.cpu).pc != before.pc {
+                self.registers = before;
+                self.next_memory = Some(sample_memory(self.cpu));
+                return Ok(());
+            }
+        }
+
+        // the cpu is stuck at an instruction, like a jump to itself, so the cycle it started in isn't known
+        self.registers = Registers {
+            cycles: None,
+            ..registers(self.cpu)
+        };
+        self.next_memory = Some(sample_memory(self.cpu));
+        Ok(())
+    }
+}
+
+fn registers<T: TestableCpu>(cpu: &T) -> Registers {
+    Registers::read(
+        cpu.registers()
+            .expect("both cpus give access to their registers"),
+    )
+}
+
+fn sample_memory<T: TestableCpu>(cpu: &T) -> Vec<u8> {
+    SAMPLED_MEMORY
+        .map(|address| cpu.memory_read(address))
+        .collect()
+}
+
+/// The amount of cycles since `start`, when the cpu reports its cycles
+fn since(cycles: Option<u64>, start: Option<u64>) -> Option<u64> {
+    Some(cycles?.wrapping_sub(start?))
+}
+
+/// The names of the registers that differ. The break flag and bit 5 of the status don't exist in the cpu,
+/// and cycles are only compared when both cpus report them.
+fn differences(first: &Registers, second: &Registers) -> Vec<&'static str> {
+    let cycles = match (first.cycles, second.cycles) {
+        (Some(first), Some(second)) => first != second,
+        _ => false,
+    };
+
+    [
+        ("PC", first.pc != second.pc),
+        ("A", first.a != second.a),
+        ("X", first.x != second.x),
+        ("Y", first.y != second.y),
+        ("P", (first.p ^ second.p) & !0x30 != 0),
+        ("SP", first.sp != second.sp),
+        ("CYC", cycles),
+    ]
+    .into_iter()
+    .filter_map(|(name, differs)| differs.then_some(name))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers(p: u8, cycles: Option<u64>) -> Registers {
+        Registers {
+            a: 0,
+            x: 0,
+            y: 0,
+            p,
+            sp: 0xFD,
+            pc: 0xC000,
+            cycles,
+        }
+    }
+
+    #[test]
+    fn differences_ignore_bits_that_dont_exist() {
+        assert!(differences(&registers(0x24, None), &registers(0x34, None)).is_empty());
+        assert_eq!(
+            differences(&registers(0x24, None), &registers(0x25, None)),
+            ["P"]
+        );
+    }
+
+    #[test]
+    fn cycles_are_only_compared_when_both_cpus_count_them() {
+        assert!(differences(&registers(0x24, Some(7)), &registers(0x24, None)).is_empty());
+        assert_eq!(
+            differences(&registers(0x24, Some(7)), &registers(0x24, Some(8))),
+            ["CYC"]
+        );
+    }
+
+    #[cfg(feature = "reference")]
+    mod reference {
+        use super::super::*;
+        use crate::{CpuRegisters, ReferenceCpu, ROM_NESTEST, ROM_NROM_TEST};
+        use tudelft_nes_ppu::{Cpu, Ppu};
+
+        /// The reference cpu, which is ticked instead of stepped. After `FLIP_AT` ticks its accumulator
+        /// is flipped, or it returns an error when `FAIL` is set.
+        struct Ticked<const FAIL: bool>(ReferenceCpu, u64);
+
+        const FLIP_AT: u64 = 1000;
+
+        impl<const FAIL: bool> Cpu for Ticked<FAIL> {
+            fn tick(&mut self, ppu: &mut Ppu) -> Result<(), Box<dyn Error>> {
+                self.1 += 1;
+                if self.1 == FLIP_AT {
+                    if FAIL {
+                        return Err("broken".into());
+                    }
+                    let a = self.0.accumulator();
+                    self.0.registers_mut().unwrap().set_accumulator(a ^ 1);
+                }
+                self.0.tick(ppu)
+            }
+
+            fn ppu_read_chr_rom(&self, offset: u16) -> u8 {
+                self.0.ppu_read_chr_rom(offset)
+            }
+
+            fn non_maskable_interrupt(&mut self) {
+                self.0.non_maskable_interrupt()
+            }
+        }
+
+        impl<const FAIL: bool> TestableCpu for Ticked<FAIL> {
+            fn get_cpu(rom: &[u8]) -> Result<Self, Box<dyn Error>> {
+                Ok(Self(ReferenceCpu::new(rom)?, 0))
+            }
+
+            fn memory_read(&self, address: u16) -> u8 {
+                self.0.memory_read(address)
+            }
+
+            fn registers(&self) -> Option<&dyn CpuRegisters> {
+                self.0.registers()
+            }
+        }
+
+        #[test]
+        fn same_cpus_dont_diverge() {
+            assert_eq!(
+                diff_cpus::<ReferenceCpu, Ticked<false>>(ROM_NROM_TEST, 1000),
+                Ok(None)
+            );
+        }
+
+        #[test]
+        fn runners_run_diffs_with_their_options() {
+            let inline = TestRunner {
+                isolation: Isolation::Inline,
+                ..TestRunner::default()
+            };
+            assert_eq!(
+                inline.diff_cpus::<ReferenceCpu, Ticked<false>>(ROM_NROM_TEST, 1000),
+                Ok(None)
+            );
+
+            let impatient = TestRunner {
+                timeout: Some(Duration::from_millis(1)),
+                ..TestRunner::default()
+            };
+            let error = impatient
+                .diff_cpus::<ReferenceCpu, ReferenceCpu>(ROM_NESTEST, 200_000)
+                .unwrap_err();
+            assert!(
+                matches!(&error, TestError::TimedOut { test, .. } if test == "cpu diff"),
+                "expected a time out, not {error}"
+            );
+        }
+
+        #[test]
+        fn divergences_have_the_history_of_both_cpus() {
+            let divergence = diff_cpus::<ReferenceCpu, Ticked<false>>(ROM_NESTEST, 10_000)
+                .unwrap()
+                .unwrap();
+
+            assert!(divergence.registers.contains(&"A"));
+            assert_eq!(divergence.first_history.len(), 50);
+            assert_eq!(divergence.second_history.len(), 50);
+            // the stepped cpu keeps an instruction before executing it, the ticked one after its first tick
+            let last = |history: &[ExecutedInstruction]| history.last().unwrap().registers.pc;
+            assert_eq!(
+                last(&divergence.first_history),
+                divergence.previous.unwrap().pc
+            );
+            assert!(divergence
+                .to_string()
+                .contains("\nlast instructions of the second cpu:\n"));
+        }
+
+        #[test]
+        fn errors_show_the_cpu_that_failed() {
+            let error = diff_cpus::<ReferenceCpu, Ticked<true>>(ROM_NESTEST, 10_000).unwrap_err();
+
+            let TestError::Cpu {
+                source,
+                registers,
+                history,
+                ..
+            } = error
+            else {
+                panic!("expected a cpu error, not {error}");
+            };
+            assert_eq!(source.to_string(), "broken");
+            assert!(registers.is_some());
+            assert!(!history.is_empty());
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use crate::{AddressingMode, OpcodeClass, TestableCpu, OPCODES};
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+
+/// The registers of the PPU, APU and controllers. Reading them can change them, like reading $2002 clears
+/// the vertical blank flag, so the disassembler doesn't read them.
+const IO_REGISTERS: RangeInclusive<u16> = 0x2000..=0x401F;
+
+/// An instruction decoded from memory, which formats like the disassembly column of `nestest.log`:
+/// `LDA ($89),Y = 0300 @ 0300 = 89`. Unofficial instructions start with a `*`, like `*NOP $04 = 00`.
+///
+/// Instructions that are [read](Self::read) from a cpu that gives access to its registers show the values in memory
+/// they access, like `nestest.log` does. These are read through [`TestableCpu::memory_read`], right when the instruction
+/// is disassembled, so they are only correct before the instruction executes. The registers of the PPU, APU
+/// and controllers at $2000-$401F aren't read, as reading them can change them, so only their address is shown.
+/// Instructions that are [decoded](Self::decode) from their bytes only show their operand, like `LDA ($89),Y`.
+///
+/// ```
+/// use tudelft_nes_test::{AddressingMode, Disassembly};
+///
+/// let jump = Disassembly::decode(0xC000, &[0x4C, 0xF5, 0xC5]);
+/// assert_eq!(jump.to_string(), "JMP $C5F5");
+/// assert_eq!(jump.bytes(), &[0x4C, 0xF5, 0xC5]);
+///
+/// let branch = Disassembly::decode(0xC72F, &[0xB0, 0x04]);
+/// assert_eq!(branch.to_string(), "BCS $C735");
+/// assert_eq!(branch.mode, AddressingMode::Relative);
+///
+/// let nop = Disassembly::decode(0xC6BD, &[0x04, 0xA9]);
+/// assert_eq!(nop.mnemonic, "NOP");
+/// assert!(!nop.official);
+/// assert_eq!(nop.to_string(), "*NOP $A9");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disassembly {
+    /// The address of the opcode
+    pub address: u16,
+    /// The name of the instruction, like `LDA`. Unofficial instructions don't start with a `*` here.
+    pub mnemonic: &'static str,
+    /// Whether the instruction is one of the 151 official instructions of the 6502
+    pub official: bool,
+    /// How the instruction gets its operand
+    pub mode: AddressingMode,
+    bytes: [u8; 3],
+    length: usize,
+    /// The memory the instruction accesses, when it was read from a cpu
+    accessed: Option<Accessed>,
+}
+
+/// The memory an instruction accesses, as `nestest.log` shows it after the operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Accessed {
+    /// The address in the zero page plus X for `(indirect,X)`,
+    /// and the address read from the zero page for `(indirect),Y`
+    pointer: u16,
+    /// The address the operand is at, or the address an indirect jump goes to
+    address: u16,
+    /// The operand, unless it is one of the [`IO_REGISTERS`]
+    value: Option<u8>,
+}
+
+impl Disassembly {
+    /// Decodes the instruction in `bytes`, which starts at `address`. Bytes that are missing are treated as 0,
+    /// and bytes after the instruction are ignored.
+    pub fn decode(address: u16, bytes: &[u8]) -> Self {
+        let opcode = &OPCODES[usize::from(bytes.first().copied().unwrap_or_default())];
+        let length = usize::from(opcode.length);
+
+        let mut instruction = [0; 3];
+        for (byte, &value) in instruction.iter_mut().zip(bytes).take(length) {
+            *byte = value;
+        }
+
+        Self {
+            address,
+            mnemonic: opcode.mnemonic,
+            official: opcode.class == OpcodeClass::Official,
+            mode: opcode.mode,
+            bytes: instruction,
+            length,
+            accessed: None,
+        }
+    }
+
+    /// Reads the instruction at `address` from the memory of `cpu`. When the cpu gives access to its registers,
+    /// the memory the instruction accesses with the current X and Y registers is read as well.
+    pub fn read<T: TestableCpu>(cpu: &T, address: u16) -> Self {
+        let opcode = cpu.memory_read(address);
+        let length = OPCODES[usize::from(opcode)].length;
+        let bytes: Vec<u8> = (0..length)
+            .map(|i| cpu.memory_read(address.wrapping_add(i)))
+            .collect();
+
+        let mut instruction = Self::decode(address, &bytes);
+        if let Some(registers) = cpu.registers() {
+            instruction.accessed =
+                instruction.accessed(cpu, registers.x_register(), registers.y_register());
+        }
+        instruction
+    }
+
+    /// The opcode and the bytes of its operand
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.length]
+    }
+
+    /// The address of the instruction after this one
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.length as u16)
+    }
+
+    /// The operand as a byte, or as a little endian address
+    fn operand(&self) -> u16 {
+        u16::from_le_bytes([self.bytes[1], self.bytes[2]])
+    }
+
+    /// Reads the memory the instruction accesses, like the cpu would with registers `x` and `y`
+    fn accessed<T: TestableCpu>(&self, cpu: &T, x: u8, y: u8) -> Option<Accessed> {
+        // a pointer in the zero page, of which the high byte wraps around in the zero page as well
+        let zero_page_pointer = |address: u8| {
+            u16::from_le_bytes([
+                cpu.memory_read(u16::from(address)),
+                cpu.memory_read(u16::from(address.wrapping_add(1))),
+            ])
+        };
+        let operand = self.operand();
+        let low = self.bytes[1];
+
+        let (pointer, address) = match self.mode {
+            AddressingMode::ZeroPage => (0, u16::from(low)),
+            AddressingMode::ZeroPageX => (0, u16::from(low.wrapping_add(x))),
+            AddressingMode::ZeroPageY => (0, u16::from(low.wrapping_add(y))),
+            // jumps don't access the memory at their operand
+            AddressingMode::Absolute if matches!(self.bytes[0], 0x20 | 0x4C) => return None,
+            AddressingMode::Absolute => (0, operand),
+            AddressingMode::AbsoluteX => (0, operand.wrapping_add(u16::from(x))),
+            AddressingMode::AbsoluteY => (0, operand.wrapping_add(u16::from(y))),
+            AddressingMode::Indirect => {
+                if IO_REGISTERS.contains(&operand) {
+                    return None;
+                }
+                // the high byte of the target is read from the same page as the low byte
+                let high = (operand & 0xFF00) | (operand.wrapping_add(1) & 0x00FF);
+                let target = u16::from_le_bytes([cpu.memory_read(operand), cpu.memory_read(high)]);
+                (operand, target)
+            }
+            AddressingMode::IndirectX => {
+                let pointer = low.wrapping_add(x);
+                (u16::from(pointer), zero_page_pointer(pointer))
+            }
+            AddressingMode::IndirectY => {
+                let pointer = zero_page_pointer(low);
+                (pointer, pointer.wrapping_add(u16::from(y)))
+            }
+            AddressingMode::Implied
+            | AddressingMode::Accumulator
+            | AddressingMode::Immediate
+            | AddressingMode::Relative => return None,
+        };
+
+        Some(Accessed {
+            pointer,
+            address,
+            value: (!IO_REGISTERS.contains(&address)).then(|| cpu.memory_read(address)),
+        })
+    }
+}
+
+impl Display for Disassembly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !self.official {
+            write!(f, "*")?;
+        }
+        write!(f, "{}", self.mnemonic)?;
+
+        let low = self.bytes[1];
+        let operand = self.operand();
+        match self.mode {
+            AddressingMode::Implied => {}
+            AddressingMode::Accumulator => write!(f, " A")?,
+            AddressingMode::Immediate => write!(f, " #${low:02X}")?,
+            AddressingMode::ZeroPage => write!(f, " ${low:02X}")?,
+            AddressingMode::ZeroPageX => write!(f, " ${low:02X},X")?,
+            AddressingMode::ZeroPageY => write!(f, " ${low:02X},Y")?,
+            AddressingMode::Absolute => write!(f, " ${operand:04X}")?,
+            AddressingMode::AbsoluteX => write!(f, " ${operand:04X},X")?,
+            AddressingMode::AbsoluteY => write!(f, " ${operand:04X},Y")?,
+            AddressingMode::Indirect => write!(f, " (${operand:04X})")?,
+            AddressingMode::IndirectX => write!(f, " (${low:02X},X)")?,
+            AddressingMode::IndirectY => write!(f, " (${low:02X}),Y")?,
+            AddressingMode::Relative => {
+                let target = self.next_address().wrapping_add(low as i8 as u16);
+                write!(f, " ${target:04X}")?
+            }
+        }
+
+        let Some(Accessed {
+            pointer,
+            address,
+            value,
+        }) = self.accessed
+        else {
+            return Ok(());
+        };
+        match self.mode {
+            AddressingMode::ZeroPageX | AddressingMode::ZeroPageY => write!(f, " @ {address:02X}")?,
+            AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => write!(f, " @ {address:04X}")?,
+            AddressingMode::Indirect => return write!(f, " = {address:04X}"),
+            AddressingMode::IndirectX => write!(f, " @ {pointer:02X} = {address:04X}")?,
+            AddressingMode::IndirectY => write!(f, " = {pointer:04X} @ {address:04X}")?,
+            _ => {}
+        }
+        match value {
+            Some(value) => write!(f, " = {value:02X}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Pads the disassembly `text` to the column of `nestest.log`, which starts with the `*` of unofficial instructions
+/// and is `width` characters wide after it
+pub(crate) fn column(text: &str, width: usize) -> String {
+    match text.strip_prefix('*') {
+        Some(unofficial) => format!("*{unofficial:<width$}"),
+        None => format!(" {text:<width$}"),
+    }
+}
+
+/// Reads `count` instructions from the memory of `cpu`, one after the other, starting at `address`.
+/// Like [`Disassembly::read`], the values in memory the instructions access are those the cpu would access right now.
+///
+/// As the 6502 has no way to tell code from data, bytes that aren't code are disassembled as well.
+pub fn disassemble<T: TestableCpu>(cpu: &T, address: u16, count: usize) -> Vec<Disassembly> {
+    let mut address = address;
+    (0..count)
+        .map(|_| {
+            let instruction = Disassembly::read(cpu, address);
+            address = instruction.next_address();
+            instruction
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CpuRegisters;
+    use std::cell::RefCell;
+    use std::error::Error;
+    use tudelft_nes_ppu::{Cpu, Ppu};
+
+    /// 64 KiB of memory with X and Y registers, which remembers the addresses that were read
+    struct Memory {
+        memory: Vec<u8>,
+        x: u8,
+        y: u8,
+        read: RefCell<Vec<u16>>,
+    }
+
+    impl Memory {
+        fn new(x: u8, y: u8, writes: &[(u16, &[u8])]) -> Self {
+            let mut memory = vec![0; 0x10000];
+            for &(address, bytes) in writes {
+                let start = usize::from(address);
+                memory[start..start + bytes.len()].copy_from_slice(bytes);
+            }
+
+            Self {
+                memory,
+                x,
+                y,
+                read: RefCell::default(),
+            }
+        }
+
+        fn disassemble(&self, address: u16) -> String {
+            Disassembly::read(self, address).to_string()
+        }
+    }
+
+    impl Cpu for Memory {
+        fn tick(&mut self, _ppu: &mut Ppu) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn ppu_read_chr_rom(&self, _offset: u16) -> u8 {
+            0
+        }
+
+        fn non_maskable_interrupt(&mut self) {}
+    }
+
+    impl TestableCpu for Memory {
+        fn get_cpu(_rom: &[u8]) -> Result<Self, Box<dyn Error>> {
+            Ok(Self::new(0, 0, &[]))
+        }
+
+        fn memory_read(&self, address: u16) -> u8 {
+            self.read.borrow_mut().push(address);
+            self.memory[usize::from(address)]
+        }
+
+        fn registers(&self) -> Option<&dyn CpuRegisters> {
+            Some(self)
+        }
+    }
+
+    impl CpuRegisters for Memory {
+        fn accumulator(&self) -> u8 {
+            0
+        }
+
+        fn x_register(&self) -> u8 {
+            self.x
+        }
+
+        fn y_register(&self) -> u8 {
+            self.y
+        }
+
+        fn status(&self) -> u8 {
+            0x24
+        }
+
+        fn stack_pointer(&self) -> u8 {
+            0xFD
+        }
+
+        fn program_counter(&self) -> u16 {
+            0xC000
+        }
+    }
+
+    #[test]
+    fn accessed_memory_like_nestest_log() {
+        let cpu = Memory::new(
+            0x65,
+            0x00,
+            &[
+                (0x0089, &[0x00, 0x03]),
+                (0x0300, &[0x89]),
+                (0x0647, &[0x5A]),
+                (0x04FF, &[0x34]),
+                (0x0400, &[0x12]),
+                (0x0500, &[0x99]),
+                (0xD922, &[0xB1, 0x89]),
+                (0xC000, &[0x8D, 0x47, 0x06]),
+                (0xC003, &[0x6C, 0xFF, 0x04]),
+                (0xC006, &[0xB5, 0xFF]),
+            ],
+        );
+
+        assert_eq!(cpu.disassemble(0xD922), "LDA ($89),Y = 0300 @ 0300 = 89");
+        assert_eq!(cpu.disassemble(0xC000), "STA $0647 = 5A");
+        // the high byte of the target is read from $0400, not $0500
+        assert_eq!(cpu.disassemble(0xC003), "JMP ($04FF) = 1234");
+        assert_eq!(cpu.disassemble(0xC006), "LDA $FF,X @ 64 = 00");
+    }
+
+    #[test]
+    fn io_registers_are_not_read() {
+        let cpu = Memory::new(
+            0x10,
+            0x07,
+            &[
+                (0x0010, &[0x00, 0x20]),
+                (0xC000, &[0xAD, 0x02, 0x20]),
+                (0xC003, &[0xBD, 0xF8, 0x3F]),
+                (0xC006, &[0x91, 0x10]),
+                (0xC008, &[0x6C, 0x00, 0x20]),
+                (0xC00B, &[0xA1, 0x00]),
+            ],
+        );
+
+        assert_eq!(cpu.disassemble(0xC000), "LDA $2002");
+        assert_eq!(cpu.disassemble(0xC003), "LDA $3FF8,X @ 4008");
+        assert_eq!(cpu.disassemble(0xC006), "STA ($10),Y = 2000 @ 2007");
+        assert_eq!(cpu.disassemble(0xC008), "JMP ($2000)");
+        assert_eq!(cpu.disassemble(0xC00B), "LDA ($00,X) @ 10 = 2000");
+        assert!(cpu
+            .read
+            .borrow()
+            .iter()
+            .all(|address| !IO_REGISTERS.contains(address)));
+
+        // the cartridge starts right after the registers
+        let cartridge = Memory::new(0, 0, &[(0xC000, &[0xAD, 0x20, 0x40]), (0x4020, &[0x12])]);
+        assert_eq!(cartridge.disassemble(0xC000), "LDA $4020 = 12");
+    }
+
+    #[test]
+    fn instructions_follow_each_other() {
+        let cpu = Memory::new(0, 0, &[(0xC000, &[0xA9, 0x01, 0xEA, 0x4C, 0x00, 0xC0])]);
+
+        let listed: Vec<_> = disassemble(&cpu, 0xC000, 3)
+            .iter()
+            .map(|instruction| (instruction.address, instruction.to_string()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (0xC000, "LDA #$01".to_string()),
+                (0xC002, "NOP".to_string()),
+                (0xC003, "JMP $C000".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use crate::history::hang_location;
+use crate::nestest::LogLine;
+use crate::timing::timing_table;
+use crate::{
+    Capability, ExecutedInstruction, NestestFailure, Registers, SubtestResult, TimingMismatch,
+    OPCODES,
+};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Why a test didn't pass
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum TestError {
+    /// [`get_cpu`](crate::TestableCpu::get_cpu) returned an error while loading the rom of the test
+    #[error("cpu failed to load the rom of test {test}: {source}")]
+    LoadRom {
+        /// The name of the test
+        test: String,
+        /// The messages of the error returned by `get_cpu`. Tests run on their own thread or in a child process,
+        /// and a `Box<dyn Error>` can't be sent from there, so the original error isn't available.
+        source: CpuError,
+    },
+    /// The cpu returned an error while it was running the test
+    #[error(
+        "cpu failed while running test {test} with custom error message {source}{}{}{}",
+        possible_failure(failure),
+        registers_dump(registers),
+        history_dump(history)
+    )]
+    Cpu {
+        /// The name of the test
+        test: String,
+        /// The messages of the error returned by the cpu. Like for [`LoadRom`](Self::LoadRom),
+        /// the original error isn't available.
+        source: CpuError,
+        /// What the test rom reported at the time of the error, which is possibly what caused it
+        failure: Option<RomFailure>,
+        /// The registers of the cpu after the error
+        registers: Option<Registers>,
+        /// The last instructions the cpu executed before the error
+        history: Vec<ExecutedInstruction>,
+    },
+    /// The test rom reported that the cpu did something wrong
+    #[error(
+        "cpu didn't pass test {test}: '{failure}'{}{}",
+        registers_dump(registers),
+        history_dump(history)
+    )]
+    Failed {
+        /// The name of the test
+        test: String,
+        /// What the test rom reported
+        failure: RomFailure,
+        /// The registers of the cpu when the test ended
+        registers: Option<Registers>,
+        /// The last instructions the cpu executed before the test ended
+        history: Vec<ExecutedInstruction>,
+    },
+    /// The cpu implementation panicked while running the test
+    #[error(
+        "cpu implementation panicked while running test {test}: {message}{}",
+        history_dump(history)
+    )]
+    Panicked {
+        /// The name of the test
+        test: String,
+        /// The message the cpu panicked with
+        message: String,
+        /// The last instructions the cpu executed before it panicked
+        history: Vec<ExecutedInstruction>,
+    },
+    /// The test didn't finish within the amount of cycles or the time it was given
+    #[error(
+        "cpu timed out while running test {test}: {message}{}{}",
+        registers_dump(registers),
+        history_dump(history)
+    )]
+    TimedOut {
+        /// The name of the test
+        test: String,
+        /// What the test was doing when it timed out
+        message: String,
+        /// The registers of the cpu when the test timed out
+        registers: Option<Registers>,
+        /// The last instructions the cpu executed before the test timed out
+        history: Vec<ExecutedInstruction>,
+    },
+    /// The cpu kept executing the same few instructions for a long time
+    #[error(
+        "CPU is stuck at {} while running test {test}{}{}",
+        hang_location(*pc, history.last()),
+        registers_dump(registers),
+        history_dump(history)
+    )]
+    Stuck {
+        /// The name of the test
+        test: String,
+        /// The program counter the cpu got stuck at
+        pc: u16,
+        /// The registers of the cpu when it got stuck
+        registers: Option<Registers>,
+        /// The last instructions the cpu executed before it got stuck
+        history: Vec<ExecutedInstruction>,
+    },
+    /// The thread running the test overflowed its stack
+    #[error("the thread running test {test} overflowed its stack, try a larger `TestRunner::stack_size`")]
+    StackOverflow {
+        /// The name of the test
+        test: String,
+    },
+    /// The process running the test crashed or had to be killed, see [`Isolation::Process`](crate::Isolation::Process)
+    #[error("the process running test {test} crashed: {message}")]
+    Crashed {
+        /// The name of the test
+        test: String,
+        /// How the process ended
+        message: String,
+    },
+    /// The test needs a capability the cpu doesn't have
+    #[error("test {test} was skipped: it needs {capability}")]
+    Skipped {
+        /// The name of the test
+        test: String,
+        /// The capability the test needs
+        capability: Capability,
+    },
+}
+
+fn possible_failure(failure: &Option<RomFailure>) -> String {
+    failure
+        .as_ref()
+        .map(|failure| format!(", possibly due to a test that didn't pass: '{failure}'"))
+        .unwrap_or_default()
+}
+
+/// Describes the timing of the previous instruction when the cycle counts differ, as that instruction took
+/// the wrong amount of cycles
+fn previous_timing(differences: &[&str], previous: &Option<String>) -> String {
+    let opcode = previous
+        .as_deref()
+        .and_then(LogLine::parse)
+        .and_then(|line| line.bytes.first().copied());
+
+    match opcode {
+        Some(opcode) if differences.contains(&"CYC") => {
+            let opcode = &OPCODES[usize::from(opcode)];
+            format!(
+                "\n  the previous instruction, {opcode}, takes {}",
+                opcode.timing()
+            )
+        }
+        _ => String::new(),
+    }
+}
+
+fn instr_test_summary(status: u8, subtests: &[SubtestResult], text: &str) -> String {
+    let Some(failed) = subtests.iter().find(|subtest| !subtest.passed) else {
+        return format!("exited with status {status}:\n {text}");
+    };
+
+    let mut summary = format!("exited with status {status} in {}:", failed.name);
+    for instruction in &failed.failures {
+        summary += &format!("\n {:02X} {}", instruction.opcode, instruction.mnemonic);
+        if let Some(mode) = &instruction.mode {
+            summary += &format!(" {mode}");
+        }
+        if let Some(message) = &instruction.message {
+            summary += &format!(": {message}");
+        }
+    }
+    for message in &failed.messages {
+        summary += &format!("\n {message}");
+    }
+
+    summary
+}
+
+fn registers_dump(registers: &Option<Registers>) -> String {
+    registers
+        .map(|registers| format!("\nregisters: {registers}"))
+        .unwrap_or_default()
+}
+
+fn history_dump(history: &[ExecutedInstruction]) -> String {
+    if history.is_empty() {
+        return String::new();
+    }
+
+    let mut dump = "\nlast instructions:".to_string();
+    for instruction in history {
+        dump += &format!("\n  {instruction}");
+    }
+    dump
+}
+
+/// An error returned by the cpu, either from [`get_cpu`](crate::TestableCpu::get_cpu) or while it was running.
+/// It shows the message of the original error, and its [`source`](Error::source) has the messages of the sources
+/// of the original error.
+#[derive(Debug, Clone)]
+pub struct CpuError(Arc<Message>);
+
+impl CpuError {
+    /// The message of the original error, followed by the messages of its sources
+    pub fn messages(&self) -> Vec<String> {
+        let mut messages = vec![self.0.message.clone()];
+        let mut source = self.0.source.as_deref();
+        while let Some(e) = source {
+            messages.push(e.message.clone());
+            source = e.source.as_deref();
+        }
+        messages
+    }
+
+    /// Replaces an error with its `messages`, see [`CpuError::messages`]
+    pub(crate) fn from_messages(messages: Vec<String>) -> Self {
+        let message = messages
+            .into_iter()
+            .rev()
+            .fold(None, |source, message| {
+                Some(Message {
+                    message,
+                    source: source.map(Box::new),
+                })
+            })
+            .unwrap_or_else(|| Message {
+                message: String::new(),
+                source: None,
+            });
+
+        Self(Arc::new(message))
+    }
+}
+
+impl From<Box<dyn Error>> for CpuError {
+    fn from(error: Box<dyn Error>) -> Self {
+        let mut messages = vec![error.to_string()];
+        let mut source = error.source();
+        while let Some(e) = source {
+            messages.push(e.to_string());
+            source = e.source();
+        }
+
+        Self::from_messages(messages)
+    }
+}
+
+impl Display for CpuError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for CpuError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Errors are equal when they and their sources have the same messages
+impl PartialEq for CpuError {
+    fn eq(&self, other: &Self) -> bool {
+        self.messages() == other.messages()
+    }
+}
+
+impl Eq for CpuError {}
+
+/// An error that was replaced by its message, see [`CpuError`]
+#[derive(Debug)]
+pub(crate) struct Message {
+    message: String,
+    source: Option<Box<Message>>,
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for Message {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn Error + 'static))
+    }
+}
+
+/// What a test rom reported when the cpu didn't pass it
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum RomFailure {
+    /// nestest left an error code at $03 (official instructions), $11 (unofficial instructions) or $02 (RRA)
+    #[error("{description}")]
+    Nestest {
+        /// The value at $03
+        official: u8,
+        /// The value at $11
+        unofficial: u8,
+        /// The value at $02
+        rra: u8,
+        /// What the error codes mean according to nestest.txt
+        description: String,
+        /// The error codes that aren't 0, decoded
+        failures: Vec<NestestFailure>,
+    },
+    /// A rom using blargg's $6000 protocol wrote a status code other than 0
+    #[error("exited with status {status}:\n {text}")]
+    Blargg {
+        /// The status code at $6000
+        status: u8,
+        /// The text starting at $6004
+        text: String,
+    },
+    /// One of the roms of instr_test-v5 reported that the cpu got some instructions wrong
+    #[error("{}", instr_test_summary(*status, subtests, text))]
+    InstrTest {
+        /// The status code at $6000
+        status: u8,
+        /// The roms that ran, where only the last one can have failed
+        subtests: Vec<SubtestResult>,
+        /// The text starting at $6004
+        text: String,
+    },
+    /// The signature blargg's roms write to $6001-$6003 was wrong
+    #[error("invalid magic sequence: {:x}{:x}{:x}. the test output was corrupted", .magic[0], .magic[1], .magic[2])]
+    InvalidMagic {
+        /// The bytes at $6001-$6003
+        magic: [u8; 3],
+    },
+    /// The cpu didn't do what it should when it was reset
+    #[error("{description}")]
+    Reset {
+        /// What the cpu did wrong
+        description: String,
+    },
+    /// The cpu didn't do what it should when it was interrupted
+    #[error("{description}")]
+    Interrupts {
+        /// What the cpu did wrong
+        description: String,
+    },
+    /// A memory location had the wrong value after running the test
+    #[error(
+        "memory location {address:#x} is wrong: expected {expected:#04x}, found {actual:#04x}"
+    )]
+    Memory {
+        /// The address of the memory location
+        address: u16,
+        /// The value the memory location should have had
+        expected: u8,
+        /// The value the memory location had
+        actual: u8,
+    },
+    /// The state of the cpu differed from [`NESTEST_LOG`](crate::NESTEST_LOG)
+    #[error("cpu state differs from line {line} of nestest.log in {}\n  previous: {}\n  expected: {expected}\n  actual:   {actual}{}", .differences.join(", "), .previous.as_deref().unwrap_or("<none>"), previous_timing(differences, previous))]
+    LogMismatch {
+        /// The line number in the log, starting at 1
+        line: usize,
+        /// The names of the fields that differ
+        differences: Vec<&'static str>,
+        /// The line before the one that differs
+        previous: Option<String>,
+        /// The line in the log
+        expected: String,
+        /// The state of the cpu, formatted like the line in the log
+        actual: String,
+    },
+    /// Instructions took a different amount of cycles than they do on a NES, see
+    /// [`TestSelector::INSTR_TIMING`](crate::TestSelector::INSTR_TIMING)
+    #[error("{}", timing_table(mismatches))]
+    Timing {
+        /// The instructions that took the wrong amount of cycles, ordered by opcode
+        mismatches: Vec<TimingMismatch>,
+    },
+    /// The cpu stopped executing instructions while comparing its state to [`NESTEST_LOG`](crate::NESTEST_LOG)
+    #[error("cpu got stuck at ${pc:04X} while executing line {line} of nestest.log\n  expected: {expected}")]
+    LogStuck {
+        /// The line number in the log, starting at 1
+        line: usize,
+        /// The program counter the cpu got stuck at
+        pc: u16,
+        /// The line in the log
+        expected: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Error)]
+    #[error("the rom has no prg banks")]
+    struct NoBanks;
+
+    #[derive(Debug, Error)]
+    #[error("couldn't load the rom")]
+    struct LoadFailed(#[source] NoBanks);
+
+    #[test]
+    fn keeps_the_messages_of_errors() {
+        let original: Box<dyn Error> = Box::new(LoadFailed(NoBanks));
+        let error = CpuError::from(original);
+
+        assert_eq!(error.to_string(), "couldn't load the rom");
+        assert_eq!(
+            error.source().map(ToString::to_string).as_deref(),
+            Some("the rom has no prg banks")
+        );
+        assert!(error.source().unwrap().source().is_none());
+        assert_eq!(
+            error,
+            CpuError::from_messages(vec![
+                "couldn't load the rom".to_string(),
+                "the rom has no prg banks".to_string(),
+            ]),
+            "errors with the same messages are equal"
+        );
+    }
+
+    #[test]
+    fn test_errors_have_the_cpu_error_as_source() {
+        let error = TestError::LoadRom {
+            test: "nestest".to_string(),
+            source: CpuError::from(Box::new(NoBanks) as Box<dyn Error>),
+        };
+
+        let source = error.source().unwrap().downcast_ref::<CpuError>().unwrap();
+        assert_eq!(source.messages(), ["the rom has no prg banks"]);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::disassembler::column;
+use crate::{CpuRegisters, Disassembly, Registers, TestableCpu, OPCODES};
+use std::fmt::{Display, Formatter};
+
+/// The amount of instructions that are kept, see [`TestResult::history`](crate::TestResult::history)
+const HISTORY_LENGTH: usize = 50;
+/// The amount of cycles the cpu spends on reset before it executes the first instruction
+const RESET_CYCLES: u64 = 7;
+
+/// An instruction your CPU executed, with the state it was in right before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutedInstruction {
+    /// The registers right before the instruction, where the program counter is the address of the instruction
+    pub registers: Registers,
+    /// The opcode and the bytes of its operand
+    pub bytes: Vec<u8>,
+}
+
+impl ExecutedInstruction {
+    /// The instruction, decoded from its bytes. The values in memory it accessed aren't known anymore.
+    pub fn disassembly(&self) -> Disassembly {
+        Disassembly::decode(self.registers.pc, &self.bytes)
+    }
+}
+
+impl Display for ExecutedInstruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let bytes = self
+            .bytes
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let Registers {
+            a,
+            x,
+            y,
+            p,
+            sp,
+            pc,
+            cycles,
+        } = self.registers;
+
+        write!(
+            f,
+            "{pc:04X}  {bytes:<8} {} A:{a:02X} X:{x:02X} Y:{y:02X} P:{p:02X} SP:{sp:02X}",
+            column(&self.disassembly().to_string(), 11)
+        )?;
+        if let Some(cycles) = cycles {
+            write!(f, " CYC:{cycles}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps the last [`HISTORY_LENGTH`] instructions the cpu executed. Like [`run_nestest_log`](crate::run_nestest_log),
+/// every tick in which the program counter changes is considered to be the start of a new instruction.
+/// As this happens for every tick of a test, the instructions are kept in a ring buffer that doesn't allocate,
+/// and only the program counter and cycles are read in the ticks in which the cpu waits for an instruction to finish.
+#[derive(Debug)]
+pub(crate) struct History {
+    instructions: [Kept; HISTORY_LENGTH],
+    /// The amount of instructions that were kept, of which the last one is at `(kept - 1) % HISTORY_LENGTH`
+    kept: usize,
+    /// The registers after the last tick in which the program counter changed, with the cycles after the last tick
+    previous: Option<Registers>,
+    /// The amount of ticks since the rom was loaded, starting at the cycles a reset takes
+    ticks: u64,
+}
+
+/// An [`ExecutedInstruction`] in the [`History`]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Kept {
+    pub(crate) registers: Registers,
+    bytes: [u8; 3],
+    length: usize,
+}
+
+impl Kept {
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.length]
+    }
+
+    fn executed(&self) -> ExecutedInstruction {
+        ExecutedInstruction {
+            registers: self.registers,
+            bytes: self.bytes().to_vec(),
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        let empty = Kept {
+            registers: Registers {
+                a: 0,
+                x: 0,
+                y: 0,
+                p: 0,
+                sp: 0,
+                pc: 0,
+                cycles: None,
+            },
+            bytes: [0; 3],
+            length: 0,
+        };
+
+        Self {
+            instructions: [empty; HISTORY_LENGTH],
+            kept: 0,
+            previous: None,
+            ticks: RESET_CYCLES,
+        }
+    }
+}
+
+impl History {
+    /// Reads the registers of `cpu` before its first tick
+    pub(crate) fn before_tick<T: TestableCpu>(&mut self, cpu: &T) {
+        if self.previous.is_none() {
+            self.previous = cpu.registers().map(Registers::read);
+        }
+    }
+
+    /// The program counter after the last tick, when the cpu gives access to its registers
+    pub(crate) fn pc(&self) -> Option<u16> {
+        self.previous.map(|registers| registers.pc)
+    }
+
+    /// Looks at the `registers` of `cpu` after a tick, where the program counter is `pc`. When it started a new
+    /// instruction in that tick, the instruction is kept and returned, together with the tick it started in.
+    pub(crate) fn after_tick<T: TestableCpu>(
+        &mut self,
+        cpu: &T,
+        registers: &dyn CpuRegisters,
+        pc: u16,
+    ) -> Option<(&Kept, u64)> {
+        let tick = self.ticks;
+        self.ticks += 1;
+
+        let previous = self.previous.as_mut()?;
+        if pc == previous.pc {
+            previous.cycles = registers.cycles();
+            return None;
+        }
+        let before = std::mem::replace(previous, Registers::read(registers));
+
+        Some((self.keep(cpu, before), tick))
+    }
+
+    /// Keeps the instruction the cpu is stuck at. A cpu that jumps to itself doesn't change its program counter,
+    /// so the instruction would otherwise never be kept.
+    pub(crate) fn keep_stuck<T: TestableCpu>(&mut self, cpu: &T) {
+        let Some(current) = self.previous else {
+            return;
+        };
+        if self.last().map(|last| last.registers.pc) != Some(current.pc) {
+            self.keep(cpu, current);
+        }
+    }
+
+    /// Keeps the instruction a cpu that executes one instruction at a time is about to execute,
+    /// where `registers` are its registers before it
+    pub(crate) fn before_step<T: TestableCpu>(&mut self, cpu: &T, registers: Registers) {
+        self.keep(cpu, registers);
+    }
+
+    /// Keeps the instruction at the program counter of `registers`, reading its bytes from `cpu`
+    fn keep<T: TestableCpu>(&mut self, cpu: &T, registers: Registers) -> &Kept {
+        let length = usize::from(OPCODES[usize::from(cpu.memory_read(registers.pc))].length);
+        let mut bytes = [0; 3];
+        for (i, byte) in bytes.iter_mut().enumerate().take(length) {
+            *byte = cpu.memory_read(registers.pc.wrapping_add(i as u16));
+        }
+
+        let kept = &mut self.instructions[self.kept % HISTORY_LENGTH];
+        *kept = Kept {
+            registers,
+            bytes,
+            length,
+        };
+        self.kept += 1;
+
+        kept
+    }
+
+    /// The kept instructions, from the oldest to the most recent one
+    pub(crate) fn instructions(&self) -> Vec<ExecutedInstruction> {
+        let oldest = self.kept.saturating_sub(HISTORY_LENGTH);
+        (oldest..self.kept)
+            .map(|i| self.instructions[i % HISTORY_LENGTH].executed())
+            .collect()
+    }
+
+    /// The most recent instruction
+    pub(crate) fn last(&self) -> Option<ExecutedInstruction> {
+        let last = self.kept.checked_sub(1)?;
+        Some(self.instructions[last % HISTORY_LENGTH].executed())
+    }
+}
+
+/// Describes the program counter `pc` a cpu hangs at, with the instruction there when it is the `last` one it executed,
+/// like `$C000 (JMP $C000)`
+pub(crate) fn hang_location(pc: u16, last: Option<&ExecutedInstruction>) -> String {
+    match last {
+        Some(last) if last.registers.pc == pc => format!("${pc:04X} ({})", last.disassembly()),
+        _ => format!("${pc:04X}"),
+    }
+}
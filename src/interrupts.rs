@@ -0,0 +1,209 @@
+use crate::{RomFailure, TestableCpu};
+
+/// Where the rom stores which code it ran last, one of the `STAGE_*` values
+const STAGE: u16 = 0x02;
+/// Where the IRQ handler counts how many times it ran
+const IRQ_COUNT: u16 = 0x03;
+/// Where the NMI handler counts how many times it ran
+const NMI_COUNT: u16 = 0x04;
+/// Where the NMI handler stores the stack pointer it sees
+const NMI_STACK_POINTER: u16 = 0x05;
+/// Where the NMI handler stores the status register the NMI pushed
+const NMI_STATUS: u16 = 0x06;
+/// Where the IRQ handler stores the stack pointer it sees
+const IRQ_STACK_POINTER: u16 = 0x07;
+/// Where the IRQ handler stores the status register the IRQ pushed
+const IRQ_STATUS: u16 = 0x08;
+
+/// The stack pointer the rom sets before it waits for an interrupt
+const STACK_POINTER: u8 = 0xFF;
+
+const STAGE_MASKED: u8 = 0x01;
+const STAGE_UNMASKED: u8 = 0x02;
+
+const BREAK: u8 = 0x10;
+const INTERRUPT_DISABLE: u8 = 0x04;
+
+/// Builds an NROM rom to test what the cpu does when it is interrupted.
+///
+/// The reset vector points to $8100. The rom sets the interrupt disable flag and waits for an NMI.
+/// The NMI handler at $8200 counts how often it runs in $04, and stores the stack pointer and the status
+/// the NMI pushed in $05-$06. After the NMI, the rom clears the interrupt disable flag and waits for an IRQ.
+/// The IRQ handler at $8300 does the same in $03 and $07-$08, and doesn't return.
+pub(crate) fn interrupts_test_rom() -> Vec<u8> {
+    let mut prg = vec![0; 0x4000];
+
+    let mut write = |address: u16, code: &[u8]| {
+        let start = usize::from(address - 0x8000);
+        prg[start..start + code.len()].copy_from_slice(code);
+    };
+
+    #[rustfmt::skip]
+    let reset_handler = [
+        0x78,                               // SEI
+        0xD8,                               // CLD
+        0xA2, STACK_POINTER,                // LDX #$FF
+        0x9A,                               // TXS
+        0xA9, 0x00,                         // LDA #$00
+        0x85, IRQ_COUNT as u8,              // STA $03
+        0x85, NMI_COUNT as u8,              // STA $04
+        0xA9, STAGE_MASKED,                 // LDA #$01
+        0x85, STAGE as u8,                  // STA $02
+        // wait_for_nmi:
+        0xA5, NMI_COUNT as u8,              // LDA $04
+        0xF0, 0xFC,                         // BEQ wait_for_nmi
+        0xA9, STAGE_UNMASKED,               // LDA #$02
+        0x85, STAGE as u8,                  // STA $02
+        0x58,                               // CLI
+        0x4C, 0x18, 0x81,                   // JMP $8118
+    ];
+    write(0x8100, &reset_handler);
+
+    #[rustfmt::skip]
+    let nmi_handler = [
+        0xE6, NMI_COUNT as u8,              // INC $04
+        0xBA,                               // TSX
+        0x86, NMI_STACK_POINTER as u8,      // STX $05
+        0xBD, 0x01, 0x01,                   // LDA $0101,X
+        0x85, NMI_STATUS as u8,             // STA $06
+        0x40,                               // RTI
+    ];
+    write(0x8200, &nmi_handler);
+
+    #[rustfmt::skip]
+    let irq_handler = [
+        0xE6, IRQ_COUNT as u8,              // INC $03
+        0xBA,                               // TSX
+        0x86, IRQ_STACK_POINTER as u8,      // STX $07
+        0xBD, 0x01, 0x01,                   // LDA $0101,X
+        0x85, IRQ_STATUS as u8,             // STA $08
+        0x4C, 0x0A, 0x83,                   // JMP $830A
+    ];
+    write(0x8300, &irq_handler);
+
+    // NMI, reset and IRQ vectors
+    write(0xBFFA, &[0x00, 0x82, 0x00, 0x81, 0x00, 0x83]);
+
+    let mut rom = b"NES\x1A\x01\x01\x00\x00".to_vec();
+    rom.resize(16, 0);
+    rom.extend(prg);
+    rom.resize(rom.len() + 0x2000, 0);
+    rom
+}
+
+/// Checks that the rom booted and is waiting for an NMI, after the IRQ line was set while interrupts were disabled
+pub(crate) fn masked_status(cpu: &impl TestableCpu) -> Result<(), RomFailure> {
+    if cpu.memory_read(STAGE) != STAGE_MASKED || cpu.memory_read(NMI_COUNT) != 0 {
+        return Err(interrupts_failure(
+            "the rom didn't boot, the cpu should start at the address in the reset vector at $FFFC",
+        ));
+    }
+
+    if cpu.memory_read(IRQ_COUNT) != 0 {
+        return Err(interrupts_failure(
+            "the cpu handled an IRQ while the interrupt disable flag was set",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks what the cpu did after the NMI, and after the rom cleared the interrupt disable flag with the IRQ line set
+pub(crate) fn interrupts_status(cpu: &impl TestableCpu) -> Result<(), RomFailure> {
+    match cpu.memory_read(NMI_COUNT) {
+        0 => {
+            return Err(interrupts_failure(
+                "the cpu didn't jump to the address in the NMI vector at $FFFA after an NMI",
+            ))
+        }
+        1 => {}
+        count => {
+            return Err(interrupts_failure(&format!(
+                "the cpu handled a single NMI {count} times"
+            )))
+        }
+    }
+    pushed(cpu, "an NMI", NMI_STACK_POINTER, NMI_STATUS)?;
+
+    if cpu.memory_read(STAGE) != STAGE_UNMASKED {
+        return Err(interrupts_failure(
+            "the cpu didn't return to the code the NMI interrupted with RTI, or handled the IRQ before the rom cleared the interrupt disable flag",
+        ));
+    }
+
+    match cpu.memory_read(IRQ_COUNT) {
+        0 => {
+            return Err(interrupts_failure(
+                "the cpu didn't jump to the address in the IRQ vector at $FFFE while the IRQ line was set \
+                and the interrupt disable flag was clear",
+            ))
+        }
+        1 => {}
+        count => {
+            return Err(interrupts_failure(&format!(
+                "the cpu handled the IRQ {count} times, it should set the interrupt disable flag when it handles an IRQ"
+            )))
+        }
+    }
+    pushed(cpu, "an IRQ", IRQ_STACK_POINTER, IRQ_STATUS)?;
+
+    if cpu.memory_read(IRQ_STATUS) & INTERRUPT_DISABLE != 0 {
+        return Err(interrupts_failure(
+            "the status an IRQ pushes should be the status before the IRQ, with the interrupt disable flag clear",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks the stack pointer and status a handler stored at `stack_pointer` and `status`
+fn pushed(
+    cpu: &impl TestableCpu,
+    interrupt: &str,
+    stack_pointer: u16,
+    status: u16,
+) -> Result<(), RomFailure> {
+    let expected = STACK_POINTER.wrapping_sub(3);
+    let actual = cpu.memory_read(stack_pointer);
+    if actual != expected {
+        return Err(interrupts_failure(&format!(
+            "{interrupt} should push the program counter and the status, from ${STACK_POINTER:02X} to ${expected:02X}, \
+            but the stack pointer was ${actual:02X}"
+        )));
+    }
+
+    if cpu.memory_read(status) & BREAK != 0 {
+        return Err(interrupts_failure(&format!(
+            "the status {interrupt} pushes should have the B flag clear"
+        )));
+    }
+
+    Ok(())
+}
+
+fn interrupts_failure(description: &str) -> RomFailure {
+    RomFailure::Interrupts {
+        description: description.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handlers_are_where_the_vectors_point() {
+        let rom = interrupts_test_rom();
+        let prg = &rom[16..16 + 0x4000];
+
+        assert_eq!(rom.len(), 16 + 0x4000 + 0x2000);
+        assert_eq!(&prg[0x3FFA..], &[0x00, 0x82, 0x00, 0x81, 0x00, 0x83]);
+        // SEI, INC $04 and INC $03
+        assert_eq!(prg[0x100], 0x78);
+        assert_eq!(prg[0x200..0x202], [0xE6, 0x04]);
+        assert_eq!(prg[0x300..0x302], [0xE6, 0x03]);
+        // both loops jump to themselves
+        assert_eq!(prg[0x118..0x11B], [0x4C, 0x18, 0x81]);
+        assert_eq!(prg[0x30A..0x30D], [0x4C, 0x0A, 0x83]);
+    }
+}
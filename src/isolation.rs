@@ -0,0 +1,726 @@
+use crate::nestest::LOG_FIELDS;
+use crate::{
+    Capability, CpuError, ExecutedInstruction, FailedInstruction, NestestFailure, Registers,
+    RomFailure, SubtestResult, TestOutcome, TestResult, TimingCase, TimingMismatch,
+};
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::str::{FromStr, Split};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Set on a child process to the name of the test it should run
+const CHILD_ENV: &str = "TUDELFT_NES_TEST_CHILD";
+/// Starts the line a child process uses to report the result of its test
+const RESULT_MARKER: &str = "tudelft-nes-test result\t";
+/// How much longer than the timeout of its test a child process may take before it is killed.
+/// The child times out by itself, so this is only needed when the whole process hangs.
+const KILL_MARGIN: Duration = Duration::from_secs(10);
+
+/// What a child process reports about its test: the outcome, the registers, the history and the amount of cycles
+type ChildResult = (
+    TestOutcome,
+    Option<Registers>,
+    Vec<ExecutedInstruction>,
+    u64,
+);
+
+/// The name of the test this process should run, when it is a child process
+pub(crate) fn child_test() -> Option<String> {
+    env::var(CHILD_ENV).ok()
+}
+
+/// Sends the result of the test this child process was started for to the parent process, and exits
+pub(crate) fn report_to_parent(result: &TestResult) -> ! {
+    let mut line = Line::default();
+    line.encode_result(result);
+
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{RESULT_MARKER}{}", line.0.join("\t"));
+    let _ = stdout.flush();
+    std::process::exit(0)
+}
+
+/// Runs the test called `name` in a child process, by running the current executable again.
+/// When it runs as a test, only the test that is currently running is run again.
+pub(crate) fn run_in_child(name: &str, timeout: Duration) -> TestResult {
+    let start = Instant::now();
+    let (outcome, registers, history, cycles) = match spawn_child(name) {
+        Ok(child) => wait_for_child(name, child, timeout + KILL_MARGIN),
+        Err(e) => (
+            TestOutcome::Crashed(format!("couldn't start the test process: {e}")),
+            None,
+            Vec::new(),
+            0,
+        ),
+    };
+
+    TestResult {
+        name: name.to_string(),
+        outcome,
+        registers,
+        history,
+        cycles,
+        duration: start.elapsed(),
+    }
+}
+
+fn spawn_child(name: &str) -> std::io::Result<Child> {
+    let mut command = Command::new(env::current_exe()?);
+
+    // the test harness runs every test on a thread named after the test
+    match thread::current().name() {
+        Some(test) if test != "main" => {
+            command.args([test, "--exact", "--nocapture", "--test-threads=1"]);
+        }
+        _ => {
+            command.args(env::args_os().skip(1));
+        }
+    }
+
+    command
+        .env(CHILD_ENV, name)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+}
+
+fn wait_for_child(name: &str, mut child: Child, timeout: Duration) -> ChildResult {
+    let stdout = child
+        .stdout
+        .take()
+        .expect("stdout of the test process is piped");
+    let stderr = child
+        .stderr
+        .take()
+        .expect("stderr of the test process is piped");
+
+    // read the output on another thread, so the child never blocks on a full pipe
+    let reader = thread::spawn(move || {
+        let mut result = None;
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            // the test harness doesn't end its line before the test prints something
+            match line.split_once(RESULT_MARKER) {
+                Some((before, encoded)) => {
+                    print!("{before}");
+                    result = Fields(encoded.split('\t')).decode_result();
+                }
+                None => println!("{line}"),
+            }
+        }
+        result
+    });
+    let overflowed = thread::spawn(move || {
+        let mut overflowed = false;
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            overflowed |= line.contains("has overflowed its stack");
+            eprintln!("{line}");
+        }
+        overflowed
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) if Instant::now() >= deadline => {
+                log::warn!(
+                    "the process running {name} didn't finish within {timeout:?}, killing it"
+                );
+                let _ = child.kill();
+                break child.wait();
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(e) => break Err(e),
+        }
+    };
+
+    match (reader.join().ok().flatten(), status) {
+        (Some(result), _) => result,
+        (None, _) if overflowed.join().unwrap_or(false) => {
+            (TestOutcome::StackOverflow, None, Vec::new(), 0)
+        }
+        (None, Ok(status)) => (
+            TestOutcome::Crashed(format!(
+                "the test process exited without reporting a result ({status})"
+            )),
+            None,
+            Vec::new(),
+            0,
+        ),
+        (None, Err(e)) => (
+            TestOutcome::Crashed(format!("couldn't wait for the test process: {e}")),
+            None,
+            Vec::new(),
+            0,
+        ),
+    }
+}
+
+/// The fields of the line a child process reports its result with
+#[derive(Default)]
+struct Line(Vec<String>);
+
+impl Line {
+    fn push(&mut self, value: impl ToString) {
+        self.0.push(value.to_string());
+    }
+
+    fn push_str(&mut self, value: &str) {
+        self.0.push(
+            value
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r"),
+        );
+    }
+
+    fn encode_result(&mut self, result: &TestResult) {
+        // the name and duration are known to the parent already
+        let child_result: ChildResult = (
+            result.outcome.clone(),
+            result.registers,
+            result.history.clone(),
+            result.cycles,
+        );
+        child_result.encode(self);
+    }
+}
+
+/// Reads the fields of a [`Line`] back
+struct Fields<'a>(Split<'a, char>);
+
+impl Fields<'_> {
+    fn next<V: FromStr>(&mut self) -> Option<V> {
+        self.0.next()?.parse().ok()
+    }
+
+    fn next_str(&mut self) -> Option<String> {
+        let mut res = String::new();
+        let mut chars = self.0.next()?.chars();
+        while let Some(c) = chars.next() {
+            res.push(if c == '\\' {
+                match chars.next()? {
+                    't' => '\t',
+                    'n' => '\n',
+                    'r' => '\r',
+                    c => c,
+                }
+            } else {
+                c
+            });
+        }
+
+        Some(res)
+    }
+
+    fn decode_result(&mut self) -> Option<ChildResult> {
+        Field::decode(self)
+    }
+}
+
+/// A value that a child process sends to its parent as fields of a [`Line`]
+trait Field: Sized {
+    fn encode(&self, line: &mut Line);
+
+    /// Reads the value back, or returns `None` when the fields don't hold one
+    fn decode(fields: &mut Fields) -> Option<Self>;
+}
+
+/// Implements [`Field`] for a struct, or for an enum with a tag for every variant, from the list of its fields.
+/// The fields are sent in the order they are listed in, and a field or variant that is left out doesn't compile.
+macro_rules! fields {
+    (struct $type:ident { $($field:ident),* $(,)? }) => {
+        impl Field for $type {
+            fn encode(&self, line: &mut Line) {
+                let $type { $($field),* } = self;
+                $($field.encode(line);)*
+            }
+
+            fn decode(fields: &mut Fields) -> Option<Self> {
+                Some($type { $($field: Field::decode(fields)?),* })
+            }
+        }
+    };
+    (enum $type:ident { $($tag:literal => $variant:ident $variant_fields:tt),* $(,)? }) => {
+        impl Field for $type {
+            fn encode(&self, line: &mut Line) {
+                match self {
+                    $(fields!(@pattern $type $variant $variant_fields) => {
+                        line.push_str($tag);
+                        fields!(@encode line $variant_fields);
+                    })*
+                }
+            }
+
+            fn decode(fields: &mut Fields) -> Option<Self> {
+                let tag = fields.next_str()?;
+                $(if tag == $tag {
+                    return Some(fields!(@decode fields $type $variant $variant_fields));
+                })*
+                None
+            }
+        }
+    };
+    (@pattern $type:ident $variant:ident { $($field:ident),* }) => {
+        $type::$variant { $($field),* }
+    };
+    (@pattern $type:ident $variant:ident ( $($field:ident),* )) => {
+        $type::$variant($($field),*)
+    };
+    (@encode $line:ident { $($field:ident),* }) => {
+        $($field.encode($line);)*
+    };
+    (@encode $line:ident ( $($field:ident),* )) => {
+        $($field.encode($line);)*
+    };
+    (@decode $fields:ident $type:ident $variant:ident { $($field:ident),* }) => {
+        $type::$variant { $($field: Field::decode($fields)?),* }
+    };
+    (@decode $fields:ident $type:ident $variant:ident ( $($field:ident),* )) => {
+        $type::$variant($(fields!(@decode_one $fields $field)),*)
+    };
+    (@decode_one $fields:ident $field:ident) => {
+        Field::decode($fields)?
+    };
+}
+
+/// Implements [`Field`] for values that are sent with their [`Display`](std::fmt::Display) implementation
+macro_rules! displayed_fields {
+    ($($type:ty),*) => {
+        $(impl Field for $type {
+            fn encode(&self, line: &mut Line) {
+                line.push(self);
+            }
+
+            fn decode(fields: &mut Fields) -> Option<Self> {
+                fields.next()
+            }
+        })*
+    };
+}
+
+displayed_fields!(bool, u8, u16, u64, usize);
+
+impl Field for String {
+    fn encode(&self, line: &mut Line) {
+        line.push_str(self);
+    }
+
+    fn decode(fields: &mut Fields) -> Option<Self> {
+        fields.next_str()
+    }
+}
+
+/// The names of the fields of a line of `nestest.log`, see [`RomFailure::LogMismatch`]
+impl Field for &'static str {
+    fn encode(&self, line: &mut Line) {
+        line.push_str(self);
+    }
+
+    fn decode(fields: &mut Fields) -> Option<Self> {
+        let name = fields.next_str()?;
+        LOG_FIELDS.into_iter().find(|field| *field == name)
+    }
+}
+
+impl<T: Field> Field for Option<T> {
+    fn encode(&self, line: &mut Line) {
+        match self {
+            Some(value) => {
+                line.push(1);
+                value.encode(line);
+            }
+            None => line.push(0),
+        }
+    }
+
+    fn decode(fields: &mut Fields) -> Option<Self> {
+        match fields.next::<u8>()? {
+            0 => Some(None),
+            _ => T::decode(fields).map(Some),
+        }
+    }
+}
+
+impl<T: Field> Field for Vec<T> {
+    fn encode(&self, line: &mut Line) {
+        line.push(self.len());
+        for value in self {
+            value.encode(line);
+        }
+    }
+
+    fn decode(fields: &mut Fields) -> Option<Self> {
+        (0..fields.next::<usize>()?)
+            .map(|_| T::decode(fields))
+            .collect()
+    }
+}
+
+impl<T: Field, const N: usize> Field for [T; N] {
+    fn encode(&self, line: &mut Line) {
+        for value in self {
+            value.encode(line);
+        }
+    }
+
+    fn decode(fields: &mut Fields) -> Option<Self> {
+        let values: Vec<_> = (0..N).map(|_| T::decode(fields)).collect::<Option<_>>()?;
+        values.try_into().ok()
+    }
+}
+
+impl<A: Field, B: Field, C: Field, D: Field> Field for (A, B, C, D) {
+    fn encode(&self, line: &mut Line) {
+        let (a, b, c, d) = self;
+        a.encode(line);
+        b.encode(line);
+        c.encode(line);
+        d.encode(line);
+    }
+
+    fn decode(fields: &mut Fields) -> Option<Self> {
+        Some((
+            A::decode(fields)?,
+            B::decode(fields)?,
+            C::decode(fields)?,
+            D::decode(fields)?,
+        ))
+    }
+}
+
+/// The error is sent as its messages, see [`CpuError`]
+impl Field for CpuError {
+    fn encode(&self, line: &mut Line) {
+        self.messages().encode(line);
+    }
+
+    fn decode(fields: &mut Fields) -> Option<Self> {
+        Vec::decode(fields).map(CpuError::from_messages)
+    }
+}
+
+/// The failure is sent as its address and code, the rest is looked up again
+impl Field for NestestFailure {
+    fn encode(&self, line: &mut Line) {
+        self.address.encode(line);
+        self.code.encode(line);
+    }
+
+    fn decode(fields: &mut Fields) -> Option<Self> {
+        Some(NestestFailure::decode(
+            Field::decode(fields)?,
+            Field::decode(fields)?,
+        ))
+    }
+}
+
+fields!(struct Registers { a, x, y, p, sp, pc, cycles });
+fields!(struct ExecutedInstruction { registers, bytes });
+fields!(struct SubtestResult { number, name, passed, finished_at, failures, messages });
+fields!(struct FailedInstruction { opcode, mnemonic, mode, message });
+fields!(struct TimingMismatch { opcode, case, expected, actual });
+
+fields!(enum TimingCase {
+    "base" => Base {},
+    "page crossed" => PageCrossed {},
+    "branch taken" => BranchTaken {},
+    "branch to other page" => BranchToOtherPage {},
+});
+
+fields!(enum Capability {
+    "registers" => Registers {},
+    "cycles" => Cycles {},
+    "memory write" => MemoryWrite {},
+    "register write" => RegisterWrite {},
+    "reset" => Reset {},
+    "interrupts" => Interrupts {},
+    "step" => Step {},
+    "bus log" => BusLog {},
+});
+
+fields!(enum RomFailure {
+    "nestest" => Nestest { official, unofficial, rra, description, failures },
+    "blargg" => Blargg { status, text },
+    "instr test" => InstrTest { status, subtests, text },
+    "invalid magic" => InvalidMagic { magic },
+    "reset" => Reset { description },
+    "interrupts" => Interrupts { description },
+    "memory" => Memory { address, expected, actual },
+    "log mismatch" => LogMismatch { line, differences, previous, expected, actual },
+    "log stuck" => LogStuck { line, pc, expected },
+    "timing" => Timing { mismatches },
+});
+
+fields!(enum TestOutcome {
+    "passed" => Passed {},
+    "failed" => Failed(failure),
+    "panicked" => Panicked(message),
+    "load error" => LoadError(error),
+    "cpu error" => CpuError { error, failure },
+    "timed out" => TimedOut(message),
+    "stuck" => Stuck { pc },
+    "stack overflow" => StackOverflow {},
+    "crashed" => Crashed(message),
+    "skipped" => Skipped(capability),
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nestest::nestest_failures;
+    use crate::TestError;
+
+    /// A message with every character that is escaped
+    const MESSAGE: &str = "a\tb\nc\\d\re\\t \\\\n";
+
+    fn result(outcome: TestOutcome) -> TestResult {
+        let registers = Registers {
+            a: 0x01,
+            x: 0x02,
+            y: 0x03,
+            p: 0x24,
+            sp: 0xFD,
+            pc: 0xC000,
+            cycles: Some(7),
+        };
+
+        TestResult {
+            name: "nestest".to_string(),
+            outcome,
+            registers: Some(registers),
+            history: vec![
+                ExecutedInstruction {
+                    registers,
+                    bytes: vec![0x4C, 0xF5, 0xC5],
+                },
+                ExecutedInstruction {
+                    registers: Registers {
+                        cycles: None,
+                        ..registers
+                    },
+                    bytes: vec![0xEA],
+                },
+            ],
+            cycles: 26_554,
+            duration: Duration::ZERO,
+        }
+    }
+
+    fn round_trip(result: &TestResult) -> TestResult {
+        let mut line = Line::default();
+        line.encode_result(result);
+        let encoded = line.0.join("\t");
+        assert!(!encoded.contains('\n'), "{encoded}");
+
+        let (outcome, registers, history, cycles) = Fields(encoded.split('\t'))
+            .decode_result()
+            .unwrap_or_else(|| panic!("couldn't decode {encoded}"));
+        TestResult {
+            name: result.name.clone(),
+            outcome,
+            registers,
+            history,
+            cycles,
+            duration: result.duration,
+        }
+    }
+
+    fn failures() -> Vec<RomFailure> {
+        let subtest = SubtestResult {
+            number: 5,
+            name: "05-zp_xy".to_string(),
+            passed: false,
+            finished_at: 3_200_000,
+            failures: vec![
+                FailedInstruction {
+                    opcode: 0xB6,
+                    mnemonic: "LDX".to_string(),
+                    mode: Some("z,Y".to_string()),
+                    message: Some(MESSAGE.to_string()),
+                },
+                FailedInstruction {
+                    opcode: 0xEA,
+                    mnemonic: "NOP".to_string(),
+                    mode: None,
+                    message: None,
+                },
+            ],
+            messages: vec![MESSAGE.to_string(), String::new()],
+        };
+
+        vec![
+            RomFailure::Nestest {
+                official: 0x58,
+                unofficial: 0xAA,
+                rra: 0x0A,
+                description: MESSAGE.to_string(),
+                failures: nestest_failures(0x58, 0xAA, 0x0A),
+            },
+            RomFailure::Blargg {
+                status: 2,
+                text: MESSAGE.to_string(),
+            },
+            RomFailure::InstrTest {
+                status: 1,
+                subtests: vec![
+                    SubtestResult {
+                        passed: true,
+                        failures: Vec::new(),
+                        messages: Vec::new(),
+                        finished_at: 200_000,
+                        ..subtest.clone()
+                    },
+                    subtest,
+                ],
+                text: MESSAGE.to_string(),
+            },
+            RomFailure::InvalidMagic {
+                magic: [0xDE, 0xB0, 0x00],
+            },
+            RomFailure::Reset {
+                description: MESSAGE.to_string(),
+            },
+            RomFailure::Interrupts {
+                description: MESSAGE.to_string(),
+            },
+            RomFailure::Memory {
+                address: 0x42,
+                expected: 0x43,
+                actual: 0x00,
+            },
+            RomFailure::LogMismatch {
+                line: 12,
+                differences: vec!["A", "CYC"],
+                previous: Some(MESSAGE.to_string()),
+                expected: "expected\t".to_string(),
+                actual: "actual\\".to_string(),
+            },
+            RomFailure::LogMismatch {
+                line: 1,
+                differences: vec!["PC"],
+                previous: None,
+                expected: String::new(),
+                actual: String::new(),
+            },
+            RomFailure::LogStuck {
+                line: 3,
+                pc: 0xC5F5,
+                expected: MESSAGE.to_string(),
+            },
+            RomFailure::Timing {
+                mismatches: TimingCase::ALL
+                    .into_iter()
+                    .map(|case| TimingMismatch {
+                        opcode: 0xBD,
+                        case,
+                        expected: 4,
+                        actual: Some(5),
+                    })
+                    .chain([TimingMismatch {
+                        opcode: 0x00,
+                        case: TimingCase::Base,
+                        expected: 7,
+                        actual: None,
+                    }])
+                    .collect(),
+            },
+        ]
+    }
+
+    fn outcomes() -> Vec<TestOutcome> {
+        let error = CpuError::from_messages(vec![MESSAGE.to_string(), "source".to_string()]);
+        let mut outcomes = vec![
+            TestOutcome::Passed,
+            TestOutcome::Panicked(MESSAGE.to_string()),
+            TestOutcome::LoadError(error.clone()),
+            TestOutcome::CpuError {
+                error: error.clone(),
+                failure: None,
+            },
+            TestOutcome::CpuError {
+                error,
+                failure: Some(RomFailure::Memory {
+                    address: 0x6000,
+                    expected: 0,
+                    actual: 1,
+                }),
+            },
+            TestOutcome::TimedOut(MESSAGE.to_string()),
+            TestOutcome::Stuck { pc: 0xC00A },
+            TestOutcome::StackOverflow,
+            TestOutcome::Crashed(MESSAGE.to_string()),
+        ];
+        outcomes.extend(Capability::ALL.map(TestOutcome::Skipped));
+        outcomes.extend(failures().into_iter().map(TestOutcome::Failed));
+        outcomes
+    }
+
+    #[test]
+    fn every_outcome_round_trips() {
+        for outcome in outcomes() {
+            let result = result(outcome);
+            assert_eq!(round_trip(&result), result);
+        }
+    }
+
+    #[test]
+    fn every_error_round_trips() {
+        let mut errors = 0;
+        for outcome in outcomes() {
+            let result = result(outcome);
+            let Err(error) = result.clone().into_result() else {
+                continue;
+            };
+
+            let decoded = round_trip(&result).into_result().unwrap_err();
+            assert_eq!(decoded, error);
+            assert_eq!(decoded.to_string(), error.to_string());
+            errors += 1;
+        }
+
+        // every variant of TestError but Failed shows up once, and Failed once for every rom failure
+        assert_eq!(errors, 8 + Capability::ALL.len() + failures().len());
+    }
+
+    #[test]
+    fn results_without_registers_round_trip() {
+        let result = TestResult {
+            registers: None,
+            history: Vec::new(),
+            ..result(TestOutcome::Passed)
+        };
+        assert_eq!(round_trip(&result), result);
+    }
+
+    #[test]
+    fn cpu_errors_keep_their_sources() {
+        let result = result(TestOutcome::LoadError(CpuError::from_messages(vec![
+            "outer".to_string(),
+            MESSAGE.to_string(),
+        ])));
+        let Err(TestError::LoadRom { source, .. }) = round_trip(&result).into_result() else {
+            panic!("the outcome changed");
+        };
+
+        assert_eq!(source.messages(), ["outer", MESSAGE]);
+    }
+
+    #[test]
+    fn broken_lines_are_not_decoded() {
+        let mut line = Line::default();
+        line.encode_result(&result(TestOutcome::Crashed(MESSAGE.to_string())));
+
+        for length in 0..line.0.len() {
+            let truncated = line.0[..length].join("\t");
+            assert!(Fields(truncated.split('\t')).decode_result().is_none());
+        }
+        assert!(Fields("unknown\t0\t0\t0".split('\t'))
+            .decode_result()
+            .is_none());
+    }
+}
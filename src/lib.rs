@@ -1,26 +1,79 @@
 //! # `tudelft-nes-test`
 //! This is a helper crate for your NES emulator to run various test ROMs
-use crate::all_instrs::{all_instrs_status_code, read_status_string};
+// test failures carry everything needed to explain them, and are only created once per test
+#![allow(clippy::result_large_err)]
 use bitflags::bitflags;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, TryLockError};
 use std::thread;
-use std::thread::JoinHandle;
-use thiserror::Error;
+use std::time::{Duration, Instant};
 use tudelft_nes_ppu::{run_cpu_headless_for, Cpu, Mirroring};
 
 mod all_instrs;
+mod blargg;
+mod diff;
+mod disassembler;
+mod error;
+mod history;
+mod interrupts;
+mod isolation;
 mod nestest;
+mod opcodes;
+#[cfg(feature = "reference")]
+mod reference;
+mod report;
+mod reset;
+mod single_step;
+mod state;
+mod timing;
+mod trace;
+mod watchdog;
 
-use crate::nestest::nestest_status_code;
+use crate::all_instrs::{single_instr_rom, SubtestTimes};
+pub use crate::all_instrs::{FailedInstruction, SubtestResult};
+use crate::blargg::run_blargg;
+pub use crate::blargg::{run_blargg_rom, BlarggOptions, BlarggResult};
+pub use crate::diff::{diff_cpus, Divergence};
+pub use crate::disassembler::{disassemble, Disassembly};
+pub use crate::error::{CpuError, RomFailure, TestError};
+pub use crate::history::ExecutedInstruction;
+use crate::history::{hang_location, History};
+use crate::interrupts::{interrupts_status, interrupts_test_rom, masked_status};
+use crate::isolation::{child_test, report_to_parent, run_in_child};
+pub use crate::nestest::NestestFailure;
+use crate::nestest::{automated_nestest_rom, nestest_status, LogLine};
+pub use crate::opcodes::{AddressingMode, Opcode, OpcodeClass, StatusFlags, OPCODES};
+#[cfg(feature = "reference")]
+pub use crate::reference::ReferenceCpu;
+pub use crate::report::{TestOutcome, TestReport, TestResult};
+use crate::reset::{booted, reset_status, reset_test_rom};
+pub use crate::single_step::{
+    run_single_step_tests, BusCycle, OpcodeResult, SingleStepFailure, SingleStepReport,
+    SingleStepTest,
+};
+pub use crate::state::CpuState;
+use crate::timing::{time_instructions, timing_rom};
+pub use crate::timing::{TimingCase, TimingMismatch};
+use crate::trace::Tracer;
+pub use crate::trace::{Trace, TraceFormat};
+use crate::watchdog::{CpuStuck, Stopped, StuckDetector, Watched};
 
 /// Raw bytes for the all_instr rom
 pub const ROM_ALL_INSTR: &[u8] = include_bytes!("roms/all_instrs.nes");
-/// Raw bytes for the nestest rom
+/// Raw bytes for the nestest rom. Its reset vector starts an interactive menu,
+/// the test suite runs a copy of it that starts the automated tests at $C000 instead.
 pub const ROM_NESTEST: &[u8] = include_bytes!("roms/nestest.nes");
 /// Raw bytes for the nrom rom
 pub const ROM_NROM_TEST: &[u8] = include_bytes!("roms/nrom-test.nes");
 /// Raw bytes for the official_only rom
 pub const ROM_OFFICIAL_ONLY: &[u8] = include_bytes!("roms/official_only.nes");
+/// The expected execution log of the nestest rom, starting at the automated entry point $C000.
+/// Every line holds the state of the cpu right before an instruction is executed, in the format Nintendulator uses.
+pub const NESTEST_LOG: &str = include_str!("roms/nestest.log");
 
 /// Implement this trait to run our test on our CPU via the [`run_tests`] function.
 pub trait TestableCpu: Cpu + Sized + 'static {
@@ -28,13 +81,268 @@ pub trait TestableCpu: Cpu + Sized + 'static {
     /// `rom` is a rom file in INES format.
     fn get_cpu(rom: &[u8]) -> Result<Self, Box<dyn Error>>;
 
-    /// [`set_program_counter`] is used to set the program counter of the cpu to a specific position
-    /// this is needed by some tests.
-    fn set_program_counter(&mut self, value: u16);
+    /// [`set_program_counter`] is used to set the program counter of the cpu to a specific position.
+    /// The rom tests don't need this anymore, as every test starts from the reset vector of its rom,
+    /// so implementing it is optional. Tests that set all registers use [`CpuRegisterWrite::set_pc`] instead.
+    fn set_program_counter(&mut self, _value: u16) {}
 
     /// [`memory_read`] is used to test the succesfulness of tests by seeing if the CPU has expected values
     /// at certain memory locations, it simply takes an address and should return the byte of data at that memory location
     fn memory_read(&self, address: u16) -> u8;
+
+    /// [`registers`] gives the test suite access to the registers of your CPU, which are shown when a test fails.
+    /// Implement [`CpuRegisters`] for your CPU and return `Some(self)` here to enable this.
+    fn registers(&self) -> Option<&dyn CpuRegisters> {
+        None
+    }
+
+    /// [`resettable`] gives the test suite a way to reset your CPU, which some tests need.
+    /// Implement [`CpuReset`] for your CPU and return `Some(self)` here to enable this.
+    fn resettable(&mut self) -> Option<&mut dyn CpuReset> {
+        None
+    }
+
+    /// [`writable`] gives the test suite a way to write to the memory of your CPU, which some tests need.
+    /// Implement [`CpuMemoryWrite`] for your CPU and return `Some(self)` here to enable this.
+    fn writable(&mut self) -> Option<&mut dyn CpuMemoryWrite> {
+        None
+    }
+
+    /// [`registers_mut`] gives the test suite a way to set the registers of your CPU, which some tests need.
+    /// Implement [`CpuRegisterWrite`] for your CPU and return `Some(self)` here to enable this.
+    fn registers_mut(&mut self) -> Option<&mut dyn CpuRegisterWrite> {
+        None
+    }
+
+    /// [`interruptible`] gives the test suite a way to trigger interrupts on your CPU, which some tests need.
+    /// Implement [`CpuInterrupts`] for your CPU and return `Some(self)` here to enable this.
+    fn interruptible(&mut self) -> Option<&mut dyn CpuInterrupts> {
+        None
+    }
+
+    /// [`steppable`] gives the test suite a way to run your CPU one instruction at a time, which some tests need.
+    /// Implement [`CpuStep`] for your CPU and return `Some(self)` here to enable this.
+    fn steppable(&mut self) -> Option<&mut dyn CpuStep> {
+        None
+    }
+
+    /// [`bus_log`] gives the test suite a way to see the reads and writes of your CPU, which some tests compare.
+    /// Implement [`CpuBusLog`] for your CPU and return `Some(self)` here to enable this.
+    fn bus_log(&mut self) -> Option<&mut dyn CpuBusLog> {
+        None
+    }
+}
+
+/// Implement this trait to let the test suite reset your CPU, like the reset button of a NES does.
+/// This is optional: it is needed by [`TestSelector::RESET`], and by test roms that ask to be reset while they run.
+pub trait CpuReset {
+    /// Resets the CPU: the stack pointer is decremented by 3, the interrupt disable flag is set
+    /// and the program counter is loaded from the reset vector at $FFFC. The other registers and memory stay the same.
+    fn reset(&mut self);
+}
+
+/// Implement this trait to give the test suite access to the registers of your CPU.
+/// This is optional: when you also return `Some(self)` from [`TestableCpu::registers`], every failing test
+/// shows the state of your CPU. It is needed to compare the execution of your CPU to a reference log with [`TestSelector::NESTEST_LOG`].
+pub trait CpuRegisters {
+    /// The value of the accumulator
+    fn accumulator(&self) -> u8;
+
+    /// The value of the X register
+    fn x_register(&self) -> u8;
+
+    /// The value of the Y register
+    fn y_register(&self) -> u8;
+
+    /// The value of the status register (P). Bit 4 (the break flag) and bit 5 don't exist in the real register,
+    /// so they are ignored by the test suite.
+    fn status(&self) -> u8;
+
+    /// The value of the stack pointer
+    fn stack_pointer(&self) -> u8;
+
+    /// The value of the program counter
+    fn program_counter(&self) -> u16;
+
+    /// The total amount of cycles your CPU has executed, or `None` if your CPU doesn't keep track of this.
+    /// When this is `None`, cycle counts aren't checked.
+    fn cycles(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Implement this trait to let the test suite write to the memory of your CPU, to put it in a specific state before a test.
+/// This is optional: it is needed by tests that don't use a rom to set up the state they need.
+pub trait CpuMemoryWrite {
+    /// Writes `value` to `address`, like a store instruction of the CPU would
+    fn memory_write(&mut self, address: u16, value: u8);
+}
+
+/// Implement this trait to let the test suite set the registers of your CPU, to put it in a specific state before a test.
+/// This is optional: it is needed by tests that don't use a rom to set up the state they need.
+pub trait CpuRegisterWrite {
+    /// Sets the accumulator
+    fn set_accumulator(&mut self, value: u8);
+
+    /// Sets the X register
+    fn set_x_register(&mut self, value: u8);
+
+    /// Sets the Y register
+    fn set_y_register(&mut self, value: u8);
+
+    /// Sets the status register (P). Bit 4 (the break flag) and bit 5 don't exist in the real register,
+    /// so your CPU may ignore them.
+    fn set_status(&mut self, value: u8);
+
+    /// Sets the stack pointer
+    fn set_stack_pointer(&mut self, value: u8);
+
+    /// Sets the program counter, so the next instruction the CPU executes is the one at `value`
+    fn set_pc(&mut self, value: u16);
+}
+
+/// Implement this trait to let the test suite trigger interrupts on your CPU, without a PPU or APU to cause them.
+/// This is optional: it is needed by [`TestSelector::INTERRUPTS`].
+pub trait CpuInterrupts {
+    /// Triggers a non-maskable interrupt, which the CPU handles after the instruction it is executing
+    fn nmi(&mut self);
+
+    /// Sets the IRQ line. While it is `true`, the CPU handles an interrupt after every instruction
+    /// it executes with the interrupt disable flag clear.
+    fn set_irq(&mut self, active: bool);
+}
+
+/// Implement this trait to let the test suite run your CPU one instruction at a time.
+/// This is optional: it is needed by tests that check the state of your CPU after every instruction.
+pub trait CpuStep {
+    /// Executes exactly one instruction, including all of its cycles
+    fn step(&mut self) -> Result<(), Box<dyn Error>>;
+}
+
+/// Implement this trait to let the test suite see what your CPU does on its bus in every cycle.
+/// This is optional: [`run_single_step_tests`] compares the reads and writes of every cycle when your CPU has it.
+pub trait CpuBusLog {
+    /// Returns the reads and writes your CPU did since the last call, one for every cycle in the order it did them,
+    /// and forgets them. A cycle in which your CPU reads a value it doesn't use, like the dummy reads of the 6502,
+    /// is a read as well.
+    fn take_bus_cycles(&mut self) -> Vec<BusCycle>;
+}
+
+/// One of the optional parts of [`TestableCpu`]. A test that needs a capability your CPU doesn't have
+/// is reported as [`TestOutcome::Skipped`] instead of failing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Capability {
+    /// Reading the registers, see [`CpuRegisters`]
+    Registers,
+    /// Counting cycles, see [`CpuRegisters::cycles`]
+    Cycles,
+    /// Writing memory, see [`CpuMemoryWrite`]
+    MemoryWrite,
+    /// Setting the registers, see [`CpuRegisterWrite`]
+    RegisterWrite,
+    /// Resetting, see [`CpuReset`]
+    Reset,
+    /// Triggering interrupts, see [`CpuInterrupts`]
+    Interrupts,
+    /// Executing one instruction at a time, see [`CpuStep`]
+    Step,
+    /// Logging the reads and writes of every cycle, see [`CpuBusLog`]
+    BusLog,
+}
+
+impl Capability {
+    /// Every capability
+    pub const ALL: [Capability; 8] = [
+        Capability::Registers,
+        Capability::Cycles,
+        Capability::MemoryWrite,
+        Capability::RegisterWrite,
+        Capability::Reset,
+        Capability::Interrupts,
+        Capability::Step,
+        Capability::BusLog,
+    ];
+
+    /// Whether `cpu` has this capability
+    pub fn supported_by(self, cpu: &mut impl TestableCpu) -> bool {
+        match self {
+            Capability::Registers => cpu.registers().is_some(),
+            Capability::Cycles => cpu.registers().and_then(|r| r.cycles()).is_some(),
+            Capability::MemoryWrite => cpu.writable().is_some(),
+            Capability::RegisterWrite => cpu.registers_mut().is_some(),
+            Capability::Reset => cpu.resettable().is_some(),
+            Capability::Interrupts => cpu.interruptible().is_some(),
+            Capability::Step => cpu.steppable().is_some(),
+            Capability::BusLog => cpu.bus_log().is_some(),
+        }
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Capability::Registers => "register access (CpuRegisters)",
+            Capability::Cycles => "cycle counting (CpuRegisters::cycles)",
+            Capability::MemoryWrite => "memory writes (CpuMemoryWrite)",
+            Capability::RegisterWrite => "register writes (CpuRegisterWrite)",
+            Capability::Reset => "reset (CpuReset)",
+            Capability::Interrupts => "interrupt injection (CpuInterrupts)",
+            Capability::Step => "instruction stepping (CpuStep)",
+            Capability::BusLog => "bus activity (CpuBusLog)",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A snapshot of the registers of a CPU, which can be printed in the same format as `nestest.log`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    /// The accumulator
+    pub a: u8,
+    /// The X register
+    pub x: u8,
+    /// The Y register
+    pub y: u8,
+    /// The status register
+    pub p: u8,
+    /// The stack pointer
+    pub sp: u8,
+    /// The program counter
+    pub pc: u16,
+    /// The total amount of cycles executed, if the CPU keeps track of this
+    pub cycles: Option<u64>,
+}
+
+impl Registers {
+    /// Reads all registers of `cpu`
+    pub fn read(cpu: &(impl CpuRegisters + ?Sized)) -> Self {
+        Self {
+            a: cpu.accumulator(),
+            x: cpu.x_register(),
+            y: cpu.y_register(),
+            p: cpu.status(),
+            sp: cpu.stack_pointer(),
+            pc: cpu.program_counter(),
+            cycles: cpu.cycles(),
+        }
+    }
+}
+
+impl Display for Registers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            self.pc, self.a, self.x, self.y, self.p, self.sp
+        )?;
+
+        if let Some(cycles) = self.cycles {
+            write!(f, " CYC:{cycles}")?;
+        }
+
+        Ok(())
+    }
 }
 
 bitflags! {
@@ -57,9 +365,96 @@ bitflags! {
         /// The source for this rom can be found [here](https://gitlab.ewi.tudelft.nl/software-fundamentals/nes-nrom-test/-/blob/main/src/init.s)
         const NROM_TEST       = 0b00001000;
 
+        /// `RESET` checks that your CPU does what a NES does when its reset button is pressed, using a small generated rom.
+        /// Your CPU has to implement [`CpuReset`] for this test, otherwise it is skipped.
+        const RESET           = 0b00010000;
+
+        /// Only "01-basics" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_BASICS    = 1 << 5;
+        /// Only "02-implied" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_IMPLIED   = 1 << 6;
+        /// Only "03-immediate" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_IMMEDIATE = 1 << 7;
+        /// Only "04-zero_page" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_ZERO_PAGE = 1 << 8;
+        /// Only "05-zp_xy" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_ZP_XY     = 1 << 9;
+        /// Only "06-absolute" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_ABSOLUTE  = 1 << 10;
+        /// Only "07-abs_xy" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_ABS_XY    = 1 << 11;
+        /// Only "08-ind_x" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_IND_X     = 1 << 12;
+        /// Only "09-ind_y" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_IND_Y     = 1 << 13;
+        /// Only "10-branches" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_BRANCHES  = 1 << 14;
+        /// Only "11-stack" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_STACK     = 1 << 15;
+        /// Only "12-jmp_jsr" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_JMP_JSR   = 1 << 16;
+        /// Only "13-rts" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_RTS       = 1 << 17;
+        /// Only "14-rti" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_RTI       = 1 << 18;
+        /// Only "15-brk" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_BRK       = 1 << 19;
+        /// Only "16-special" of instr_test-v5, see `INSTR_SINGLES`
+        const INSTR_SPECIAL   = 1 << 20;
+
+        /// `NESTEST_LOG` runs nestest and compares the state of your CPU before every instruction to [`NESTEST_LOG`],
+        /// see [`run_nestest_log`]. Your CPU has to implement [`CpuRegisters`] for this test.
+        const NESTEST_LOG     = 1 << 21;
+
+        /// `INSTR_TIMING` checks how many cycles every instruction takes, including the unofficial ones, with a
+        /// generated rom that executes every opcode. Instructions that index are timed within a page and crossing a page,
+        /// and branches are timed when they aren't taken, are taken and are taken to another page.
+        /// Your CPU has to implement [`CpuStep`] and count its cycles for this test, see [`CpuRegisters::cycles`],
+        /// otherwise it is skipped.
+        ///
+        /// This is like blargg's instr_timing, which isn't included: it measures time with the APU,
+        /// which your emulator doesn't need to have to pass these tests.
+        const INSTR_TIMING    = 1 << 22;
+
+        /// Like `INSTR_TIMING`, but only checks the official instructions
+        const OFFICIAL_TIMING = 1 << 23;
+
+        /// `INTERRUPTS` checks how your CPU handles an NMI, and an IRQ while the interrupt disable flag is set and clear,
+        /// using a small generated rom. Your CPU has to implement [`CpuInterrupts`] for this test, otherwise it is skipped.
+        const INTERRUPTS      = 1 << 24;
+
+        /// Every part of instr_test-v5 on its own, like the single roms that come with it. A part takes a few million cycles,
+        /// so running one in a `#[test]` of its own is a quick way to check a group of instructions, and `cargo test`
+        /// runs such tests in parallel. Like `ALL_INSTRS` and the single roms, these parts test the unofficial instructions too.
+        const INSTR_SINGLES   = Self::INSTR_BASICS.bits
+            | Self::INSTR_IMPLIED.bits
+            | Self::INSTR_IMMEDIATE.bits
+            | Self::INSTR_ZERO_PAGE.bits
+            | Self::INSTR_ZP_XY.bits
+            | Self::INSTR_ABSOLUTE.bits
+            | Self::INSTR_ABS_XY.bits
+            | Self::INSTR_IND_X.bits
+            | Self::INSTR_IND_Y.bits
+            | Self::INSTR_BRANCHES.bits
+            | Self::INSTR_STACK.bits
+            | Self::INSTR_JMP_JSR.bits
+            | Self::INSTR_RTS.bits
+            | Self::INSTR_RTI.bits
+            | Self::INSTR_BRK.bits
+            | Self::INSTR_SPECIAL.bits;
+
         /// This test selector runs all available tests
         const ALL             = Self::NESTEST.bits | Self::ALL_INSTRS.bits | Self::NROM_TEST.bits;
 
+        /// This test selector runs the tests of `ALL`, and the tests that need your CPU to have some of the optional
+        /// capabilities, which are skipped when it doesn't. Tests that only run a part of another test are left out:
+        /// `OFFICIAL_INSTRS` and `INSTR_SINGLES` are part of `ALL_INSTRS`, and `OFFICIAL_TIMING` is part of `INSTR_TIMING`.
+        const EVERYTHING      = Self::ALL.bits
+            | Self::NESTEST_LOG.bits
+            | Self::RESET.bits
+            | Self::INTERRUPTS.bits
+            | Self::INSTR_TIMING.bits;
+
         /// This test selector runs a default selection of tests: `OFFICIAL_INSTRS` and `NROM_TEST`
         const DEFAULT         = Self::OFFICIAL_INSTRS.bits | Self::NROM_TEST.bits;
     }
@@ -71,170 +466,824 @@ impl Default for TestSelector {
     }
 }
 
+/// A test that can be run by [`run_test`]
+type Test = fn(&TestProgress) -> Result<(), TestOutcome>;
+
+/// All tests that can be selected with a [`TestSelector`], with their names and how long they may take,
+/// in the order they are run
+fn selectable_tests<T: TestableCpu>() -> [(TestSelector, &'static str, Test, Duration); 25] {
+    [
+        (
+            TestSelector::NROM_TEST,
+            "nrom_test",
+            nrom_test::<T>,
+            Duration::from_secs(10),
+        ),
+        (
+            TestSelector::OFFICIAL_INSTRS,
+            "all instructions (official only)",
+            |progress| all_instrs::<T>(true, progress),
+            Duration::from_secs(600),
+        ),
+        (
+            TestSelector::ALL_INSTRS,
+            "all instructions",
+            |progress| all_instrs::<T>(false, progress),
+            Duration::from_secs(600),
+        ),
+        (
+            TestSelector::INSTR_BASICS,
+            "01-basics",
+            instr_single::<T, 1>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_IMPLIED,
+            "02-implied",
+            instr_single::<T, 2>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_IMMEDIATE,
+            "03-immediate",
+            instr_single::<T, 3>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_ZERO_PAGE,
+            "04-zero_page",
+            instr_single::<T, 4>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_ZP_XY,
+            "05-zp_xy",
+            instr_single::<T, 5>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_ABSOLUTE,
+            "06-absolute",
+            instr_single::<T, 6>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_ABS_XY,
+            "07-abs_xy",
+            instr_single::<T, 7>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_IND_X,
+            "08-ind_x",
+            instr_single::<T, 8>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_IND_Y,
+            "09-ind_y",
+            instr_single::<T, 9>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_BRANCHES,
+            "10-branches",
+            instr_single::<T, 10>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_STACK,
+            "11-stack",
+            instr_single::<T, 11>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_JMP_JSR,
+            "12-jmp_jsr",
+            instr_single::<T, 12>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_RTS,
+            "13-rts",
+            instr_single::<T, 13>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_RTI,
+            "14-rti",
+            instr_single::<T, 14>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_BRK,
+            "15-brk",
+            instr_single::<T, 15>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_SPECIAL,
+            "16-special",
+            instr_single::<T, 16>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::NESTEST,
+            "nestest",
+            nestest::<T>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::NESTEST_LOG,
+            "nestest log",
+            nestest_log::<T>,
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::RESET,
+            "reset",
+            reset_test::<T>,
+            Duration::from_secs(10),
+        ),
+        (
+            TestSelector::INTERRUPTS,
+            "interrupts",
+            interrupts_test::<T>,
+            Duration::from_secs(10),
+        ),
+        (
+            TestSelector::OFFICIAL_TIMING,
+            "instruction timing (official only)",
+            |progress| instr_timing::<T>(true, progress),
+            Duration::from_secs(60),
+        ),
+        (
+            TestSelector::INSTR_TIMING,
+            "instruction timing",
+            |progress| instr_timing::<T>(false, progress),
+            Duration::from_secs(60),
+        ),
+    ]
+}
+
 /// The main function of this crate, run this with your CPU as generic parameter and a [`TestSelector`] to run the tests
 pub fn run_tests<T: TestableCpu>(selector: TestSelector) -> Result<(), String> {
-    if selector.contains(TestSelector::NROM_TEST) {
-        nrom_test::<T>()?;
+    TestRunner::default().run_tests::<T>(selector)
+}
+
+/// Like [`run_tests`], but keeps going when a test doesn't pass. The [`TestReport`] it returns contains
+/// the outcome of every selected test, together with the amount of cycles it ran and how long it took.
+pub fn run_tests_report<T: TestableCpu>(selector: TestSelector) -> TestReport {
+    TestRunner::default().run_tests_report::<T>(selector)
+}
+
+/// Where every test is run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Isolation {
+    /// Every test runs on the thread that runs the test suite, which makes it easier to step through a test
+    /// with a debugger. A panic in your CPU is reported as a failed test, but tests can't time out.
+    Inline,
+    /// Every test runs on its own thread, named after the test. A panic in your CPU is reported as a failed test,
+    /// but an abort or stack overflow ends the whole process.
+    #[default]
+    Thread,
+    /// Every test runs in a child process, which runs the current executable again. When the tests are run
+    /// by `cargo test`, only the test that is currently running is run again in the child process.
+    /// A stack overflow is reported as [`TestOutcome::StackOverflow`],
+    /// and an abort or a process that has to be killed as [`TestOutcome::Crashed`].
+    ///
+    /// The current executable should call the test suite in the same way every time it runs,
+    /// so the child process gets to the same test.
+    Process,
+}
+
+/// Runs the tests with different options than [`run_tests`] does
+///
+/// ```no_run
+/// # use tudelft_nes_test::{Isolation, TestRunner, TestSelector, TestableCpu};
+/// # fn test<MyCpu: TestableCpu>() {
+/// let runner = TestRunner {
+///     isolation: Isolation::Process,
+///     ..TestRunner::default()
+/// };
+/// runner.run_tests::<MyCpu>(TestSelector::DEFAULT).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TestRunner {
+    /// Where every test is run
+    pub isolation: Isolation,
+    /// The size of the stack of the thread a test runs on in bytes, when it doesn't run [`Inline`](Isolation::Inline).
+    /// Increase this when your CPU keeps a lot of data on the stack, and overflows the default of 2 MiB.
+    pub stack_size: Option<usize>,
+    /// Writes a trace of every test to a file, see [`Trace`]
+    pub trace: Option<Trace>,
+    /// How long every test may take before it times out, when it doesn't run [`Inline`](Isolation::Inline).
+    /// By default, every test gets a timeout that fits the amount of cycles it runs for.
+    pub timeout: Option<Duration>,
+}
+
+impl TestRunner {
+    /// Like [`run_tests`], with the options of this runner
+    pub fn run_tests<T: TestableCpu>(&self, selector: TestSelector) -> Result<(), String> {
+        for (test_selector, name, test, timeout) in selectable_tests::<T>() {
+            if selector.contains(test_selector) {
+                let result = self.run_test(name, test, timeout);
+                if let TestOutcome::Skipped(capability) = result.outcome {
+                    log::warn!("{name} was skipped: it needs {capability}");
+                    continue;
+                }
+
+                result.into_result().map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(())
     }
 
-    if selector.contains(TestSelector::OFFICIAL_INSTRS) {
-        all_instrs::<T>(true)?;
+    /// Like [`run_tests_report`], with the options of this runner
+    pub fn run_tests_report<T: TestableCpu>(&self, selector: TestSelector) -> TestReport {
+        TestReport {
+            results: selectable_tests::<T>()
+                .into_iter()
+                .filter(|(test_selector, _, _, _)| selector.contains(*test_selector))
+                .map(|(_, name, test, timeout)| self.run_test(name, test, timeout))
+                .collect(),
+        }
     }
 
-    if selector.contains(TestSelector::ALL_INSTRS) {
-        all_instrs::<T>(false)?;
+    /// Like [`run_single_step_tests`], with the options of this runner. The report of the cases can't be sent back
+    /// from a child process, so with [`Isolation::Process`] the cases run on a thread of their own.
+    pub fn run_single_step_tests<T: TestableCpu>(
+        &self,
+        tests: Vec<SingleStepTest>,
+    ) -> Result<SingleStepReport, TestError> {
+        single_step::run_cases::<T>(self, tests)
     }
 
-    if selector.contains(TestSelector::NESTEST) {
-        nestest::<T>()?;
+    /// Like [`diff_cpus`], with the options of this runner. The divergence can't be sent back from a child process,
+    /// so with [`Isolation::Process`] the cpus run on a thread of their own.
+    pub fn diff_cpus<A: TestableCpu, B: TestableCpu>(
+        &self,
+        rom: &[u8],
+        budget: u64,
+    ) -> Result<Option<Divergence>, TestError> {
+        diff::run_diff::<A, B>(self, rom, budget)
+    }
+
+    fn run_test(&self, name: &str, test: Test, timeout: Duration) -> TestResult {
+        let timeout = self.timeout.unwrap_or(timeout);
+        match self.isolation {
+            Isolation::Inline => run_inline(name, test, self.trace.as_ref()),
+            Isolation::Thread => {
+                run_on_thread(name, test, timeout, self.stack_size, self.trace.as_ref())
+            }
+            Isolation::Process => match child_test() {
+                Some(child) if child == name => report_to_parent(&run_on_thread(
+                    name,
+                    test,
+                    timeout,
+                    self.stack_size,
+                    self.trace.as_ref(),
+                )),
+                // a child process skips the tests before the one it was started for
+                Some(_) => TestResult {
+                    name: name.to_string(),
+                    outcome: TestOutcome::Passed,
+                    registers: None,
+                    history: Vec::new(),
+                    cycles: 0,
+                    duration: Duration::ZERO,
+                },
+                None => run_in_child(name, timeout),
+            },
+        }
     }
-    Ok(())
 }
 
 /// Tests the emulator using "all_instrs.nes" or "official_only.nes":
 /// https://github.com/christopherpow/nes-test-roms/tree/master/instr_test-v5
-fn all_instrs<T: TestableCpu + 'static>(only_official: bool) -> Result<(), String> {
-    let (rom, limit) = if only_official {
-        (ROM_OFFICIAL_ONLY, 350)
+fn all_instrs<T: TestableCpu>(
+    only_official: bool,
+    progress: &TestProgress,
+) -> Result<(), TestOutcome> {
+    let (rom, max_cycles) = if only_official {
+        (ROM_OFFICIAL_ONLY, 70_000_000)
     } else {
-        (ROM_ALL_INSTR, 500)
+        (ROM_ALL_INSTR, 100_000_000)
     };
 
-    let handle = thread::spawn(move || {
-        // TODO: make initial program counter obsolete by modifying nestest
-        let mut cpu = T::get_cpu(rom).map_err(|i| TestError::Custom(i.to_string()))?;
-        let mut prev = String::new();
-
-        for i in 0..limit {
-            if let Err(e1) = run_cpu_headless_for(&mut cpu, Mirroring::Horizontal, 200_000) {
-                if let Err(e2) = all_instrs_status_code(&cpu) {
-                    return Err(TestError::Custom(format!(
-                        "{e1}, possibly due to a test that didn't pass: '{e2}'"
-                    )));
-                } else {
-                    return Err(TestError::Custom(format!("{e1}")));
-                }
-            }
+    instr_test::<T>(rom, max_cycles, None, progress)
+}
 
-            let status = read_status_string(&cpu);
+/// Tests the emulator using a single part of instr_test-v5, see [`TestSelector::INSTR_SINGLES`]
+fn instr_single<T: TestableCpu, const N: u8>(progress: &TestProgress) -> Result<(), TestOutcome> {
+    instr_test::<T>(&single_instr_rom(N), 20_000_000, Some(N), progress)
+}
 
-            if status.contains("Failed") {
-                break;
-            }
+/// Runs a rom of instr_test-v5. `single` is the number of the part it runs when it runs only one.
+fn instr_test<T: TestableCpu>(
+    rom: &[u8],
+    max_cycles: u64,
+    single: Option<u8>,
+    progress: &TestProgress,
+) -> Result<(), TestOutcome> {
+    run_on_cpu::<T>(rom, progress, |cpu| {
+        let mut times = SubtestTimes::default();
+        let result = run_blargg(cpu, progress, max_cycles, |cycles, status| {
+            times.status_changed(cycles, status)
+        })?;
 
-            let status = status.split('\n').next().unwrap().trim().to_string();
-            if !status.is_empty() && status != prev {
-                log::info!("{:05}k cycles passed: {}", i * 200, status);
-            }
-            prev = status;
+        if result.passed() {
+            Ok(())
+        } else {
+            Err(TestOutcome::Failed(RomFailure::InstrTest {
+                status: result.status,
+                subtests: times
+                    .results(&result.text, result.cycles)
+                    .into_iter()
+                    .filter(|subtest| single.is_none() || single == Some(subtest.number))
+                    .collect(),
+                text: result.text,
+            }))
         }
+    })
+}
 
-        let result = run_cpu_headless_for(&mut cpu, Mirroring::Horizontal, 200_000);
+/// Runs the nestest rom:
+/// https://github.com/christopherpow/nes-test-roms/blob/master/other/nestest.nes
+fn nestest<T: TestableCpu>(progress: &TestProgress) -> Result<(), TestOutcome> {
+    run_on_cpu::<T>(&automated_nestest_rom(), progress, |cpu| {
+        let result = match cpu.registers() {
+            // stop right at the end, before the cpu executes whatever isn't part of the rom
+            Some(_) => progress
+                .run_until(cpu, NESTEST_CYCLES, NESTEST_END)
+                .map(|_| ()),
+            None => progress.run(cpu, NESTEST_CYCLES),
+        };
 
         match result {
-            Err(e1) => {
-                if let Err(e2) = all_instrs_status_code(&cpu) {
-                    Err(TestError::Custom(format!(
-                        "{e1}, possibly due to a test that didn't pass: '{e2}'"
-                    )))
-                } else {
-                    Err(TestError::Custom(format!("{e1}")))
-                }
-            }
-            Ok(()) => all_instrs_status_code(&cpu),
+            Err(e) => Err(cpu_error(e, nestest_status(cpu).err())),
+            Ok(()) => nestest_status(cpu).map_err(TestOutcome::Failed),
         }
-    });
+    })
+}
 
-    process_handle(
-        &format!(
-            "all instructions{}",
-            if only_official {
-                " (official only)"
-            } else {
-                ""
-            }
-        ),
-        handle,
-    )
+/// The amount of cycles nestest gets to run all its tests
+const NESTEST_CYCLES: usize = 1_000_000;
+
+/// The final RTS of the automated run of nestest. It returns to the address the stack held at reset,
+/// which isn't part of the rom, so an accurate cpu ends up executing whatever is in memory there.
+const NESTEST_END: u16 = 0xC66E;
+
+/// The most cycles a single instruction is allowed to take before [`run_nestest_log`] gives up
+const MAX_INSTRUCTION_CYCLES: u64 = 100;
+
+/// Runs the nestest rom and compares the state of your CPU before every instruction to [`NESTEST_LOG`].
+/// The test stops at the first instruction where the program counter, opcode bytes, registers or cycle count
+/// differ, and reports both the expected and the actual line.
+///
+/// When your CPU implements [`CpuStep`], the test suite steps it one instruction at a time.
+/// Otherwise it ticks your CPU one at a time, and considers every tick in which the program counter changes
+/// to be the start of a new instruction. A CPU that can't step should then execute an instruction in the first tick
+/// of it, because a CPU that moves its program counter as it fetches every byte seems to start a new instruction
+/// on every fetch.
+pub fn run_nestest_log<T: TestableCpu>() -> Result<(), String> {
+    TestRunner::default()
+        .run_test("nestest log", nestest_log::<T>, Duration::from_secs(60))
+        .into_result()
+        .map_err(|e| e.to_string())
 }
 
-/// Runs the nestest rom:
-/// https://github.com/christopherpow/nes-test-roms/blob/master/other/nestest.nes
-fn nestest<T: TestableCpu + 'static>() -> Result<(), String> {
-    let rom = ROM_NESTEST;
+fn nestest_log<T: TestableCpu>(progress: &TestProgress) -> Result<(), TestOutcome> {
+    run_on_cpu::<T>(&automated_nestest_rom(), progress, |cpu| {
+        require(cpu, Capability::Registers)?;
+        let registers = |cpu: &T| Registers::read(cpu.registers().unwrap());
 
-    let handle = thread::spawn(|| {
-        // TODO: make initial program counter obsolete by modifying nestest
-        let mut cpu = T::get_cpu(rom).map_err(|i| TestError::Custom(i.to_string()))?;
-        cpu.set_program_counter(0xC000);
-        let result = run_cpu_headless_for(&mut cpu, Mirroring::Horizontal, 1_000_000);
+        // the cycle of the first instruction in the log, and the cycle count the cpu reported for it
+        let mut first: Option<(u64, u64)> = None;
+        let mut previous = None;
 
-        match result {
-            Err(e1) => {
-                if let Err(e2) =
-                    nestest_status_code(cpu.memory_read(0x0002), cpu.memory_read(0x0003))
-                {
-                    Err(TestError::Custom(format!(
-                        "{e1}, possibly due to a test that didn't pass: '{e2}'"
-                    )))
-                } else {
-                    Err(TestError::Custom(format!("{e1}")))
+        for (number, expected_line) in NESTEST_LOG.lines().enumerate() {
+            let expected = LogLine::parse(expected_line).expect("nestest.log is malformed");
+
+            // the instruction is read once, before the cpu starts executing it
+            let pc = registers(cpu).pc;
+            let bytes = (0..expected.bytes.len() as u16)
+                .map(|i| cpu.memory_read(pc.wrapping_add(i)))
+                .collect();
+            let disassembly = Disassembly::read(cpu, pc).to_string();
+
+            let before = if cpu.steppable().is_some() {
+                let before = registers(cpu);
+                progress.step(cpu).map_err(|e| cpu_error(e, None))?;
+                before
+            } else {
+                tick_instruction(cpu, progress)
+                    .map_err(|e| cpu_error(e, None))?
+                    .ok_or_else(|| {
+                        TestOutcome::Failed(RomFailure::LogStuck {
+                            line: number + 1,
+                            pc,
+                            expected: expected_line.to_string(),
+                        })
+                    })?
+            };
+
+            let mut actual = LogLine {
+                pc,
+                bytes,
+                disassembly,
+                a: before.a,
+                x: before.x,
+                y: before.y,
+                p: (before.p & !0x10) | 0x20,
+                sp: before.sp,
+                cycle: 0,
+            };
+            actual.cycle = match before.cycles {
+                Some(cycles) => {
+                    let (first_cycle, first_reported) =
+                        *first.get_or_insert((expected.cycle, cycles));
+                    first_cycle + cycles.wrapping_sub(first_reported)
                 }
+                None => expected.cycle,
+            };
+
+            let differences = expected.differences(&actual);
+            if !differences.is_empty() {
+                return Err(TestOutcome::Failed(RomFailure::LogMismatch {
+                    line: number + 1,
+                    differences,
+                    previous: previous.map(str::to_string),
+                    expected: expected_line.to_string(),
+                    actual: actual.to_string(),
+                }));
             }
-            Ok(()) => nestest_status_code(cpu.memory_read(0x0002), cpu.memory_read(0x0003)),
+
+            previous = Some(expected_line);
         }
-    });
 
-    process_handle("nestest", handle)
+        Ok(())
+    })
+}
+
+/// Runs a cpu that can't step until it starts executing the next instruction, and returns the registers from right
+/// before the tick in which its program counter changes, or `None` when that doesn't happen in time
+fn tick_instruction<T: TestableCpu>(
+    cpu: &mut T,
+    progress: &TestProgress,
+) -> Result<Option<Registers>, Box<dyn Error>> {
+    let registers = |cpu: &T| Registers::read(cpu.registers().unwrap());
+    let pc = registers(cpu).pc;
+
+    for _ in 0..MAX_INSTRUCTION_CYCLES {
+        let before = registers(cpu);
+        progress.run(cpu, 1)?;
+
+        if registers(cpu).pc != pc {
+            return Ok(Some(before));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Boots a generated rom, resets the cpu and checks the state the rom finds the cpu in
+fn reset_test<T: TestableCpu>(progress: &TestProgress) -> Result<(), TestOutcome> {
+    run_on_cpu::<T>(&reset_test_rom(), progress, |cpu| {
+        require(cpu, Capability::Reset)?;
+
+        progress.run(cpu, 100).map_err(|e| cpu_error(e, None))?;
+        booted(cpu).map_err(TestOutcome::Failed)?;
+
+        cpu.resettable().expect("the cpu can be reset").reset();
+
+        progress.run(cpu, 100).map_err(|e| cpu_error(e, None))?;
+        reset_status(cpu).map_err(TestOutcome::Failed)
+    })
+}
+
+/// Boots a generated rom, interrupts the cpu and checks what the rom saw in its interrupt handlers
+fn interrupts_test<T: TestableCpu>(progress: &TestProgress) -> Result<(), TestOutcome> {
+    run_on_cpu::<T>(&interrupts_test_rom(), progress, |cpu| {
+        require(cpu, Capability::Interrupts)?;
+
+        progress.run(cpu, 100).map_err(|e| cpu_error(e, None))?;
+        interruptible(cpu).set_irq(true);
+        progress.run(cpu, 100).map_err(|e| cpu_error(e, None))?;
+        masked_status(cpu).map_err(TestOutcome::Failed)?;
+
+        interruptible(cpu).nmi();
+        progress.run(cpu, 100).map_err(|e| cpu_error(e, None))?;
+        interruptible(cpu).set_irq(false);
+        interrupts_status(cpu).map_err(TestOutcome::Failed)
+    })
+}
+
+/// The interrupts of a cpu that has [`Capability::Interrupts`]
+fn interruptible<T: TestableCpu>(cpu: &mut T) -> &mut dyn CpuInterrupts {
+    cpu.interruptible().expect("the cpu can be interrupted")
+}
+
+/// Times every instruction with a generated rom, see [`TestSelector::INSTR_TIMING`]
+fn instr_timing<T: TestableCpu>(
+    only_official: bool,
+    progress: &TestProgress,
+) -> Result<(), TestOutcome> {
+    let rom = timing_rom(only_official);
+    run_on_cpu::<T>(&rom.rom, progress, |cpu| {
+        require(cpu, Capability::Step)?;
+        require(cpu, Capability::Cycles)?;
+
+        let mismatches = time_instructions(cpu, &rom, progress)?;
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(TestOutcome::Failed(RomFailure::Timing { mismatches }))
+        }
+    })
 }
 
 /// runs our own nrom test rom
 /// https://gitlab.ewi.tudelft.nl/software-fundamentals/nes-nrom-test
-fn nrom_test<T: TestableCpu + 'static>() -> Result<(), String> {
-    let rom = ROM_NROM_TEST;
+fn nrom_test<T: TestableCpu>(progress: &TestProgress) -> Result<(), TestOutcome> {
+    run_on_cpu::<T>(ROM_NROM_TEST, progress, |cpu| {
+        progress.run(cpu, 100).map_err(|e| cpu_error(e, None))?;
+
+        for (address, expected) in [(0x42, 0x43), (0x43, 0x6A)] {
+            let actual = cpu.memory_read(address);
+            if actual != expected {
+                return Err(TestOutcome::Failed(RomFailure::Memory {
+                    address,
+                    expected,
+                    actual,
+                }));
+            }
+        }
 
-    let handle = thread::spawn(|| {
-        let mut cpu = T::get_cpu(rom).map_err(|i| TestError::Custom(i.to_string()))?;
-        run_cpu_headless_for(&mut cpu, Mirroring::Horizontal, 10)
-            .map_err(|i| TestError::Custom(i.to_string()))?;
+        Ok(())
+    })
+}
 
-        if cpu.memory_read(0x42) != 0x43 {
-            Err(TestError::String(
-                "memory location 0x42 is wrong after executing nrom_test".to_owned(),
-            ))
-        } else if cpu.memory_read(0x43) != 0x6A {
-            Err(TestError::String(
-                "memory location 0x43 is wrong after executing nrom_test".to_owned(),
-            ))
-        } else {
-            Ok(())
+/// Loads `rom` into a new cpu and runs `test` on it.
+/// When the test fails, the registers of the cpu are stored in `progress`.
+fn run_on_cpu<T: TestableCpu>(
+    rom: &[u8],
+    progress: &TestProgress,
+    test: impl FnOnce(&mut T) -> Result<(), TestOutcome>,
+) -> Result<(), TestOutcome> {
+    let mut cpu = T::get_cpu(rom).map_err(|e| TestOutcome::LoadError(e.into()))?;
+    let result = test(&mut cpu);
+    if result.is_err() {
+        *progress.registers.lock().unwrap() = cpu.registers().map(Registers::read);
+    }
+
+    result
+}
+
+/// Skips the test when `cpu` doesn't have `capability`
+fn require<T: TestableCpu>(cpu: &mut T, capability: Capability) -> Result<(), TestOutcome> {
+    if capability.supported_by(cpu) {
+        Ok(())
+    } else {
+        Err(TestOutcome::Skipped(capability))
+    }
+}
+
+/// Turns an error returned while running the cpu into the outcome of the test.
+/// `failure` is what the test rom reported at the time of the error.
+fn cpu_error(error: Box<dyn Error>, failure: Option<RomFailure>) -> TestOutcome {
+    match error.downcast_ref::<CpuStuck>() {
+        Some(stuck) => TestOutcome::Stuck { pc: stuck.pc },
+        None => TestOutcome::CpuError {
+            error: error.into(),
+            failure,
+        },
+    }
+}
+
+/// How many times [`TestProgress::history`] tries to get the history of a test that is still running, 10 ms apart
+const HISTORY_ATTEMPTS: usize = 100;
+
+/// Keeps track of how far a test got. It is shared between the thread running the test
+/// and the thread waiting for it, so it is still available when the test panics or times out.
+#[derive(Debug)]
+struct TestProgress {
+    cycles: AtomicU64,
+    /// The registers of the cpu when the test failed
+    registers: Mutex<Option<Registers>>,
+    /// The program counter after the last tick, or `u32::MAX` when the cpu doesn't give access to it
+    last_pc: AtomicU32,
+    stuck: Mutex<StuckDetector>,
+    /// The instructions the cpu executed most recently
+    history: Mutex<History>,
+    tracer: Mutex<Option<Tracer>>,
+}
+
+impl TestProgress {
+    /// Starts keeping track of the test called `name`, and of its trace when there is one
+    fn new(name: &str, trace: Option<&Trace>) -> Self {
+        Self {
+            cycles: AtomicU64::default(),
+            registers: Mutex::default(),
+            last_pc: AtomicU32::new(u32::MAX),
+            stuck: Mutex::default(),
+            history: Mutex::default(),
+            tracer: Mutex::new(trace.and_then(|trace| Tracer::create(trace, name))),
         }
-    });
+    }
+
+    /// Runs `cpu` for `cycles` cycles, and adds them to the total.
+    /// Returns a [`CpuStuck`] error when the cpu keeps executing the same few instructions.
+    fn run<T: TestableCpu>(&self, cpu: &mut T, cycles: usize) -> Result<(), Box<dyn Error>> {
+        self.cycles.fetch_add(cycles as u64, Ordering::Relaxed);
+        self.run_watched(cpu, cycles, None).0
+    }
+
+    /// Runs `cpu` for at most `cycles` cycles, until the tick after which its program counter is `pc`,
+    /// and adds the cycles it ran to the total. Returns whether it reached `pc`.
+    /// This only stops early when the cpu gives access to its registers.
+    fn run_until<T: TestableCpu>(
+        &self,
+        cpu: &mut T,
+        cycles: usize,
+        pc: u16,
+    ) -> Result<bool, Box<dyn Error>> {
+        let (result, ticks) = self.run_watched(cpu, cycles, Some(pc));
+        self.cycles.fetch_add(ticks, Ordering::Relaxed);
+
+        match result {
+            Err(e) if e.is::<Stopped>() => Ok(true),
+            Err(e) => Err(e),
+            Ok(()) => Ok(false),
+        }
+    }
+
+    /// Runs `cpu` for `cycles` cycles through [`Watched`], and returns the amount of ticks it ran
+    fn run_watched<T: TestableCpu>(
+        &self,
+        cpu: &mut T,
+        cycles: usize,
+        stop_at: Option<u16>,
+    ) -> (Result<(), Box<dyn Error>>, u64) {
+        let mut detector = self.stuck.lock().unwrap();
+        let mut history = self.history.lock().unwrap();
+        let mut tracer = self.tracer.lock().unwrap();
+        let mut cpu = Watched {
+            cpu,
+            detector: &mut detector,
+            last_pc: &self.last_pc,
+            history: &mut history,
+            tracer: tracer.as_mut(),
+            stop_at,
+            ticks: 0,
+        };
+        let result = run_cpu_headless_for(&mut cpu, Mirroring::Horizontal, cycles);
+        (result, cpu.ticks)
+    }
+
+    /// Executes one instruction of a cpu that has [`Capability::Step`], and adds the cycles it took to the total
+    /// when the cpu counts them. A stepped cpu isn't watched, so its instructions are kept in the history here.
+    fn step<T: TestableCpu>(&self, cpu: &mut T) -> Result<(), Box<dyn Error>> {
+        let cycles = |cpu: &T| cpu.registers().and_then(|r| r.cycles());
+        let before = cycles(cpu);
+        if let Some(registers) = cpu.registers() {
+            let registers = Registers::read(registers);
+            self.last_pc
+                .store(u32::from(registers.pc), Ordering::Relaxed);
+            self.history.lock().unwrap().before_step(cpu, registers);
+        }
+
+        let result = cpu.steppable().expect("the cpu can step").step();
+        if let Some((before, after)) = before.zip(cycles(cpu)) {
+            self.cycles
+                .fetch_add(after.wrapping_sub(before), Ordering::Relaxed);
+        }
+
+        result
+    }
+
+    /// The instructions the cpu executed most recently. When the test timed out, the thread running it holds on to
+    /// them while it runs the cpu, so this waits a bit for it to let go.
+    fn history(&self) -> Vec<ExecutedInstruction> {
+        for _ in 0..HISTORY_ATTEMPTS {
+            match self.history.try_lock() {
+                Ok(history) => return history.instructions(),
+                // the cpu panicked while it was running
+                Err(TryLockError::Poisoned(history)) => return history.into_inner().instructions(),
+                Err(TryLockError::WouldBlock) => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+
+        Vec::new()
+    }
 
-    process_handle("nrom_test", handle)
+    /// Collects everything that is known about the test into a [`TestResult`]
+    fn result(&self, name: &str, outcome: TestOutcome, start: Instant) -> TestResult {
+        // the tracer is still in use when the test timed out
+        if let Ok(Some(tracer)) = self.tracer.try_lock().as_deref_mut() {
+            tracer.flush();
+        }
+
+        let history = if outcome.passed() || outcome.skipped() {
+            Vec::new()
+        } else {
+            self.history()
+        };
+
+        TestResult {
+            name: name.to_string(),
+            outcome,
+            registers: *self.registers.lock().unwrap(),
+            history,
+            cycles: self.cycles.load(Ordering::Relaxed),
+            duration: start.elapsed(),
+        }
+    }
 }
 
-#[derive(Debug, Error)]
-enum TestError {
-    #[error("{0}")]
-    Custom(String),
-    #[error("{0}")]
-    String(String),
+/// Runs `test` on the current thread, and turns whatever happened into a [`TestResult`]
+fn run_inline(
+    name: &str,
+    test: impl FnOnce(&TestProgress) -> Result<(), TestOutcome>,
+    trace: Option<&Trace>,
+) -> TestResult {
+    let progress = TestProgress::new(name, trace);
+    let start = Instant::now();
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| test(&progress)));
+    progress.result(name, process_result(name, result), start)
 }
 
-fn process_handle(name: &str, handle: JoinHandle<Result<(), TestError>>) -> Result<(), String> {
-    match handle.join() {
+/// Runs `test` on a separate thread, and turns whatever happened into a [`TestResult`].
+/// When the test takes longer than `timeout`, it is reported as timed out and its thread is left running in the background.
+fn run_on_thread(
+    name: &str,
+    test: impl FnOnce(&TestProgress) -> Result<(), TestOutcome> + Send + 'static,
+    timeout: Duration,
+    stack_size: Option<usize>,
+    trace: Option<&Trace>,
+) -> TestResult {
+    let progress = Arc::new(TestProgress::new(name, trace));
+    let start = Instant::now();
+    let (done, finished) = mpsc::channel();
+
+    let mut builder = thread::Builder::new().name(format!("tudelft-nes-test: {name}"));
+    if let Some(stack_size) = stack_size {
+        builder = builder.stack_size(stack_size);
+    }
+
+    let spawned = builder.spawn({
+        let progress = Arc::clone(&progress);
+        move || {
+            let result = test(&progress);
+            // the receiver is gone when the test timed out
+            let _ = done.send(());
+            result
+        }
+    });
+    let handle = match spawned {
+        Ok(handle) => handle,
+        Err(e) => {
+            let outcome = TestOutcome::Crashed(format!("couldn't start the test thread: {e}"));
+            return progress.result(name, outcome, start);
+        }
+    };
+
+    // the sender is dropped without sending when the test panics
+    let outcome = match finished.recv_timeout(timeout) {
+        Err(RecvTimeoutError::Timeout) => {
+            log::warn!("{name} didn't finish within {timeout:?}");
+            let last_pc = match u16::try_from(progress.last_pc.load(Ordering::Relaxed)) {
+                Ok(pc) => {
+                    // the history is still in use when the cpu hangs in a tick
+                    let last = progress.history.try_lock().ok().and_then(|h| h.last());
+                    format!(
+                        ", the last known program counter was {}",
+                        hang_location(pc, last.as_ref())
+                    )
+                }
+                Err(_) => String::new(),
+            };
+            TestOutcome::TimedOut(format!(
+                "the test didn't finish within {timeout:?}{last_pc}"
+            ))
+        }
         // <- waits for the thread to complete or panic
+        Ok(()) | Err(RecvTimeoutError::Disconnected) => process_result(name, handle.join()),
+    };
+
+    progress.result(name, outcome, start)
+}
+
+fn process_result(name: &str, result: thread::Result<Result<(), TestOutcome>>) -> TestOutcome {
+    match result {
         Ok(Ok(_)) => {
             log::info!("{name} finished succesfully");
-            Ok(())
+            TestOutcome::Passed
         }
-        Ok(Err(e)) => match e {
-            TestError::Custom(e) => Err(format!(
-                "cpu failed while running test {name} with custom error message {e}"
-            )),
-            TestError::String(e) => Err(format!("cpu didn't pass test {name}: '{e}'")),
-        },
+        Ok(Err(e)) => e,
         Err(e) => {
             let err_msg = match (e.downcast_ref::<&str>(), e.downcast_ref::<String>()) {
                 (Some(&s), _) => s,
@@ -242,9 +1291,265 @@ fn process_handle(name: &str, handle: JoinHandle<Result<(), TestError>>) -> Resu
                 (None, None) => "<No panic info>",
             };
 
-            Err(format!(
-                "cpu implementation panicked while running test {name}: {err_msg}"
-            ))
+            TestOutcome::Panicked(err_msg.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cpu with none of the optional capabilities, which doesn't do anything
+    struct Idle;
+
+    impl Cpu for Idle {
+        fn tick(&mut self, _ppu: &mut tudelft_nes_ppu::Ppu) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn ppu_read_chr_rom(&self, _offset: u16) -> u8 {
+            0
+        }
+
+        fn non_maskable_interrupt(&mut self) {}
+    }
+
+    impl TestableCpu for Idle {
+        fn get_cpu(_rom: &[u8]) -> Result<Self, Box<dyn Error>> {
+            Ok(Self)
+        }
+
+        fn memory_read(&self, _address: u16) -> u8 {
+            0
+        }
+    }
+
+    /// A cpu that moves its program counter one byte ahead every tick
+    #[derive(Default)]
+    struct Counting {
+        pc: u16,
+    }
+
+    impl Cpu for Counting {
+        fn tick(&mut self, _ppu: &mut tudelft_nes_ppu::Ppu) -> Result<(), Box<dyn Error>> {
+            self.pc += 1;
+            Ok(())
+        }
+
+        fn ppu_read_chr_rom(&self, _offset: u16) -> u8 {
+            0
+        }
+
+        fn non_maskable_interrupt(&mut self) {}
+    }
+
+    impl TestableCpu for Counting {
+        fn get_cpu(_rom: &[u8]) -> Result<Self, Box<dyn Error>> {
+            Ok(Self::default())
+        }
+
+        fn memory_read(&self, _address: u16) -> u8 {
+            0
+        }
+
+        fn registers(&self) -> Option<&dyn CpuRegisters> {
+            Some(self)
+        }
+    }
+
+    impl CpuRegisters for Counting {
+        fn accumulator(&self) -> u8 {
+            0
+        }
+
+        fn x_register(&self) -> u8 {
+            0
+        }
+
+        fn y_register(&self) -> u8 {
+            0
+        }
+
+        fn status(&self) -> u8 {
+            0
+        }
+
+        fn stack_pointer(&self) -> u8 {
+            0
+        }
+
+        fn program_counter(&self) -> u16 {
+            self.pc
+        }
+    }
+
+    #[test]
+    fn run_until_stops_right_after_reaching_the_address() {
+        let progress = TestProgress::new("counting", None);
+        let mut cpu = Counting::default();
+
+        assert!(progress.run_until(&mut cpu, 1000, 300).unwrap());
+        assert_eq!(cpu.pc, 300);
+        assert_eq!(progress.cycles.load(Ordering::Relaxed), 300);
+
+        assert!(!progress.run_until(&mut cpu, 100, 300).unwrap());
+        assert_eq!(cpu.pc, 400);
+        assert_eq!(progress.cycles.load(Ordering::Relaxed), 400);
+    }
+
+    #[test]
+    fn everything_runs_every_test_once() {
+        let selectable = selectable_tests::<Idle>()
+            .into_iter()
+            .fold(TestSelector::empty(), |all, (selector, ..)| all | selector);
+        let parts = TestSelector::OFFICIAL_INSTRS
+            | TestSelector::INSTR_SINGLES
+            | TestSelector::OFFICIAL_TIMING;
+
+        assert_eq!(TestSelector::EVERYTHING | parts, selectable);
+        assert!(!TestSelector::EVERYTHING.intersects(parts));
+        assert!(TestSelector::EVERYTHING.contains(TestSelector::ALL));
+    }
+
+    #[test]
+    fn reset_is_skipped_without_running() {
+        let report = run_tests_report::<Idle>(TestSelector::RESET);
+
+        assert_eq!(
+            report.results[0].outcome,
+            TestOutcome::Skipped(Capability::Reset)
+        );
+        assert_eq!(report.results[0].cycles, 0);
+    }
+
+    #[cfg(feature = "reference")]
+    #[test]
+    fn reference_cpu_handles_interrupts() {
+        run_tests::<ReferenceCpu>(TestSelector::INTERRUPTS).unwrap();
+    }
+
+    #[test]
+    fn interrupts_are_skipped_without_running() {
+        let report = run_tests_report::<Idle>(TestSelector::INTERRUPTS);
+
+        assert_eq!(
+            report.results[0].outcome,
+            TestOutcome::Skipped(Capability::Interrupts)
+        );
+        assert_eq!(report.results[0].cycles, 0);
+    }
+
+    #[test]
+    fn timing_is_skipped_without_stepping() {
+        let report = run_tests_report::<Counting>(TestSelector::INSTR_TIMING);
+
+        for result in &report.results {
+            assert_eq!(result.outcome, TestOutcome::Skipped(Capability::Step));
+            assert_eq!(result.cycles, 0);
+        }
+    }
+
+    #[cfg(feature = "reference")]
+    #[test]
+    fn reference_cpu_passes_instr_timing() {
+        run_tests::<ReferenceCpu>(TestSelector::INSTR_TIMING).unwrap();
+    }
+
+    #[cfg(feature = "reference")]
+    #[test]
+    fn reference_cpu_matches_nestest_log() {
+        run_nestest_log::<ReferenceCpu>().unwrap();
+    }
+
+    /// The reference cpu, with a program counter that moves ahead in every other tick, like the program counter of
+    /// a cpu that moves it as it fetches the bytes of an instruction
+    #[cfg(feature = "reference")]
+    struct Fetching {
+        cpu: ReferenceCpu,
+        fetching: bool,
+    }
+
+    #[cfg(feature = "reference")]
+    impl Cpu for Fetching {
+        fn tick(&mut self, ppu: &mut tudelft_nes_ppu::Ppu) -> Result<(), Box<dyn Error>> {
+            self.fetching = !self.fetching;
+            self.cpu.tick(ppu)
+        }
+
+        fn ppu_read_chr_rom(&self, offset: u16) -> u8 {
+            self.cpu.ppu_read_chr_rom(offset)
+        }
+
+        fn non_maskable_interrupt(&mut self) {
+            self.cpu.non_maskable_interrupt()
+        }
+    }
+
+    #[cfg(feature = "reference")]
+    impl TestableCpu for Fetching {
+        fn get_cpu(rom: &[u8]) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {
+                cpu: ReferenceCpu::get_cpu(rom)?,
+                fetching: false,
+            })
+        }
+
+        fn memory_read(&self, address: u16) -> u8 {
+            self.cpu.memory_read(address)
+        }
+
+        fn registers(&self) -> Option<&dyn CpuRegisters> {
+            Some(self)
         }
+
+        fn steppable(&mut self) -> Option<&mut dyn CpuStep> {
+            Some(self)
+        }
+    }
+
+    #[cfg(feature = "reference")]
+    impl CpuRegisters for Fetching {
+        fn accumulator(&self) -> u8 {
+            self.cpu.accumulator()
+        }
+
+        fn x_register(&self) -> u8 {
+            self.cpu.x_register()
+        }
+
+        fn y_register(&self) -> u8 {
+            self.cpu.y_register()
+        }
+
+        fn status(&self) -> u8 {
+            self.cpu.status()
+        }
+
+        fn stack_pointer(&self) -> u8 {
+            self.cpu.stack_pointer()
+        }
+
+        fn program_counter(&self) -> u16 {
+            self.cpu.program_counter() + u16::from(self.fetching)
+        }
+
+        fn cycles(&self) -> Option<u64> {
+            self.cpu.cycles()
+        }
+    }
+
+    #[cfg(feature = "reference")]
+    impl CpuStep for Fetching {
+        fn step(&mut self) -> Result<(), Box<dyn Error>> {
+            self.fetching = false;
+            self.cpu.step()
+        }
+    }
+
+    #[cfg(feature = "reference")]
+    #[test]
+    fn cpus_that_can_step_are_stepped_through_the_nestest_log() {
+        run_nestest_log::<Fetching>().unwrap();
     }
 }
@@ -1,714 +1,1188 @@
-use crate::TestError;
-
-pub(crate) fn nestest_status_code(eb2: u8, eb3: u8) -> Result<(), TestError> {
-    match (eb2, eb3) {
-        (0, 0) => Ok(()),
-
-        // branch tests
-        // ------------
-        (0x001, _) => Err(TestError::String("BCS failed to branch".into())),
-        (0x002, _) => Err(TestError::String(
-            "BCS branched when it shouldn't have".into(),
-        )),
-        (0x003, _) => Err(TestError::String(
-            "BCC branched when it shouldn't have".into(),
-        )),
-        (0x004, _) => Err(TestError::String("BCC failed to branch".into())),
-        (0x005, _) => Err(TestError::String("BEQ failed to branch".into())),
-        (0x006, _) => Err(TestError::String(
-            "BEQ branched when it shouldn't have".into(),
-        )),
-        (0x007, _) => Err(TestError::String("BNE failed to branch".into())),
-        (0x008, _) => Err(TestError::String(
-            "BNE branched when it shouldn't have".into(),
-        )),
-        (0x009, _) => Err(TestError::String("BVS failed to branch".into())),
-        (0x00A, _) => Err(TestError::String(
-            "BVC branched when it shouldn't have".into(),
-        )),
-        (0x00B, _) => Err(TestError::String("BVC failed to branch".into())),
-        (0x00C, _) => Err(TestError::String(
-            "BVS branched when it shouldn't have".into(),
-        )),
-        (0x00D, _) => Err(TestError::String("BPL failed to branch".into())),
-        (0x00E, _) => Err(TestError::String(
-            "BPL branched when it shouldn't have".into(),
-        )),
-        (0x00F, _) => Err(TestError::String("BMI failed to branch".into())),
-        (0x010, _) => Err(TestError::String(
-            "BMI branched when it shouldn't have".into(),
-        )),
-
-        // flag tests
-        // ----------
-        (0x011, _) => Err(TestError::String("PHP/flags failure (bits set)".into())),
-        (0x012, _) => Err(TestError::String("PHP/flags failure (bits clear)".into())),
-        (0x013, _) => Err(TestError::String(
-            "PHP/flags failure (misc bit states)".into(),
-        )),
-        (0x014, _) => Err(TestError::String(
-            "PLP/flags failure (misc bit states)".into(),
-        )),
-        (0x015, _) => Err(TestError::String(
-            "PLP/flags failure (misc bit states)".into(),
-        )),
-        (0x016, _) => Err(TestError::String(
-            "PHA/PLA failure (PLA didn't affect Z and N properly)".into(),
-        )),
-        (0x017, _) => Err(TestError::String(
-            "PHA/PLA failure (PLA didn't affect Z and N properly)".into(),
-        )),
-
-        // immediate instruction tests
-        // ---------------------------
-        (0x018, _) => Err(TestError::String("ORA # failure".into())),
-        (0x019, _) => Err(TestError::String("ORA # failure".into())),
-        (0x01A, _) => Err(TestError::String("AND # failure".into())),
-        (0x01B, _) => Err(TestError::String("AND # failure".into())),
-        (0x01C, _) => Err(TestError::String("EOR # failure".into())),
-        (0x01D, _) => Err(TestError::String("EOR # failure".into())),
-        (0x01E, _) => Err(TestError::String(
-            "ADC # failure (overflow/carry problems)".into(),
-        )),
-        (0x01F, _) => Err(TestError::String(
-            "ADC # failure (decimal mode was turned on)".into(),
-        )),
-        (0x020, _) => Err(TestError::String("ADC # failure".into())),
-        (0x021, _) => Err(TestError::String("ADC # failure".into())),
-        (0x022, _) => Err(TestError::String("ADC # failure".into())),
-        (0x023, _) => Err(TestError::String(
-            "LDA # failure (didn't set N and Z correctly)".into(),
-        )),
-        (0x024, _) => Err(TestError::String(
-            "LDA # failure (didn't set N and Z correctly)".into(),
-        )),
-        (0x025, _) => Err(TestError::String("CMP # failure (messed up flags)".into())),
-        (0x026, _) => Err(TestError::String("CMP # failure (messed up flags)".into())),
-        (0x027, _) => Err(TestError::String("CMP # failure (messed up flags)".into())),
-        (0x028, _) => Err(TestError::String("CMP # failure (messed up flags)".into())),
-        (0x029, _) => Err(TestError::String("CMP # failure (messed up flags)".into())),
-        (0x02A, _) => Err(TestError::String("CMP # failure (messed up flags)".into())),
-        (0x02B, _) => Err(TestError::String("CPY # failure (messed up flags)".into())),
-        (0x02C, _) => Err(TestError::String("CPY # failure (messed up flags)".into())),
-        (0x02D, _) => Err(TestError::String("CPY # failure (messed up flags)".into())),
-        (0x02E, _) => Err(TestError::String("CPY # failure (messed up flags)".into())),
-        (0x02F, _) => Err(TestError::String("CPY # failure (messed up flags)".into())),
-        (0x030, _) => Err(TestError::String("CPY # failure (messed up flags)".into())),
-        (0x031, _) => Err(TestError::String("CPY # failure (messed up flags)".into())),
-        (0x032, _) => Err(TestError::String("CPX # failure (messed up flags)".into())),
-        (0x033, _) => Err(TestError::String("CPX # failure (messed up flags)".into())),
-        (0x034, _) => Err(TestError::String("CPX # failure (messed up flags)".into())),
-        (0x035, _) => Err(TestError::String("CPX # failure (messed up flags)".into())),
-        (0x036, _) => Err(TestError::String("CPX # failure (messed up flags)".into())),
-        (0x037, _) => Err(TestError::String("CPX # failure (messed up flags)".into())),
-        (0x038, _) => Err(TestError::String("CPX # failure (messed up flags)".into())),
-        (0x039, _) => Err(TestError::String(
-            "LDX # failure (didn't set N and Z correctly)".into(),
-        )),
-        (0x03A, _) => Err(TestError::String(
-            "LDX # failure (didn't set N and Z correctly)".into(),
-        )),
-        (0x03B, _) => Err(TestError::String(
-            "LDY # failure (didn't set N and Z correctly)".into(),
-        )),
-        (0x03C, _) => Err(TestError::String(
-            "LDY # failure (didn't set N and Z correctly)".into(),
-        )),
-        (0x03D, _) => Err(TestError::String(
-            "compare(s) stored the result in a register (whoops!)".into(),
-        )),
-        (0x071, _) => Err(TestError::String("SBC # failure".into())),
-        (0x072, _) => Err(TestError::String("SBC # failure".into())),
-        (0x073, _) => Err(TestError::String("SBC # failure".into())),
-        (0x074, _) => Err(TestError::String("SBC # failure".into())),
-        (0x075, _) => Err(TestError::String("SBC # failure".into())),
-
-        // implied instruction tests
-        // -------------------------
-        (0x03E, _) => Err(TestError::String(
-            "INX/DEX/INY/DEY did something bad".into(),
-        )),
-        (0x03F, _) => Err(TestError::String(
-            "INY/DEY messed up overflow or carry".into(),
-        )),
-        (0x040, _) => Err(TestError::String(
-            "INX/DEX messed up overflow or carry".into(),
-        )),
-        (0x041, _) => Err(TestError::String(
-            "TAY did something bad (changed wrong regs, messed up flags)".into(),
-        )),
-        (0x042, _) => Err(TestError::String(
-            "TAX did something bad (changed wrong regs, messed up flags)".into(),
-        )),
-        (0x043, _) => Err(TestError::String(
-            "TYA did something bad (changed wrong regs, messed up flags)".into(),
-        )),
-        (0x044, _) => Err(TestError::String(
-            "TXA did something bad (changed wrong regs, messed up flags)".into(),
-        )),
-        (0x045, _) => Err(TestError::String(
-            "TXS didn't set flags right, or TSX touched flags and it shouldn't have".into(),
-        )),
-
-        // stack tests
-        // -----------
-        (0x046, _) => Err(TestError::String(
-            "wrong data popped, or data not in right location on stack".into(),
-        )),
-        (0x047, _) => Err(TestError::String("JSR didn't work as expected".into())),
-        (0x048, _) => Err(TestError::String(
-            "RTS/JSR shouldn't have affected flags".into(),
-        )),
-        (0x049, _) => Err(TestError::String(
-            "RTI/RTS didn't work right when return addys/data were manually pushed".into(),
-        )),
-
-        // accumulator tests
-        // -----------------
-        (0x04A, _) => Err(TestError::String("LSR A  failed".into())),
-        (0x04B, _) => Err(TestError::String("ASL A  failed".into())),
-        (0x04C, _) => Err(TestError::String("ROR A  failed".into())),
-        (0x04D, _) => Err(TestError::String("ROL A  failed".into())),
-
-        // (indirect,x) tests
-        // ------------------
-        (0x058, _) => Err(TestError::String(
-            "LDA didn't load the data it expected to load".into(),
-        )),
-        (0x059, _) => Err(TestError::String(
-            "STA didn't store the data where it was supposed to".into(),
-        )),
-        (0x05A, _) => Err(TestError::String("ORA failure".into())),
-        (0x05B, _) => Err(TestError::String("ORA failure".into())),
-        (0x05C, _) => Err(TestError::String("AND failure".into())),
-        (0x05D, _) => Err(TestError::String("AND failure".into())),
-        (0x05E, _) => Err(TestError::String("EOR failure".into())),
-        (0x05F, _) => Err(TestError::String("EOR failure".into())),
-        (0x060, _) => Err(TestError::String("ADC failure".into())),
-        (0x061, _) => Err(TestError::String("ADC failure".into())),
-        (0x062, _) => Err(TestError::String("ADC failure".into())),
-        (0x063, _) => Err(TestError::String("ADC failure".into())),
-        (0x064, _) => Err(TestError::String("ADC failure".into())),
-        (0x065, _) => Err(TestError::String("CMP failure".into())),
-        (0x066, _) => Err(TestError::String("CMP failure".into())),
-        (0x067, _) => Err(TestError::String("CMP failure".into())),
-        (0x068, _) => Err(TestError::String("CMP failure".into())),
-        (0x069, _) => Err(TestError::String("CMP failure".into())),
-        (0x06A, _) => Err(TestError::String("CMP failure".into())),
-        (0x06B, _) => Err(TestError::String("CMP failure".into())),
-        (0x06C, _) => Err(TestError::String("SBC failure".into())),
-        (0x06D, _) => Err(TestError::String("SBC failure".into())),
-        (0x06E, _) => Err(TestError::String("SBC failure".into())),
-        (0x06F, _) => Err(TestError::String("SBC failure".into())),
-        (0x070, _) => Err(TestError::String("SBC failure".into())),
-
-        // zeropage tests
-        // --------------
-        (0x076, _) => Err(TestError::String(
-            "LDA didn't set the flags properly".into(),
-        )),
-        (0x077, _) => Err(TestError::String("STA affected flags it shouldn't".into())),
-        (0x078, _) => Err(TestError::String(
-            "LDY didn't set the flags properly".into(),
-        )),
-        (0x079, _) => Err(TestError::String("STY affected flags it shouldn't".into())),
-        (0x07A, _) => Err(TestError::String(
-            "LDX didn't set the flags properly".into(),
-        )),
-        (0x07B, _) => Err(TestError::String("STX affected flags it shouldn't".into())),
-        (0x07C, _) => Err(TestError::String("BIT failure".into())),
-        (0x07D, _) => Err(TestError::String("BIT failure".into())),
-        (0x07E, _) => Err(TestError::String("ORA failure".into())),
-        (0x07F, _) => Err(TestError::String("ORA failure".into())),
-        (0x080, _) => Err(TestError::String("AND failure".into())),
-        (0x081, _) => Err(TestError::String("AND failure".into())),
-        (0x082, _) => Err(TestError::String("EOR failure".into())),
-        (0x083, _) => Err(TestError::String("EOR failure".into())),
-        (0x084, _) => Err(TestError::String("ADC failure".into())),
-        (0x085, _) => Err(TestError::String("ADC failure".into())),
-        (0x086, _) => Err(TestError::String("ADC failure".into())),
-        (0x087, _) => Err(TestError::String("ADC failure".into())),
-        (0x088, _) => Err(TestError::String("ADC failure".into())),
-        (0x089, _) => Err(TestError::String("CMP failure".into())),
-        (0x08A, _) => Err(TestError::String("CMP failure".into())),
-        (0x08B, _) => Err(TestError::String("CMP failure".into())),
-        (0x08C, _) => Err(TestError::String("CMP failure".into())),
-        (0x08D, _) => Err(TestError::String("CMP failure".into())),
-        (0x08E, _) => Err(TestError::String("CMP failure".into())),
-        (0x08F, _) => Err(TestError::String("CMP failure".into())),
-        (0x090, _) => Err(TestError::String("SBC failure".into())),
-        (0x091, _) => Err(TestError::String("SBC failure".into())),
-        (0x092, _) => Err(TestError::String("SBC failure".into())),
-        (0x093, _) => Err(TestError::String("SBC failure".into())),
-        (0x094, _) => Err(TestError::String("SBC failure".into())),
-        (0x095, _) => Err(TestError::String("CPX failure".into())),
-        (0x096, _) => Err(TestError::String("CPX failure".into())),
-        (0x097, _) => Err(TestError::String("CPX failure".into())),
-        (0x098, _) => Err(TestError::String("CPX failure".into())),
-        (0x099, _) => Err(TestError::String("CPX failure".into())),
-        (0x09A, _) => Err(TestError::String("CPX failure".into())),
-        (0x09B, _) => Err(TestError::String("CPX failure".into())),
-        (0x09C, _) => Err(TestError::String("CPY failure".into())),
-        (0x09D, _) => Err(TestError::String("CPY failure".into())),
-        (0x09E, _) => Err(TestError::String("CPY failure".into())),
-        (0x09F, _) => Err(TestError::String("CPY failure".into())),
-        (0x0A0, _) => Err(TestError::String("CPY failure".into())),
-        (0x0A1, _) => Err(TestError::String("CPY failure".into())),
-        (0x0A2, _) => Err(TestError::String("CPY failure".into())),
-        (0x0A3, _) => Err(TestError::String("LSR failure".into())),
-        (0x0A4, _) => Err(TestError::String("LSR failure".into())),
-        (0x0A5, _) => Err(TestError::String("ASL failure".into())),
-        (0x0A6, _) => Err(TestError::String("ASL failure".into())),
-        (0x0A7, _) => Err(TestError::String("ROL failure".into())),
-        (0x0A8, _) => Err(TestError::String("ROL failure".into())),
-        (0x0A9, _) => Err(TestError::String("ROR failure".into())),
-        (0x0AA, _) => Err(TestError::String("ROR failure".into())),
-        (0x0AB, _) => Err(TestError::String("INC failure".into())),
-        (0x0AC, _) => Err(TestError::String("INC failure".into())),
-        (0x0AD, _) => Err(TestError::String("DEC failure".into())),
-        (0x0AE, _) => Err(TestError::String("DEC failure".into())),
-        (0x0AF, _) => Err(TestError::String("DEC failure".into())),
-
-        // Absolute tests
-        // --------------
-        (0x0B0, _) => Err(TestError::String(
-            "LDA didn't set the flags properly".into(),
-        )),
-        (0x0B1, _) => Err(TestError::String("STA affected flags it shouldn't".into())),
-        (0x0B2, _) => Err(TestError::String(
-            "LDY didn't set the flags properly".into(),
-        )),
-        (0x0B3, _) => Err(TestError::String("STY affected flags it shouldn't".into())),
-        (0x0B4, _) => Err(TestError::String(
-            "LDX didn't set the flags properly".into(),
-        )),
-        (0x0B5, _) => Err(TestError::String("STX affected flags it shouldn't".into())),
-        (0x0B6, _) => Err(TestError::String("BIT failure".into())),
-        (0x0B7, _) => Err(TestError::String("BIT failure".into())),
-        (0x0B8, _) => Err(TestError::String("ORA failure".into())),
-        (0x0B9, _) => Err(TestError::String("ORA failure".into())),
-        (0x0BA, _) => Err(TestError::String("AND failure".into())),
-        (0x0BB, _) => Err(TestError::String("AND failure".into())),
-        (0x0BC, _) => Err(TestError::String("EOR failure".into())),
-        (0x0BD, _) => Err(TestError::String("EOR failure".into())),
-        (0x0BE, _) => Err(TestError::String("ADC failure".into())),
-        (0x0BF, _) => Err(TestError::String("ADC failure".into())),
-        (0x0C0, _) => Err(TestError::String("ADC failure".into())),
-        (0x0C1, _) => Err(TestError::String("ADC failure".into())),
-        (0x0C2, _) => Err(TestError::String("ADC failure".into())),
-        (0x0C3, _) => Err(TestError::String("CMP failure".into())),
-        (0x0C4, _) => Err(TestError::String("CMP failure".into())),
-        (0x0C5, _) => Err(TestError::String("CMP failure".into())),
-        (0x0C6, _) => Err(TestError::String("CMP failure".into())),
-        (0x0C7, _) => Err(TestError::String("CMP failure".into())),
-        (0x0C8, _) => Err(TestError::String("CMP failure".into())),
-        (0x0C9, _) => Err(TestError::String("CMP failure".into())),
-        (0x0CA, _) => Err(TestError::String("SBC failure".into())),
-        (0x0CB, _) => Err(TestError::String("SBC failure".into())),
-        (0x0CC, _) => Err(TestError::String("SBC failure".into())),
-        (0x0CD, _) => Err(TestError::String("SBC failure".into())),
-        (0x0CE, _) => Err(TestError::String("SBC failure".into())),
-        (0x0CF, _) => Err(TestError::String("CPX failure".into())),
-        (0x0D0, _) => Err(TestError::String("CPX failure".into())),
-        (0x0D1, _) => Err(TestError::String("CPX failure".into())),
-        (0x0D2, _) => Err(TestError::String("CPX failure".into())),
-        (0x0D3, _) => Err(TestError::String("CPX failure".into())),
-        (0x0D4, _) => Err(TestError::String("CPX failure".into())),
-        (0x0D5, _) => Err(TestError::String("CPX failure".into())),
-        (0x0D6, _) => Err(TestError::String("CPY failure".into())),
-        (0x0D7, _) => Err(TestError::String("CPY failure".into())),
-        (0x0D8, _) => Err(TestError::String("CPY failure".into())),
-        (0x0D9, _) => Err(TestError::String("CPY failure".into())),
-        (0x0DA, _) => Err(TestError::String("CPY failure".into())),
-        (0x0DB, _) => Err(TestError::String("CPY failure".into())),
-        (0x0DC, _) => Err(TestError::String("CPY failure".into())),
-        (0x0DD, _) => Err(TestError::String("LSR failure".into())),
-        (0x0DE, _) => Err(TestError::String("LSR failure".into())),
-        (0x0DF, _) => Err(TestError::String("ASL failure".into())),
-        (0x0E0, _) => Err(TestError::String("ASL failure".into())),
-        (0x0E1, _) => Err(TestError::String("ROR failure".into())),
-        (0x0E2, _) => Err(TestError::String("ROR failure".into())),
-        (0x0E3, _) => Err(TestError::String("ROL failure".into())),
-        (0x0E4, _) => Err(TestError::String("ROL failure".into())),
-        (0x0E5, _) => Err(TestError::String("INC failure".into())),
-        (0x0E6, _) => Err(TestError::String("INC failure".into())),
-        (0x0E7, _) => Err(TestError::String("DEC failure".into())),
-        (0x0E8, _) => Err(TestError::String("DEC failure".into())),
-        (0x0E9, _) => Err(TestError::String("DEC failure".into())),
-
-        // (indirect),y tests
-        // ------------------
-        (0x0EA, _) => Err(TestError::String(
-            "LDA didn't load what it was supposed to".into(),
-        )),
-        (0x0EB, _) => Err(TestError::String(
-            "read location should've wrapped around ffffh to 0000h".into(),
-        )),
-        (0x0EC, _) => Err(TestError::String(
-            "should've wrapped zeropage address".into(),
-        )),
-        (0x0ED, _) => Err(TestError::String("ORA failure".into())),
-        (0x0EE, _) => Err(TestError::String("ORA failure".into())),
-        (0x0EF, _) => Err(TestError::String("AND failure".into())),
-        (0x0F0, _) => Err(TestError::String("AND failure".into())),
-        (0x0F1, _) => Err(TestError::String("EOR failure".into())),
-        (0x0F2, _) => Err(TestError::String("EOR failure".into())),
-        (0x0F3, _) => Err(TestError::String("ADC failure".into())),
-        (0x0F4, _) => Err(TestError::String("ADC failure".into())),
-        (0x0F5, _) => Err(TestError::String("ADC failure".into())),
-        (0x0F6, _) => Err(TestError::String("ADC failure".into())),
-        (0x0F7, _) => Err(TestError::String("ADC failure".into())),
-        (0x0F8, _) => Err(TestError::String("CMP failure".into())),
-        (0x0F9, _) => Err(TestError::String("CMP failure".into())),
-        (0x0FA, _) => Err(TestError::String("CMP failure".into())),
-        (0x0FB, _) => Err(TestError::String("CMP failure".into())),
-        (0x0FC, _) => Err(TestError::String("CMP failure".into())),
-        (0x0FD, _) => Err(TestError::String("CMP failure".into())),
-        (0x0FE, _) => Err(TestError::String("CMP failure".into())),
-
-        (_, 0x001) => Err(TestError::String("SBC failure".into())),
-        (_, 0x002) => Err(TestError::String("SBC failure".into())),
-        (_, 0x003) => Err(TestError::String("SBC failure".into())),
-        (_, 0x004) => Err(TestError::String("SBC failure".into())),
-        (_, 0x005) => Err(TestError::String("SBC failure".into())),
-        (_, 0x006) => Err(TestError::String("STA failure".into())),
-        (_, 0x007) => Err(TestError::String(
-            "JMP () data reading didn't wrap properly (this fails on a 65C02)".into(),
-        )),
-
-        // zeropage,x tests
-        // ----------------
-        (_, 0x008) => Err(TestError::String("LDY,X failure".into())),
-        (_, 0x009) => Err(TestError::String("LDY,X failure".into())),
-        (_, 0x00A) => Err(TestError::String("STY,X failure".into())),
-        (_, 0x00B) => Err(TestError::String("ORA failure".into())),
-        (_, 0x00C) => Err(TestError::String("ORA failure".into())),
-        (_, 0x00D) => Err(TestError::String("AND failure".into())),
-        (_, 0x00E) => Err(TestError::String("AND failure".into())),
-        (_, 0x00F) => Err(TestError::String("EOR failure".into())),
-        (_, 0x010) => Err(TestError::String("EOR failure".into())),
-        (_, 0x011) => Err(TestError::String("ADC failure".into())),
-        (_, 0x012) => Err(TestError::String("ADC failure".into())),
-        (_, 0x013) => Err(TestError::String("ADC failure".into())),
-        (_, 0x014) => Err(TestError::String("ADC failure".into())),
-        (_, 0x015) => Err(TestError::String("ADC failure".into())),
-        (_, 0x016) => Err(TestError::String("CMP failure".into())),
-        (_, 0x017) => Err(TestError::String("CMP failure".into())),
-        (_, 0x018) => Err(TestError::String("CMP failure".into())),
-        (_, 0x019) => Err(TestError::String("CMP failure".into())),
-        (_, 0x01A) => Err(TestError::String("CMP failure".into())),
-        (_, 0x01B) => Err(TestError::String("CMP failure".into())),
-        (_, 0x01C) => Err(TestError::String("CMP failure".into())),
-        (_, 0x01D) => Err(TestError::String("SBC failure".into())),
-        (_, 0x01E) => Err(TestError::String("SBC failure".into())),
-        (_, 0x01F) => Err(TestError::String("SBC failure".into())),
-        (_, 0x020) => Err(TestError::String("SBC failure".into())),
-        (_, 0x021) => Err(TestError::String("SBC failure".into())),
-        (_, 0x022) => Err(TestError::String("LDA failure".into())),
-        (_, 0x023) => Err(TestError::String("LDA failure".into())),
-        (_, 0x024) => Err(TestError::String("STA failure".into())),
-        (_, 0x025) => Err(TestError::String("LSR failure".into())),
-        (_, 0x026) => Err(TestError::String("LSR failure".into())),
-        (_, 0x027) => Err(TestError::String("ASL failure".into())),
-        (_, 0x028) => Err(TestError::String("ASL failure".into())),
-        (_, 0x029) => Err(TestError::String("ROR failure".into())),
-        (_, 0x02A) => Err(TestError::String("ROR failure".into())),
-        (_, 0x02B) => Err(TestError::String("ROL failure".into())),
-        (_, 0x02C) => Err(TestError::String("ROL failure".into())),
-        (_, 0x02D) => Err(TestError::String("INC failure".into())),
-        (_, 0x02E) => Err(TestError::String("INC failure".into())),
-        (_, 0x02F) => Err(TestError::String("DEC failure".into())),
-        (_, 0x030) => Err(TestError::String("DEC failure".into())),
-        (_, 0x031) => Err(TestError::String("DEC failure".into())),
-        (_, 0x032) => Err(TestError::String("LDX,Y failure".into())),
-        (_, 0x033) => Err(TestError::String("LDX,Y failure".into())),
-        (_, 0x034) => Err(TestError::String("STX,Y failure".into())),
-        (_, 0x035) => Err(TestError::String("STX,Y failure".into())),
-
-        // absolute,y tests
-        // ----------------
-        (_, 0x036) => Err(TestError::String("LDA failure".into())),
-        (_, 0x037) => Err(TestError::String(
-            "LDA failure to wrap properly from ffffh to 0000h".into(),
-        )),
-        (_, 0x038) => Err(TestError::String("LDA failure, page cross".into())),
-        (_, 0x039) => Err(TestError::String("ORA failure".into())),
-        (_, 0x03A) => Err(TestError::String("ORA failure".into())),
-        (_, 0x03B) => Err(TestError::String("AND failure".into())),
-        (_, 0x03C) => Err(TestError::String("AND failure".into())),
-        (_, 0x03D) => Err(TestError::String("EOR failure".into())),
-        (_, 0x03E) => Err(TestError::String("EOR failure".into())),
-        (_, 0x03F) => Err(TestError::String("ADC failure".into())),
-        (_, 0x040) => Err(TestError::String("ADC failure".into())),
-        (_, 0x041) => Err(TestError::String("ADC failure".into())),
-        (_, 0x042) => Err(TestError::String("ADC failure".into())),
-        (_, 0x043) => Err(TestError::String("ADC failure".into())),
-        (_, 0x044) => Err(TestError::String("CMP failure".into())),
-        (_, 0x045) => Err(TestError::String("CMP failure".into())),
-        (_, 0x046) => Err(TestError::String("CMP failure".into())),
-        (_, 0x047) => Err(TestError::String("CMP failure".into())),
-        (_, 0x048) => Err(TestError::String("CMP failure".into())),
-        (_, 0x049) => Err(TestError::String("CMP failure".into())),
-        (_, 0x04A) => Err(TestError::String("CMP failure".into())),
-        (_, 0x04B) => Err(TestError::String("SBC failure".into())),
-        (_, 0x04C) => Err(TestError::String("SBC failure".into())),
-        (_, 0x04D) => Err(TestError::String("SBC failure".into())),
-        (_, 0x04E) => Err(TestError::String("SBC failure".into())),
-        (_, 0x04F) => Err(TestError::String("SBC failure".into())),
-        (_, 0x050) => Err(TestError::String("STA failure".into())),
-
-        // absolute,x tests
-        // ----------------
-        (_, 0x051) => Err(TestError::String("LDY,X failure".into())),
-        (_, 0x052) => Err(TestError::String(
-            "LDY,X failure (didn't page cross)".into(),
-        )),
-        (_, 0x053) => Err(TestError::String("ORA failure".into())),
-        (_, 0x054) => Err(TestError::String("ORA failure".into())),
-        (_, 0x055) => Err(TestError::String("AND failure".into())),
-        (_, 0x056) => Err(TestError::String("AND failure".into())),
-        (_, 0x057) => Err(TestError::String("EOR failure".into())),
-        (_, 0x058) => Err(TestError::String("EOR failure".into())),
-        (_, 0x059) => Err(TestError::String("ADC failure".into())),
-        (_, 0x05A) => Err(TestError::String("ADC failure".into())),
-        (_, 0x05B) => Err(TestError::String("ADC failure".into())),
-        (_, 0x05C) => Err(TestError::String("ADC failure".into())),
-        (_, 0x05D) => Err(TestError::String("ADC failure".into())),
-        (_, 0x05E) => Err(TestError::String("CMP failure".into())),
-        (_, 0x05F) => Err(TestError::String("CMP failure".into())),
-        (_, 0x060) => Err(TestError::String("CMP failure".into())),
-        (_, 0x061) => Err(TestError::String("CMP failure".into())),
-        (_, 0x062) => Err(TestError::String("CMP failure".into())),
-        (_, 0x063) => Err(TestError::String("CMP failure".into())),
-        (_, 0x064) => Err(TestError::String("CMP failure".into())),
-        (_, 0x065) => Err(TestError::String("SBC failure".into())),
-        (_, 0x066) => Err(TestError::String("SBC failure".into())),
-        (_, 0x067) => Err(TestError::String("SBC failure".into())),
-        (_, 0x068) => Err(TestError::String("SBC failure".into())),
-        (_, 0x069) => Err(TestError::String("SBC failure".into())),
-        (_, 0x06A) => Err(TestError::String("LDA failure".into())),
-        (_, 0x06B) => Err(TestError::String("LDA failure (didn't page cross)".into())),
-        (_, 0x06C) => Err(TestError::String("STA failure".into())),
-        (_, 0x06D) => Err(TestError::String("LSR failure".into())),
-        (_, 0x06E) => Err(TestError::String("LSR failure".into())),
-        (_, 0x06F) => Err(TestError::String("ASL failure".into())),
-        (_, 0x070) => Err(TestError::String("ASL failure".into())),
-        (_, 0x071) => Err(TestError::String("ROR failure".into())),
-        (_, 0x072) => Err(TestError::String("ROR failure".into())),
-        (_, 0x073) => Err(TestError::String("ROL failure".into())),
-        (_, 0x074) => Err(TestError::String("ROL failure".into())),
-        (_, 0x075) => Err(TestError::String("INC failure".into())),
-        (_, 0x076) => Err(TestError::String("INC failure".into())),
-        (_, 0x077) => Err(TestError::String("DEC failure".into())),
-        (_, 0x078) => Err(TestError::String("DEC failure".into())),
-        (_, 0x079) => Err(TestError::String("DEC failure".into())),
-        (_, 0x07A) => Err(TestError::String("LDX,Y failure".into())),
-        (_, 0x07B) => Err(TestError::String("LDX,Y failure".into())),
-
-        // ------------------------------------
-        //
-        // Invalid opcode tests... all errors are reported in byte 03h unless
-        // specified.
-        //
-        // NOP - "invalid" opcode tests (error byte 02h)
-        // ---------------------------------------------
-        (0x04E, _) => Err(TestError::String(
-            "absolute,X NOPs less than 3 bytes long".into(),
-        )),
-        (0x04F, _) => Err(TestError::String("implied NOPs affects regs/flags".into())),
-        (0x050, _) => Err(TestError::String("ZP,X NOPs less than 2 bytes long".into())),
-        (0x051, _) => Err(TestError::String(
-            "absolute NOP less than 3 bytes long".into(),
-        )),
-        (0x052, _) => Err(TestError::String("ZP NOPs less than 2 bytes long".into())),
-        (0x053, _) => Err(TestError::String(
-            "absolute,X NOPs less than 3 bytes long".into(),
-        )),
-        (0x054, _) => Err(TestError::String("implied NOPs affects regs/flags".into())),
-        (0x055, _) => Err(TestError::String("ZP,X NOPs less than 2 bytes long".into())),
-        (0x056, _) => Err(TestError::String(
-            "absolute NOP less than 3 bytes long".into(),
-        )),
-        (0x057, _) => Err(TestError::String("ZP NOPs less than 2 bytes long".into())),
-
-        // LAX - "invalid" opcode tests
-        // ----------------------------
-        (_, 0x07C) => Err(TestError::String("LAX (indr,x) failure".into())),
-        (_, 0x07D) => Err(TestError::String("LAX (indr,x) failure".into())),
-        (_, 0x07E) => Err(TestError::String("LAX zeropage failure".into())),
-        (_, 0x07F) => Err(TestError::String("LAX zeropage failure".into())),
-        (_, 0x080) => Err(TestError::String("LAX absolute failure".into())),
-        (_, 0x081) => Err(TestError::String("LAX absolute failure".into())),
-        (_, 0x082) => Err(TestError::String("LAX (indr),y failure".into())),
-        (_, 0x083) => Err(TestError::String("LAX (indr),y failure".into())),
-        (_, 0x084) => Err(TestError::String("LAX zp,y failure".into())),
-        (_, 0x085) => Err(TestError::String("LAX zp,y failure".into())),
-        (_, 0x086) => Err(TestError::String("LAX abs,y failure".into())),
-        (_, 0x087) => Err(TestError::String("LAX abs,y failure".into())),
-
-        // SAX - "invalid" opcode tests
-        // ----------------------------
-        (_, 0x088) => Err(TestError::String("SAX (indr,x) failure".into())),
-        (_, 0x089) => Err(TestError::String("SAX (indr,x) failure".into())),
-        (_, 0x08A) => Err(TestError::String("SAX zeropage failure".into())),
-        (_, 0x08B) => Err(TestError::String("SAX zeropage failure".into())),
-        (_, 0x08C) => Err(TestError::String("SAX absolute failure".into())),
-        (_, 0x08D) => Err(TestError::String("SAX absolute failure".into())),
-        (_, 0x08E) => Err(TestError::String("SAX zp,y failure".into())),
-        (_, 0x08F) => Err(TestError::String("SAX zp,y failure".into())),
-
-        // SBC - "invalid" opcode test
-        // ---------------------------
-        (_, 0x090) => Err(TestError::String("SBC failure".into())),
-        (_, 0x091) => Err(TestError::String("SBC failure".into())),
-        (_, 0x092) => Err(TestError::String("SBC failure".into())),
-        (_, 0x093) => Err(TestError::String("SBC failure".into())),
-        (_, 0x094) => Err(TestError::String("SBC failure".into())),
-
-        // DCP - "invalid" opcode tests
-        // ----------------------------
-        (_, 0x095) => Err(TestError::String("DCP (indr,x) failure".into())),
-        (_, 0x096) => Err(TestError::String("DCP (indr,x) failure".into())),
-        (_, 0x097) => Err(TestError::String("DCP (indr,x) failure".into())),
-        (_, 0x098) => Err(TestError::String("DCP zeropage failure".into())),
-        (_, 0x099) => Err(TestError::String("DCP zeropage failure".into())),
-        (_, 0x09A) => Err(TestError::String("DCP zeropage failure".into())),
-        (_, 0x09B) => Err(TestError::String("DCP absolute failure".into())),
-        (_, 0x09C) => Err(TestError::String("DCP absolute failure".into())),
-        (_, 0x09D) => Err(TestError::String("DCP absolute failure".into())),
-        (_, 0x09E) => Err(TestError::String("DCP (indr),y failure".into())),
-        (_, 0x09F) => Err(TestError::String("DCP (indr),y failure".into())),
-        (_, 0x0A0) => Err(TestError::String("DCP (indr),y failure".into())),
-        (_, 0x0A1) => Err(TestError::String("DCP zp,x failure".into())),
-        (_, 0x0A2) => Err(TestError::String("DCP zp,x failure".into())),
-        (_, 0x0A3) => Err(TestError::String("DCP zp,x failure".into())),
-        (_, 0x0A4) => Err(TestError::String("DCP abs,y failure".into())),
-        (_, 0x0A5) => Err(TestError::String("DCP abs,y failure".into())),
-        (_, 0x0A6) => Err(TestError::String("DCP abs,y failure".into())),
-        (_, 0x0A7) => Err(TestError::String("DCP abs,x failure".into())),
-        (_, 0x0A8) => Err(TestError::String("DCP abs,x failure".into())),
-        (_, 0x0A9) => Err(TestError::String("DCP abs,x failure".into())),
-
-        // ISB - "invalid" opcode tests
-        // ----------------------------
-        (_, 0x0AA) => Err(TestError::String("DCP (indr,x) failure".into())),
-        (_, 0x0AB) => Err(TestError::String("DCP (indr,x) failure".into())),
-        (_, 0x0AC) => Err(TestError::String("DCP (indr,x) failure".into())),
-        (_, 0x0AD) => Err(TestError::String("DCP zeropage failure".into())),
-        (_, 0x0AE) => Err(TestError::String("DCP zeropage failure".into())),
-        (_, 0x0AF) => Err(TestError::String("DCP zeropage failure".into())),
-        (_, 0x0B0) => Err(TestError::String("DCP absolute failure".into())),
-        (_, 0x0B1) => Err(TestError::String("DCP absolute failure".into())),
-        (_, 0x0B2) => Err(TestError::String("DCP absolute failure".into())),
-        (_, 0x0B3) => Err(TestError::String("DCP (indr),y failure".into())),
-        (_, 0x0B4) => Err(TestError::String("DCP (indr),y failure".into())),
-        (_, 0x0B5) => Err(TestError::String("DCP (indr),y failure".into())),
-        (_, 0x0B6) => Err(TestError::String("DCP zp,x failure".into())),
-        (_, 0x0B7) => Err(TestError::String("DCP zp,x failure".into())),
-        (_, 0x0B8) => Err(TestError::String("DCP zp,x failure".into())),
-        (_, 0x0B9) => Err(TestError::String("DCP abs,y failure".into())),
-        (_, 0x0BA) => Err(TestError::String("DCP abs,y failure".into())),
-        (_, 0x0BB) => Err(TestError::String("DCP abs,y failure".into())),
-        (_, 0x0BC) => Err(TestError::String("DCP abs,x failure".into())),
-        (_, 0x0BD) => Err(TestError::String("DCP abs,x failure".into())),
-        (_, 0x0BE) => Err(TestError::String("DCP abs,x failure".into())),
-
-        // SLO - "invalid" opcode tests
-        // ----------------------------
-        (_, 0x0BF) => Err(TestError::String("SLO (indr,x) failure".into())),
-        (_, 0x0C0) => Err(TestError::String("SLO (indr,x) failure".into())),
-        (_, 0x0C1) => Err(TestError::String("SLO (indr,x) failure".into())),
-        (_, 0x0C2) => Err(TestError::String("SLO zeropage failure".into())),
-        (_, 0x0C3) => Err(TestError::String("SLO zeropage failure".into())),
-        (_, 0x0C4) => Err(TestError::String("SLO zeropage failure".into())),
-        (_, 0x0C5) => Err(TestError::String("SLO absolute failure".into())),
-        (_, 0x0C6) => Err(TestError::String("SLO absolute failure".into())),
-        (_, 0x0C7) => Err(TestError::String("SLO absolute failure".into())),
-        (_, 0x0C8) => Err(TestError::String("SLO (indr),y failure".into())),
-        (_, 0x0C9) => Err(TestError::String("SLO (indr),y failure".into())),
-        (_, 0x0CA) => Err(TestError::String("SLO (indr),y failure".into())),
-        (_, 0x0CB) => Err(TestError::String("SLO zp,x failure".into())),
-        (_, 0x0CC) => Err(TestError::String("SLO zp,x failure".into())),
-        (_, 0x0CD) => Err(TestError::String("SLO zp,x failure".into())),
-        (_, 0x0CE) => Err(TestError::String("SLO abs,y failure".into())),
-        (_, 0x0CF) => Err(TestError::String("SLO abs,y failure".into())),
-        (_, 0x0D0) => Err(TestError::String("SLO abs,y failure".into())),
-        (_, 0x0D1) => Err(TestError::String("SLO abs,x failure".into())),
-        (_, 0x0D2) => Err(TestError::String("SLO abs,x failure".into())),
-        (_, 0x0D3) => Err(TestError::String("SLO abs,x failure".into())),
-
-        // RLA - "invalid" opcode tests
-        // ----------------------------
-        (_, 0x0D4) => Err(TestError::String("RLA (indr,x) failure".into())),
-        (_, 0x0D5) => Err(TestError::String("RLA (indr,x) failure".into())),
-        (_, 0x0D6) => Err(TestError::String("RLA (indr,x) failure".into())),
-        (_, 0x0D7) => Err(TestError::String("RLA zeropage failure".into())),
-        (_, 0x0D8) => Err(TestError::String("RLA zeropage failure".into())),
-        (_, 0x0D9) => Err(TestError::String("RLA zeropage failure".into())),
-        (_, 0x0DA) => Err(TestError::String("RLA absolute failure".into())),
-        (_, 0x0DB) => Err(TestError::String("RLA absolute failure".into())),
-        (_, 0x0DC) => Err(TestError::String("RLA absolute failure".into())),
-        (_, 0x0DD) => Err(TestError::String("RLA (indr),y failure".into())),
-        (_, 0x0DE) => Err(TestError::String("RLA (indr),y failure".into())),
-        (_, 0x0DF) => Err(TestError::String("RLA (indr),y failure".into())),
-        (_, 0x0E0) => Err(TestError::String("RLA zp,x failure".into())),
-        (_, 0x0E1) => Err(TestError::String("RLA zp,x failure".into())),
-        (_, 0x0E2) => Err(TestError::String("RLA zp,x failure".into())),
-        (_, 0x0E3) => Err(TestError::String("RLA abs,y failure".into())),
-        (_, 0x0E4) => Err(TestError::String("RLA abs,y failure".into())),
-        (_, 0x0E5) => Err(TestError::String("RLA abs,y failure".into())),
-        (_, 0x0E6) => Err(TestError::String("RLA abs,x failure".into())),
-        (_, 0x0E7) => Err(TestError::String("RLA abs,x failure".into())),
-        (_, 0x0E8) => Err(TestError::String("RLA abs,x failure".into())),
-
-        // SRE - "invalid" opcode tests
-        // ----------------------------
-        (_, 0x0E9) => Err(TestError::String("SRE (indr,x) failure".into())),
-        (_, 0x0EA) => Err(TestError::String("SRE (indr,x) failure".into())),
-        (_, 0x0EB) => Err(TestError::String("SRE (indr,x) failure".into())),
-        (_, 0x0EC) => Err(TestError::String("SRE zeropage failure".into())),
-        (_, 0x0ED) => Err(TestError::String("SRE zeropage failure".into())),
-        (_, 0x0EE) => Err(TestError::String("SRE zeropage failure".into())),
-        (_, 0x0EF) => Err(TestError::String("SRE absolute failure".into())),
-        (_, 0x0F0) => Err(TestError::String("SRE absolute failure".into())),
-        (_, 0x0F1) => Err(TestError::String("SRE absolute failure".into())),
-        (_, 0x0F2) => Err(TestError::String("SRE (indr),y failure".into())),
-        (_, 0x0F3) => Err(TestError::String("SRE (indr),y failure".into())),
-        (_, 0x0F4) => Err(TestError::String("SRE (indr),y failure".into())),
-        (_, 0x0F5) => Err(TestError::String("SRE zp,x failure".into())),
-        (_, 0x0F6) => Err(TestError::String("SRE zp,x failure".into())),
-        (_, 0x0F7) => Err(TestError::String("SRE zp,x failure".into())),
-        (_, 0x0F8) => Err(TestError::String("SRE abs,y failure".into())),
-        (_, 0x0F9) => Err(TestError::String("SRE abs,y failure".into())),
-        (_, 0x0FA) => Err(TestError::String("SRE abs,y failure".into())),
-        (_, 0x0FB) => Err(TestError::String("SRE abs,x failure".into())),
-        (_, 0x0FC) => Err(TestError::String("SRE abs,x failure".into())),
-        (_, 0x0FD) => Err(TestError::String("SRE abs,x failure".into())),
-
-        // RRA - "invalid" opcode tests
-        // ----------------------------
-        // duplicates?
-        // (_, 0x001) => Err(TestError::String("RRA (indr,x) failure".into())),
-        // (_, 0x002) => Err(TestError::String("RRA (indr,x) failure".into())),
-        // (_, 0x003) => Err(TestError::String("RRA (indr,x) failure".into())),
-        // (_, 0x004) => Err(TestError::String("RRA zeropage failure".into())),
-        // (_, 0x005) => Err(TestError::String("RRA zeropage failure".into())),
-        // (_, 0x006) => Err(TestError::String("RRA zeropage failure".into())),
-        // (_, 0x007) => Err(TestError::String("RRA absolute failure".into())),
-        // (_, 0x008) => Err(TestError::String("RRA absolute failure".into())),
-        // (_, 0x009) => Err(TestError::String("RRA absolute failure".into())),
-        // (_, 0x00A) => Err(TestError::String("RRA (indr),y failure".into())),
-        // (_, 0x00B) => Err(TestError::String("RRA (indr),y failure".into())),
-        // (_, 0x00C) => Err(TestError::String("RRA (indr),y failure".into())),
-        // (_, 0x00D) => Err(TestError::String("RRA zp,x failure".into())),
-        // (_, 0x00E) => Err(TestError::String("RRA zp,x failure".into())),
-        // (_, 0x00F) => Err(TestError::String("RRA zp,x failure".into())),
-        // (_, 0x010) => Err(TestError::String("RRA abs,y failure".into())),
-        // (_, 0x011) => Err(TestError::String("RRA abs,y failure".into())),
-        // (_, 0x012) => Err(TestError::String("RRA abs,y failure".into())),
-        // (_, 0x013) => Err(TestError::String("RRA abs,x failure".into())),
-        // (_, 0x014) => Err(TestError::String("RRA abs,x failure".into())),
-        // (_, 0x015) => Err(TestError::String("RRA abs,x failure".into())),
-        (_, _) => Err(TestError::String("unknown failure".into())),
+use crate::disassembler::column;
+use crate::AddressingMode::{self, *};
+use crate::{RomFailure, TestableCpu, ROM_NESTEST};
+use std::fmt::{Display, Formatter};
+
+/// Where the automated run of nestest leaves the error code of the official instruction tests.
+/// nestest.txt documents these codes for $02, but the rom copies them to $03 when it finishes.
+pub(crate) const OFFICIAL_ADDRESS: u16 = 0x0003;
+/// Where the automated run of nestest leaves the error code of the unofficial instruction tests,
+/// which nestest.txt documents for $03. The rom never copies it to $02 or $03.
+pub(crate) const UNOFFICIAL_ADDRESS: u16 = 0x0011;
+/// Where the automated run of nestest leaves the error code of the RRA tests, which run last
+pub(crate) const RRA_ADDRESS: u16 = 0x0002;
+
+/// Builds a nestest rom that runs all tests automatically when the cpu is reset.
+///
+/// The reset vector of nestest points at $C004, where it starts a menu to pick tests with the controller.
+/// The automated run starts at $C000, and used to be reached by setting the program counter there.
+/// The rom has a single 16 KiB bank, so its reset vector is at the end of that bank.
+pub(crate) fn automated_nestest_rom() -> Vec<u8> {
+    let mut rom = ROM_NESTEST.to_vec();
+    let reset_vector = 16 + 0x3FFC;
+    rom[reset_vector..reset_vector + 2].copy_from_slice(&0xC000u16.to_le_bytes());
+    rom
+}
+
+/// What an error code nestest left in memory means, according to
+/// [nestest.txt](https://github.com/christopherpow/nes-test-roms/blob/master/other/nestest.txt)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NestestFailure {
+    /// The address nestest left the code at: $03 for the official instructions,
+    /// $11 for the unofficial instructions and $02 for RRA
+    pub address: u16,
+    /// The error code
+    pub code: u8,
+    /// The instruction that failed. Some codes are about more than one instruction, like `INX/DEX`.
+    /// This is `None` when nestest.txt doesn't document the code, just like `mode` and `section`.
+    pub mnemonic: Option<&'static str>,
+    /// The addressing mode of the instruction that failed
+    pub mode: Option<AddressingMode>,
+    /// The section of nestest.txt the code is in, like "zeropage tests"
+    pub section: Option<&'static str>,
+    /// What nestest.txt says about the code
+    pub description: &'static str,
+}
+
+impl NestestFailure {
+    /// Looks up the meaning of `code`, which nestest left at `address`.
+    ///
+    /// When nestest runs from $C000 it runs three groups of tests, each of which counts its own error codes:
+    /// the official instructions end up at $03, the unofficial instructions at $11 and RRA at $02.
+    /// nestest.txt documents the RRA codes together with the other unofficial instructions,
+    /// even though they reuse the codes of the first few tests in that list.
+    ///
+    /// ```
+    /// use tudelft_nes_test::{AddressingMode, NestestFailure};
+    ///
+    /// let isb = NestestFailure::decode(0x0011, 0xAA);
+    /// assert_eq!(isb.mnemonic, Some("ISB"));
+    /// assert_eq!(isb.mode, Some(AddressingMode::IndirectX));
+    ///
+    /// let rra = NestestFailure::decode(0x0002, 0x0A);
+    /// assert_eq!(rra.mnemonic, Some("RRA"));
+    /// assert_eq!(rra.mode, Some(AddressingMode::IndirectY));
+    /// ```
+    pub fn decode(address: u16, code: u8) -> Self {
+        let sections = match address {
+            OFFICIAL_ADDRESS => CODES_02,
+            UNOFFICIAL_ADDRESS => CODES_03,
+            RRA_ADDRESS => CODES_RRA,
+            _ => &[],
+        };
+        let found = sections.iter().find_map(|section| {
+            let entry = section.codes.iter().find(|entry| entry.0 == code)?;
+            Some((section.name, entry))
+        });
+
+        match found {
+            Some((section, &Code(_, mnemonic, mode, description))) => Self {
+                address,
+                code,
+                mnemonic: Some(mnemonic),
+                mode: Some(mode),
+                section: Some(section),
+                description,
+            },
+            None => Self {
+                address,
+                code,
+                mnemonic: None,
+                mode: None,
+                section: None,
+                description: "unknown failure",
+            },
+        }
+    }
+}
+
+impl Display for NestestFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.description)
+    }
+}
+
+/// Reads the error codes nestest left at $03, $11 and $02
+pub(crate) fn nestest_status(cpu: &impl TestableCpu) -> Result<(), RomFailure> {
+    let official = cpu.memory_read(OFFICIAL_ADDRESS);
+    let unofficial = cpu.memory_read(UNOFFICIAL_ADDRESS);
+    let rra = cpu.memory_read(RRA_ADDRESS);
+
+    let failures = nestest_failures(official, unofficial, rra);
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    Err(RomFailure::Nestest {
+        official,
+        unofficial,
+        rra,
+        description: failures
+            .iter()
+            .map(NestestFailure::to_string)
+            .collect::<Vec<_>>()
+            .join("; "),
+        failures,
+    })
+}
+
+/// Decodes the error codes at $03, $11 and $02, leaving out the ones that are 0
+pub(crate) fn nestest_failures(official: u8, unofficial: u8, rra: u8) -> Vec<NestestFailure> {
+    [
+        (OFFICIAL_ADDRESS, official),
+        (UNOFFICIAL_ADDRESS, unofficial),
+        (RRA_ADDRESS, rra),
+    ]
+    .into_iter()
+    .filter(|&(_, code)| code != 0)
+    .map(|(address, code)| NestestFailure::decode(address, code))
+    .collect()
+}
+
+/// A section of nestest.txt, with the error codes it documents
+struct Section {
+    name: &'static str,
+    codes: &'static [Code],
+}
+
+/// An error code with the instruction it is about, its addressing mode and its description in nestest.txt
+struct Code(u8, &'static str, AddressingMode, &'static str);
+
+/// The error codes nestest.txt documents for $02, by section. The NOP tests are listed here,
+/// but nestest runs them with the unofficial instructions, so they can only show up at $11,
+/// where they share their codes with the absolute,y and absolute,x tests.
+#[rustfmt::skip]
+const CODES_02: &[Section] = &[
+    Section {
+        name: "branch tests",
+        codes: &[
+            Code(0x01, "BCS", Relative, "BCS failed to branch"),
+            Code(0x02, "BCS", Relative, "BCS branched when it shouldn't have"),
+            Code(0x03, "BCC", Relative, "BCC branched when it shouldn't have"),
+            Code(0x04, "BCC", Relative, "BCC failed to branch"),
+            Code(0x05, "BEQ", Relative, "BEQ failed to branch"),
+            Code(0x06, "BEQ", Relative, "BEQ branched when it shouldn't have"),
+            Code(0x07, "BNE", Relative, "BNE failed to branch"),
+            Code(0x08, "BNE", Relative, "BNE branched when it shouldn't have"),
+            Code(0x09, "BVS", Relative, "BVS failed to branch"),
+            Code(0x0A, "BVC", Relative, "BVC branched when it shouldn't have"),
+            Code(0x0B, "BVC", Relative, "BVC failed to branch"),
+            Code(0x0C, "BVS", Relative, "BVS branched when it shouldn't have"),
+            Code(0x0D, "BPL", Relative, "BPL failed to branch"),
+            Code(0x0E, "BPL", Relative, "BPL branched when it shouldn't have"),
+            Code(0x0F, "BMI", Relative, "BMI failed to branch"),
+            Code(0x10, "BMI", Relative, "BMI branched when it shouldn't have"),
+        ],
+    },
+    Section {
+        name: "flag tests",
+        codes: &[
+            Code(0x11, "PHP", Implied, "PHP/flags failure (bits set)"),
+            Code(0x12, "PHP", Implied, "PHP/flags failure (bits clear)"),
+            Code(0x13, "PHP", Implied, "PHP/flags failure (misc bit states)"),
+            Code(0x14, "PLP", Implied, "PLP/flags failure (misc bit states)"),
+            Code(0x15, "PLP", Implied, "PLP/flags failure (misc bit states)"),
+            Code(0x16, "PLA", Implied, "PHA/PLA failure (PLA didn't affect Z and N properly)"),
+            Code(0x17, "PLA", Implied, "PHA/PLA failure (PLA didn't affect Z and N properly)"),
+        ],
+    },
+    Section {
+        name: "immediate instruction tests",
+        codes: &[
+            Code(0x18, "ORA", Immediate, "ORA # failure"),
+            Code(0x19, "ORA", Immediate, "ORA # failure"),
+            Code(0x1A, "AND", Immediate, "AND # failure"),
+            Code(0x1B, "AND", Immediate, "AND # failure"),
+            Code(0x1C, "EOR", Immediate, "EOR # failure"),
+            Code(0x1D, "EOR", Immediate, "EOR # failure"),
+            Code(0x1E, "ADC", Immediate, "ADC # failure (overflow/carry problems)"),
+            Code(0x1F, "ADC", Immediate, "ADC # failure (decimal mode was turned on)"),
+            Code(0x20, "ADC", Immediate, "ADC # failure"),
+            Code(0x21, "ADC", Immediate, "ADC # failure"),
+            Code(0x22, "ADC", Immediate, "ADC # failure"),
+            Code(0x23, "LDA", Immediate, "LDA # failure (didn't set N and Z correctly)"),
+            Code(0x24, "LDA", Immediate, "LDA # failure (didn't set N and Z correctly)"),
+            Code(0x25, "CMP", Immediate, "CMP # failure (messed up flags)"),
+            Code(0x26, "CMP", Immediate, "CMP # failure (messed up flags)"),
+            Code(0x27, "CMP", Immediate, "CMP # failure (messed up flags)"),
+            Code(0x28, "CMP", Immediate, "CMP # failure (messed up flags)"),
+            Code(0x29, "CMP", Immediate, "CMP # failure (messed up flags)"),
+            Code(0x2A, "CMP", Immediate, "CMP # failure (messed up flags)"),
+            Code(0x2B, "CPY", Immediate, "CPY # failure (messed up flags)"),
+            Code(0x2C, "CPY", Immediate, "CPY # failure (messed up flags)"),
+            Code(0x2D, "CPY", Immediate, "CPY # failure (messed up flags)"),
+            Code(0x2E, "CPY", Immediate, "CPY # failure (messed up flags)"),
+            Code(0x2F, "CPY", Immediate, "CPY # failure (messed up flags)"),
+            Code(0x30, "CPY", Immediate, "CPY # failure (messed up flags)"),
+            Code(0x31, "CPY", Immediate, "CPY # failure (messed up flags)"),
+            Code(0x32, "CPX", Immediate, "CPX # failure (messed up flags)"),
+            Code(0x33, "CPX", Immediate, "CPX # failure (messed up flags)"),
+            Code(0x34, "CPX", Immediate, "CPX # failure (messed up flags)"),
+            Code(0x35, "CPX", Immediate, "CPX # failure (messed up flags)"),
+            Code(0x36, "CPX", Immediate, "CPX # failure (messed up flags)"),
+            Code(0x37, "CPX", Immediate, "CPX # failure (messed up flags)"),
+            Code(0x38, "CPX", Immediate, "CPX # failure (messed up flags)"),
+            Code(0x39, "LDX", Immediate, "LDX # failure (didn't set N and Z correctly)"),
+            Code(0x3A, "LDX", Immediate, "LDX # failure (didn't set N and Z correctly)"),
+            Code(0x3B, "LDY", Immediate, "LDY # failure (didn't set N and Z correctly)"),
+            Code(0x3C, "LDY", Immediate, "LDY # failure (didn't set N and Z correctly)"),
+            Code(0x3D, "CMP", Immediate, "compare(s) stored the result in a register (whoops!)"),
+            Code(0x71, "SBC", Immediate, "SBC # failure"),
+            Code(0x72, "SBC", Immediate, "SBC # failure"),
+            Code(0x73, "SBC", Immediate, "SBC # failure"),
+            Code(0x74, "SBC", Immediate, "SBC # failure"),
+            Code(0x75, "SBC", Immediate, "SBC # failure"),
+        ],
+    },
+    Section {
+        name: "implied instruction tests",
+        codes: &[
+            Code(0x3E, "INX/DEX/INY/DEY", Implied, "INX/DEX/INY/DEY did something bad"),
+            Code(0x3F, "INY/DEY", Implied, "INY/DEY messed up overflow or carry"),
+            Code(0x40, "INX/DEX", Implied, "INX/DEX messed up overflow or carry"),
+            Code(0x41, "TAY", Implied, "TAY did something bad (changed wrong regs, messed up flags)"),
+            Code(0x42, "TAX", Implied, "TAX did something bad (changed wrong regs, messed up flags)"),
+            Code(0x43, "TYA", Implied, "TYA did something bad (changed wrong regs, messed up flags)"),
+            Code(0x44, "TXA", Implied, "TXA did something bad (changed wrong regs, messed up flags)"),
+            Code(0x45, "TXS", Implied, "TXS didn't set flags right, or TSX touched flags and it shouldn't have"),
+        ],
+    },
+    Section {
+        name: "stack tests",
+        codes: &[
+            Code(0x46, "PLA", Implied, "wrong data popped, or data not in right location on stack"),
+            Code(0x47, "JSR", Absolute, "JSR didn't work as expected"),
+            Code(0x48, "RTS/JSR", Implied, "RTS/JSR shouldn't have affected flags"),
+            Code(0x49, "RTI/RTS", Implied, "RTI/RTS didn't work right when return addys/data were manually pushed"),
+        ],
+    },
+    Section {
+        name: "accumulator tests",
+        codes: &[
+            Code(0x4A, "LSR", Accumulator, "LSR A  failed"),
+            Code(0x4B, "ASL", Accumulator, "ASL A  failed"),
+            Code(0x4C, "ROR", Accumulator, "ROR A  failed"),
+            Code(0x4D, "ROL", Accumulator, "ROL A  failed"),
+        ],
+    },
+    Section {
+        name: "(indirect,x) tests",
+        codes: &[
+            Code(0x58, "LDA", IndirectX, "LDA didn't load the data it expected to load"),
+            Code(0x59, "STA", IndirectX, "STA didn't store the data where it was supposed to"),
+            Code(0x5A, "ORA", IndirectX, "ORA failure"),
+            Code(0x5B, "ORA", IndirectX, "ORA failure"),
+            Code(0x5C, "AND", IndirectX, "AND failure"),
+            Code(0x5D, "AND", IndirectX, "AND failure"),
+            Code(0x5E, "EOR", IndirectX, "EOR failure"),
+            Code(0x5F, "EOR", IndirectX, "EOR failure"),
+            Code(0x60, "ADC", IndirectX, "ADC failure"),
+            Code(0x61, "ADC", IndirectX, "ADC failure"),
+            Code(0x62, "ADC", IndirectX, "ADC failure"),
+            Code(0x63, "ADC", IndirectX, "ADC failure"),
+            Code(0x64, "ADC", IndirectX, "ADC failure"),
+            Code(0x65, "CMP", IndirectX, "CMP failure"),
+            Code(0x66, "CMP", IndirectX, "CMP failure"),
+            Code(0x67, "CMP", IndirectX, "CMP failure"),
+            Code(0x68, "CMP", IndirectX, "CMP failure"),
+            Code(0x69, "CMP", IndirectX, "CMP failure"),
+            Code(0x6A, "CMP", IndirectX, "CMP failure"),
+            Code(0x6B, "CMP", IndirectX, "CMP failure"),
+            Code(0x6C, "SBC", IndirectX, "SBC failure"),
+            Code(0x6D, "SBC", IndirectX, "SBC failure"),
+            Code(0x6E, "SBC", IndirectX, "SBC failure"),
+            Code(0x6F, "SBC", IndirectX, "SBC failure"),
+            Code(0x70, "SBC", IndirectX, "SBC failure"),
+        ],
+    },
+    Section {
+        name: "zeropage tests",
+        codes: &[
+            Code(0x76, "LDA", ZeroPage, "LDA didn't set the flags properly"),
+            Code(0x77, "STA", ZeroPage, "STA affected flags it shouldn't"),
+            Code(0x78, "LDY", ZeroPage, "LDY didn't set the flags properly"),
+            Code(0x79, "STY", ZeroPage, "STY affected flags it shouldn't"),
+            Code(0x7A, "LDX", ZeroPage, "LDX didn't set the flags properly"),
+            Code(0x7B, "STX", ZeroPage, "STX affected flags it shouldn't"),
+            Code(0x7C, "BIT", ZeroPage, "BIT failure"),
+            Code(0x7D, "BIT", ZeroPage, "BIT failure"),
+            Code(0x7E, "ORA", ZeroPage, "ORA failure"),
+            Code(0x7F, "ORA", ZeroPage, "ORA failure"),
+            Code(0x80, "AND", ZeroPage, "AND failure"),
+            Code(0x81, "AND", ZeroPage, "AND failure"),
+            Code(0x82, "EOR", ZeroPage, "EOR failure"),
+            Code(0x83, "EOR", ZeroPage, "EOR failure"),
+            Code(0x84, "ADC", ZeroPage, "ADC failure"),
+            Code(0x85, "ADC", ZeroPage, "ADC failure"),
+            Code(0x86, "ADC", ZeroPage, "ADC failure"),
+            Code(0x87, "ADC", ZeroPage, "ADC failure"),
+            Code(0x88, "ADC", ZeroPage, "ADC failure"),
+            Code(0x89, "CMP", ZeroPage, "CMP failure"),
+            Code(0x8A, "CMP", ZeroPage, "CMP failure"),
+            Code(0x8B, "CMP", ZeroPage, "CMP failure"),
+            Code(0x8C, "CMP", ZeroPage, "CMP failure"),
+            Code(0x8D, "CMP", ZeroPage, "CMP failure"),
+            Code(0x8E, "CMP", ZeroPage, "CMP failure"),
+            Code(0x8F, "CMP", ZeroPage, "CMP failure"),
+            Code(0x90, "SBC", ZeroPage, "SBC failure"),
+            Code(0x91, "SBC", ZeroPage, "SBC failure"),
+            Code(0x92, "SBC", ZeroPage, "SBC failure"),
+            Code(0x93, "SBC", ZeroPage, "SBC failure"),
+            Code(0x94, "SBC", ZeroPage, "SBC failure"),
+            Code(0x95, "CPX", ZeroPage, "CPX failure"),
+            Code(0x96, "CPX", ZeroPage, "CPX failure"),
+            Code(0x97, "CPX", ZeroPage, "CPX failure"),
+            Code(0x98, "CPX", ZeroPage, "CPX failure"),
+            Code(0x99, "CPX", ZeroPage, "CPX failure"),
+            Code(0x9A, "CPX", ZeroPage, "CPX failure"),
+            Code(0x9B, "CPX", ZeroPage, "CPX failure"),
+            Code(0x9C, "CPY", ZeroPage, "CPY failure"),
+            Code(0x9D, "CPY", ZeroPage, "CPY failure"),
+            Code(0x9E, "CPY", ZeroPage, "CPY failure"),
+            Code(0x9F, "CPY", ZeroPage, "CPY failure"),
+            Code(0xA0, "CPY", ZeroPage, "CPY failure"),
+            Code(0xA1, "CPY", ZeroPage, "CPY failure"),
+            Code(0xA2, "CPY", ZeroPage, "CPY failure"),
+            Code(0xA3, "LSR", ZeroPage, "LSR failure"),
+            Code(0xA4, "LSR", ZeroPage, "LSR failure"),
+            Code(0xA5, "ASL", ZeroPage, "ASL failure"),
+            Code(0xA6, "ASL", ZeroPage, "ASL failure"),
+            Code(0xA7, "ROL", ZeroPage, "ROL failure"),
+            Code(0xA8, "ROL", ZeroPage, "ROL failure"),
+            Code(0xA9, "ROR", ZeroPage, "ROR failure"),
+            Code(0xAA, "ROR", ZeroPage, "ROR failure"),
+            Code(0xAB, "INC", ZeroPage, "INC failure"),
+            Code(0xAC, "INC", ZeroPage, "INC failure"),
+            Code(0xAD, "DEC", ZeroPage, "DEC failure"),
+            Code(0xAE, "DEC", ZeroPage, "DEC failure"),
+            Code(0xAF, "DEC", ZeroPage, "DEC failure"),
+        ],
+    },
+    Section {
+        name: "absolute tests",
+        codes: &[
+            Code(0xB0, "LDA", Absolute, "LDA didn't set the flags properly"),
+            Code(0xB1, "STA", Absolute, "STA affected flags it shouldn't"),
+            Code(0xB2, "LDY", Absolute, "LDY didn't set the flags properly"),
+            Code(0xB3, "STY", Absolute, "STY affected flags it shouldn't"),
+            Code(0xB4, "LDX", Absolute, "LDX didn't set the flags properly"),
+            Code(0xB5, "STX", Absolute, "STX affected flags it shouldn't"),
+            Code(0xB6, "BIT", Absolute, "BIT failure"),
+            Code(0xB7, "BIT", Absolute, "BIT failure"),
+            Code(0xB8, "ORA", Absolute, "ORA failure"),
+            Code(0xB9, "ORA", Absolute, "ORA failure"),
+            Code(0xBA, "AND", Absolute, "AND failure"),
+            Code(0xBB, "AND", Absolute, "AND failure"),
+            Code(0xBC, "EOR", Absolute, "EOR failure"),
+            Code(0xBD, "EOR", Absolute, "EOR failure"),
+            Code(0xBE, "ADC", Absolute, "ADC failure"),
+            Code(0xBF, "ADC", Absolute, "ADC failure"),
+            Code(0xC0, "ADC", Absolute, "ADC failure"),
+            Code(0xC1, "ADC", Absolute, "ADC failure"),
+            Code(0xC2, "ADC", Absolute, "ADC failure"),
+            Code(0xC3, "CMP", Absolute, "CMP failure"),
+            Code(0xC4, "CMP", Absolute, "CMP failure"),
+            Code(0xC5, "CMP", Absolute, "CMP failure"),
+            Code(0xC6, "CMP", Absolute, "CMP failure"),
+            Code(0xC7, "CMP", Absolute, "CMP failure"),
+            Code(0xC8, "CMP", Absolute, "CMP failure"),
+            Code(0xC9, "CMP", Absolute, "CMP failure"),
+            Code(0xCA, "SBC", Absolute, "SBC failure"),
+            Code(0xCB, "SBC", Absolute, "SBC failure"),
+            Code(0xCC, "SBC", Absolute, "SBC failure"),
+            Code(0xCD, "SBC", Absolute, "SBC failure"),
+            Code(0xCE, "SBC", Absolute, "SBC failure"),
+            Code(0xCF, "CPX", Absolute, "CPX failure"),
+            Code(0xD0, "CPX", Absolute, "CPX failure"),
+            Code(0xD1, "CPX", Absolute, "CPX failure"),
+            Code(0xD2, "CPX", Absolute, "CPX failure"),
+            Code(0xD3, "CPX", Absolute, "CPX failure"),
+            Code(0xD4, "CPX", Absolute, "CPX failure"),
+            Code(0xD5, "CPX", Absolute, "CPX failure"),
+            Code(0xD6, "CPY", Absolute, "CPY failure"),
+            Code(0xD7, "CPY", Absolute, "CPY failure"),
+            Code(0xD8, "CPY", Absolute, "CPY failure"),
+            Code(0xD9, "CPY", Absolute, "CPY failure"),
+            Code(0xDA, "CPY", Absolute, "CPY failure"),
+            Code(0xDB, "CPY", Absolute, "CPY failure"),
+            Code(0xDC, "CPY", Absolute, "CPY failure"),
+            Code(0xDD, "LSR", Absolute, "LSR failure"),
+            Code(0xDE, "LSR", Absolute, "LSR failure"),
+            Code(0xDF, "ASL", Absolute, "ASL failure"),
+            Code(0xE0, "ASL", Absolute, "ASL failure"),
+            Code(0xE1, "ROR", Absolute, "ROR failure"),
+            Code(0xE2, "ROR", Absolute, "ROR failure"),
+            Code(0xE3, "ROL", Absolute, "ROL failure"),
+            Code(0xE4, "ROL", Absolute, "ROL failure"),
+            Code(0xE5, "INC", Absolute, "INC failure"),
+            Code(0xE6, "INC", Absolute, "INC failure"),
+            Code(0xE7, "DEC", Absolute, "DEC failure"),
+            Code(0xE8, "DEC", Absolute, "DEC failure"),
+            Code(0xE9, "DEC", Absolute, "DEC failure"),
+        ],
+    },
+    Section {
+        name: "(indirect),y tests",
+        codes: &[
+            Code(0xEA, "LDA", IndirectY, "LDA didn't load what it was supposed to"),
+            Code(0xEB, "LDA", IndirectY, "read location should've wrapped around ffffh to 0000h"),
+            Code(0xEC, "LDA", IndirectY, "should've wrapped zeropage address"),
+            Code(0xED, "ORA", IndirectY, "ORA failure"),
+            Code(0xEE, "ORA", IndirectY, "ORA failure"),
+            Code(0xEF, "AND", IndirectY, "AND failure"),
+            Code(0xF0, "AND", IndirectY, "AND failure"),
+            Code(0xF1, "EOR", IndirectY, "EOR failure"),
+            Code(0xF2, "EOR", IndirectY, "EOR failure"),
+            Code(0xF3, "ADC", IndirectY, "ADC failure"),
+            Code(0xF4, "ADC", IndirectY, "ADC failure"),
+            Code(0xF5, "ADC", IndirectY, "ADC failure"),
+            Code(0xF6, "ADC", IndirectY, "ADC failure"),
+            Code(0xF7, "ADC", IndirectY, "ADC failure"),
+            Code(0xF8, "CMP", IndirectY, "CMP failure"),
+            Code(0xF9, "CMP", IndirectY, "CMP failure"),
+            Code(0xFA, "CMP", IndirectY, "CMP failure"),
+            Code(0xFB, "CMP", IndirectY, "CMP failure"),
+            Code(0xFC, "CMP", IndirectY, "CMP failure"),
+            Code(0xFD, "CMP", IndirectY, "CMP failure"),
+            Code(0xFE, "CMP", IndirectY, "CMP failure"),
+        ],
+    },
+    Section {
+        name: r#"NOP "invalid" opcode tests"#,
+        codes: &[
+            Code(0x4E, "NOP", AbsoluteX, "absolute,X NOPs less than 3 bytes long"),
+            Code(0x4F, "NOP", Implied, "implied NOPs affects regs/flags"),
+            Code(0x50, "NOP", ZeroPageX, "ZP,X NOPs less than 2 bytes long"),
+            Code(0x51, "NOP", Absolute, "absolute NOP less than 3 bytes long"),
+            Code(0x52, "NOP", ZeroPage, "ZP NOPs less than 2 bytes long"),
+            Code(0x53, "NOP", AbsoluteX, "absolute,X NOPs less than 3 bytes long"),
+            Code(0x54, "NOP", Implied, "implied NOPs affects regs/flags"),
+            Code(0x55, "NOP", ZeroPageX, "ZP,X NOPs less than 2 bytes long"),
+            Code(0x56, "NOP", Absolute, "absolute NOP less than 3 bytes long"),
+            Code(0x57, "NOP", ZeroPage, "ZP NOPs less than 2 bytes long"),
+        ],
+    },
+];
+
+/// The error codes nestest.txt documents for $03, by section, without the RRA tests
+#[rustfmt::skip]
+const CODES_03: &[Section] = &[
+    Section {
+        name: "(indirect),y tests",
+        codes: &[
+            Code(0x01, "SBC", IndirectY, "SBC failure"),
+            Code(0x02, "SBC", IndirectY, "SBC failure"),
+            Code(0x03, "SBC", IndirectY, "SBC failure"),
+            Code(0x04, "SBC", IndirectY, "SBC failure"),
+            Code(0x05, "SBC", IndirectY, "SBC failure"),
+            Code(0x06, "STA", IndirectY, "STA failure"),
+            Code(0x07, "JMP", Indirect, "JMP () data reading didn't wrap properly (this fails on a 65C02)"),
+        ],
+    },
+    Section {
+        name: "zeropage,x tests",
+        codes: &[
+            Code(0x08, "LDY", ZeroPageX, "LDY,X failure"),
+            Code(0x09, "LDY", ZeroPageX, "LDY,X failure"),
+            Code(0x0A, "STY", ZeroPageX, "STY,X failure"),
+            Code(0x0B, "ORA", ZeroPageX, "ORA failure"),
+            Code(0x0C, "ORA", ZeroPageX, "ORA failure"),
+            Code(0x0D, "AND", ZeroPageX, "AND failure"),
+            Code(0x0E, "AND", ZeroPageX, "AND failure"),
+            Code(0x0F, "EOR", ZeroPageX, "EOR failure"),
+            Code(0x10, "EOR", ZeroPageX, "EOR failure"),
+            Code(0x11, "ADC", ZeroPageX, "ADC failure"),
+            Code(0x12, "ADC", ZeroPageX, "ADC failure"),
+            Code(0x13, "ADC", ZeroPageX, "ADC failure"),
+            Code(0x14, "ADC", ZeroPageX, "ADC failure"),
+            Code(0x15, "ADC", ZeroPageX, "ADC failure"),
+            Code(0x16, "CMP", ZeroPageX, "CMP failure"),
+            Code(0x17, "CMP", ZeroPageX, "CMP failure"),
+            Code(0x18, "CMP", ZeroPageX, "CMP failure"),
+            Code(0x19, "CMP", ZeroPageX, "CMP failure"),
+            Code(0x1A, "CMP", ZeroPageX, "CMP failure"),
+            Code(0x1B, "CMP", ZeroPageX, "CMP failure"),
+            Code(0x1C, "CMP", ZeroPageX, "CMP failure"),
+            Code(0x1D, "SBC", ZeroPageX, "SBC failure"),
+            Code(0x1E, "SBC", ZeroPageX, "SBC failure"),
+            Code(0x1F, "SBC", ZeroPageX, "SBC failure"),
+            Code(0x20, "SBC", ZeroPageX, "SBC failure"),
+            Code(0x21, "SBC", ZeroPageX, "SBC failure"),
+            Code(0x22, "LDA", ZeroPageX, "LDA failure"),
+            Code(0x23, "LDA", ZeroPageX, "LDA failure"),
+            Code(0x24, "STA", ZeroPageX, "STA failure"),
+            Code(0x25, "LSR", ZeroPageX, "LSR failure"),
+            Code(0x26, "LSR", ZeroPageX, "LSR failure"),
+            Code(0x27, "ASL", ZeroPageX, "ASL failure"),
+            Code(0x28, "ASL", ZeroPageX, "ASL failure"),
+            Code(0x29, "ROR", ZeroPageX, "ROR failure"),
+            Code(0x2A, "ROR", ZeroPageX, "ROR failure"),
+            Code(0x2B, "ROL", ZeroPageX, "ROL failure"),
+            Code(0x2C, "ROL", ZeroPageX, "ROL failure"),
+            Code(0x2D, "INC", ZeroPageX, "INC failure"),
+            Code(0x2E, "INC", ZeroPageX, "INC failure"),
+            Code(0x2F, "DEC", ZeroPageX, "DEC failure"),
+            Code(0x30, "DEC", ZeroPageX, "DEC failure"),
+            Code(0x31, "DEC", ZeroPageX, "DEC failure"),
+            Code(0x32, "LDX", ZeroPageY, "LDX,Y failure"),
+            Code(0x33, "LDX", ZeroPageY, "LDX,Y failure"),
+            Code(0x34, "STX", ZeroPageY, "STX,Y failure"),
+            Code(0x35, "STX", ZeroPageY, "STX,Y failure"),
+        ],
+    },
+    Section {
+        name: "absolute,y tests",
+        codes: &[
+            Code(0x36, "LDA", AbsoluteY, "LDA failure"),
+            Code(0x37, "LDA", AbsoluteY, "LDA failure to wrap properly from ffffh to 0000h"),
+            Code(0x38, "LDA", AbsoluteY, "LDA failure, page cross"),
+            Code(0x39, "ORA", AbsoluteY, "ORA failure"),
+            Code(0x3A, "ORA", AbsoluteY, "ORA failure"),
+            Code(0x3B, "AND", AbsoluteY, "AND failure"),
+            Code(0x3C, "AND", AbsoluteY, "AND failure"),
+            Code(0x3D, "EOR", AbsoluteY, "EOR failure"),
+            Code(0x3E, "EOR", AbsoluteY, "EOR failure"),
+            Code(0x3F, "ADC", AbsoluteY, "ADC failure"),
+            Code(0x40, "ADC", AbsoluteY, "ADC failure"),
+            Code(0x41, "ADC", AbsoluteY, "ADC failure"),
+            Code(0x42, "ADC", AbsoluteY, "ADC failure"),
+            Code(0x43, "ADC", AbsoluteY, "ADC failure"),
+            Code(0x44, "CMP", AbsoluteY, "CMP failure"),
+            Code(0x45, "CMP", AbsoluteY, "CMP failure"),
+            Code(0x46, "CMP", AbsoluteY, "CMP failure"),
+            Code(0x47, "CMP", AbsoluteY, "CMP failure"),
+            Code(0x48, "CMP", AbsoluteY, "CMP failure"),
+            Code(0x49, "CMP", AbsoluteY, "CMP failure"),
+            Code(0x4A, "CMP", AbsoluteY, "CMP failure"),
+            Code(0x4B, "SBC", AbsoluteY, "SBC failure"),
+            Code(0x4C, "SBC", AbsoluteY, "SBC failure"),
+            Code(0x4D, "SBC", AbsoluteY, "SBC failure"),
+            Code(0x4E, "SBC", AbsoluteY, "SBC failure"),
+            Code(0x4F, "SBC", AbsoluteY, "SBC failure"),
+            Code(0x50, "STA", AbsoluteY, "STA failure"),
+        ],
+    },
+    Section {
+        name: "absolute,x tests",
+        codes: &[
+            Code(0x51, "LDY", AbsoluteX, "LDY,X failure"),
+            Code(0x52, "LDY", AbsoluteX, "LDY,X failure (didn't page cross)"),
+            Code(0x53, "ORA", AbsoluteX, "ORA failure"),
+            Code(0x54, "ORA", AbsoluteX, "ORA failure"),
+            Code(0x55, "AND", AbsoluteX, "AND failure"),
+            Code(0x56, "AND", AbsoluteX, "AND failure"),
+            Code(0x57, "EOR", AbsoluteX, "EOR failure"),
+            Code(0x58, "EOR", AbsoluteX, "EOR failure"),
+            Code(0x59, "ADC", AbsoluteX, "ADC failure"),
+            Code(0x5A, "ADC", AbsoluteX, "ADC failure"),
+            Code(0x5B, "ADC", AbsoluteX, "ADC failure"),
+            Code(0x5C, "ADC", AbsoluteX, "ADC failure"),
+            Code(0x5D, "ADC", AbsoluteX, "ADC failure"),
+            Code(0x5E, "CMP", AbsoluteX, "CMP failure"),
+            Code(0x5F, "CMP", AbsoluteX, "CMP failure"),
+            Code(0x60, "CMP", AbsoluteX, "CMP failure"),
+            Code(0x61, "CMP", AbsoluteX, "CMP failure"),
+            Code(0x62, "CMP", AbsoluteX, "CMP failure"),
+            Code(0x63, "CMP", AbsoluteX, "CMP failure"),
+            Code(0x64, "CMP", AbsoluteX, "CMP failure"),
+            Code(0x65, "SBC", AbsoluteX, "SBC failure"),
+            Code(0x66, "SBC", AbsoluteX, "SBC failure"),
+            Code(0x67, "SBC", AbsoluteX, "SBC failure"),
+            Code(0x68, "SBC", AbsoluteX, "SBC failure"),
+            Code(0x69, "SBC", AbsoluteX, "SBC failure"),
+            Code(0x6A, "LDA", AbsoluteX, "LDA failure"),
+            Code(0x6B, "LDA", AbsoluteX, "LDA failure (didn't page cross)"),
+            Code(0x6C, "STA", AbsoluteX, "STA failure"),
+            Code(0x6D, "LSR", AbsoluteX, "LSR failure"),
+            Code(0x6E, "LSR", AbsoluteX, "LSR failure"),
+            Code(0x6F, "ASL", AbsoluteX, "ASL failure"),
+            Code(0x70, "ASL", AbsoluteX, "ASL failure"),
+            Code(0x71, "ROR", AbsoluteX, "ROR failure"),
+            Code(0x72, "ROR", AbsoluteX, "ROR failure"),
+            Code(0x73, "ROL", AbsoluteX, "ROL failure"),
+            Code(0x74, "ROL", AbsoluteX, "ROL failure"),
+            Code(0x75, "INC", AbsoluteX, "INC failure"),
+            Code(0x76, "INC", AbsoluteX, "INC failure"),
+            Code(0x77, "DEC", AbsoluteX, "DEC failure"),
+            Code(0x78, "DEC", AbsoluteX, "DEC failure"),
+            Code(0x79, "DEC", AbsoluteX, "DEC failure"),
+            Code(0x7A, "LDX", AbsoluteY, "LDX,Y failure"),
+            Code(0x7B, "LDX", AbsoluteY, "LDX,Y failure"),
+        ],
+    },
+    Section {
+        name: r#"LAX "invalid" opcode tests"#,
+        codes: &[
+            Code(0x7C, "LAX", IndirectX, "LAX (indr,x) failure"),
+            Code(0x7D, "LAX", IndirectX, "LAX (indr,x) failure"),
+            Code(0x7E, "LAX", ZeroPage, "LAX zeropage failure"),
+            Code(0x7F, "LAX", ZeroPage, "LAX zeropage failure"),
+            Code(0x80, "LAX", Absolute, "LAX absolute failure"),
+            Code(0x81, "LAX", Absolute, "LAX absolute failure"),
+            Code(0x82, "LAX", IndirectY, "LAX (indr),y failure"),
+            Code(0x83, "LAX", IndirectY, "LAX (indr),y failure"),
+            Code(0x84, "LAX", ZeroPageY, "LAX zp,y failure"),
+            Code(0x85, "LAX", ZeroPageY, "LAX zp,y failure"),
+            Code(0x86, "LAX", AbsoluteY, "LAX abs,y failure"),
+            Code(0x87, "LAX", AbsoluteY, "LAX abs,y failure"),
+        ],
+    },
+    Section {
+        name: r#"SAX "invalid" opcode tests"#,
+        codes: &[
+            Code(0x88, "SAX", IndirectX, "SAX (indr,x) failure"),
+            Code(0x89, "SAX", IndirectX, "SAX (indr,x) failure"),
+            Code(0x8A, "SAX", ZeroPage, "SAX zeropage failure"),
+            Code(0x8B, "SAX", ZeroPage, "SAX zeropage failure"),
+            Code(0x8C, "SAX", Absolute, "SAX absolute failure"),
+            Code(0x8D, "SAX", Absolute, "SAX absolute failure"),
+            Code(0x8E, "SAX", ZeroPageY, "SAX zp,y failure"),
+            Code(0x8F, "SAX", ZeroPageY, "SAX zp,y failure"),
+        ],
+    },
+    Section {
+        name: r#"SBC "invalid" opcode tests"#,
+        codes: &[
+            Code(0x90, "SBC", Immediate, "SBC failure"),
+            Code(0x91, "SBC", Immediate, "SBC failure"),
+            Code(0x92, "SBC", Immediate, "SBC failure"),
+            Code(0x93, "SBC", Immediate, "SBC failure"),
+            Code(0x94, "SBC", Immediate, "SBC failure"),
+        ],
+    },
+    Section {
+        name: r#"DCP "invalid" opcode tests"#,
+        codes: &[
+            Code(0x95, "DCP", IndirectX, "DCP (indr,x) failure"),
+            Code(0x96, "DCP", IndirectX, "DCP (indr,x) failure"),
+            Code(0x97, "DCP", IndirectX, "DCP (indr,x) failure"),
+            Code(0x98, "DCP", ZeroPage, "DCP zeropage failure"),
+            Code(0x99, "DCP", ZeroPage, "DCP zeropage failure"),
+            Code(0x9A, "DCP", ZeroPage, "DCP zeropage failure"),
+            Code(0x9B, "DCP", Absolute, "DCP absolute failure"),
+            Code(0x9C, "DCP", Absolute, "DCP absolute failure"),
+            Code(0x9D, "DCP", Absolute, "DCP absolute failure"),
+            Code(0x9E, "DCP", IndirectY, "DCP (indr),y failure"),
+            Code(0x9F, "DCP", IndirectY, "DCP (indr),y failure"),
+            Code(0xA0, "DCP", IndirectY, "DCP (indr),y failure"),
+            Code(0xA1, "DCP", ZeroPageX, "DCP zp,x failure"),
+            Code(0xA2, "DCP", ZeroPageX, "DCP zp,x failure"),
+            Code(0xA3, "DCP", ZeroPageX, "DCP zp,x failure"),
+            Code(0xA4, "DCP", AbsoluteY, "DCP abs,y failure"),
+            Code(0xA5, "DCP", AbsoluteY, "DCP abs,y failure"),
+            Code(0xA6, "DCP", AbsoluteY, "DCP abs,y failure"),
+            Code(0xA7, "DCP", AbsoluteX, "DCP abs,x failure"),
+            Code(0xA8, "DCP", AbsoluteX, "DCP abs,x failure"),
+            Code(0xA9, "DCP", AbsoluteX, "DCP abs,x failure"),
+        ],
+    },
+    Section {
+        name: r#"ISB "invalid" opcode tests"#,
+        codes: &[
+            Code(0xAA, "ISB", IndirectX, "ISB (indr,x) failure"),
+            Code(0xAB, "ISB", IndirectX, "ISB (indr,x) failure"),
+            Code(0xAC, "ISB", IndirectX, "ISB (indr,x) failure"),
+            Code(0xAD, "ISB", ZeroPage, "ISB zeropage failure"),
+            Code(0xAE, "ISB", ZeroPage, "ISB zeropage failure"),
+            Code(0xAF, "ISB", ZeroPage, "ISB zeropage failure"),
+            Code(0xB0, "ISB", Absolute, "ISB absolute failure"),
+            Code(0xB1, "ISB", Absolute, "ISB absolute failure"),
+            Code(0xB2, "ISB", Absolute, "ISB absolute failure"),
+            Code(0xB3, "ISB", IndirectY, "ISB (indr),y failure"),
+            Code(0xB4, "ISB", IndirectY, "ISB (indr),y failure"),
+            Code(0xB5, "ISB", IndirectY, "ISB (indr),y failure"),
+            Code(0xB6, "ISB", ZeroPageX, "ISB zp,x failure"),
+            Code(0xB7, "ISB", ZeroPageX, "ISB zp,x failure"),
+            Code(0xB8, "ISB", ZeroPageX, "ISB zp,x failure"),
+            Code(0xB9, "ISB", AbsoluteY, "ISB abs,y failure"),
+            Code(0xBA, "ISB", AbsoluteY, "ISB abs,y failure"),
+            Code(0xBB, "ISB", AbsoluteY, "ISB abs,y failure"),
+            Code(0xBC, "ISB", AbsoluteX, "ISB abs,x failure"),
+            Code(0xBD, "ISB", AbsoluteX, "ISB abs,x failure"),
+            Code(0xBE, "ISB", AbsoluteX, "ISB abs,x failure"),
+        ],
+    },
+    Section {
+        name: r#"SLO "invalid" opcode tests"#,
+        codes: &[
+            Code(0xBF, "SLO", IndirectX, "SLO (indr,x) failure"),
+            Code(0xC0, "SLO", IndirectX, "SLO (indr,x) failure"),
+            Code(0xC1, "SLO", IndirectX, "SLO (indr,x) failure"),
+            Code(0xC2, "SLO", ZeroPage, "SLO zeropage failure"),
+            Code(0xC3, "SLO", ZeroPage, "SLO zeropage failure"),
+            Code(0xC4, "SLO", ZeroPage, "SLO zeropage failure"),
+            Code(0xC5, "SLO", Absolute, "SLO absolute failure"),
+            Code(0xC6, "SLO", Absolute, "SLO absolute failure"),
+            Code(0xC7, "SLO", Absolute, "SLO absolute failure"),
+            Code(0xC8, "SLO", IndirectY, "SLO (indr),y failure"),
+            Code(0xC9, "SLO", IndirectY, "SLO (indr),y failure"),
+            Code(0xCA, "SLO", IndirectY, "SLO (indr),y failure"),
+            Code(0xCB, "SLO", ZeroPageX, "SLO zp,x failure"),
+            Code(0xCC, "SLO", ZeroPageX, "SLO zp,x failure"),
+            Code(0xCD, "SLO", ZeroPageX, "SLO zp,x failure"),
+            Code(0xCE, "SLO", AbsoluteY, "SLO abs,y failure"),
+            Code(0xCF, "SLO", AbsoluteY, "SLO abs,y failure"),
+            Code(0xD0, "SLO", AbsoluteY, "SLO abs,y failure"),
+            Code(0xD1, "SLO", AbsoluteX, "SLO abs,x failure"),
+            Code(0xD2, "SLO", AbsoluteX, "SLO abs,x failure"),
+            Code(0xD3, "SLO", AbsoluteX, "SLO abs,x failure"),
+        ],
+    },
+    Section {
+        name: r#"RLA "invalid" opcode tests"#,
+        codes: &[
+            Code(0xD4, "RLA", IndirectX, "RLA (indr,x) failure"),
+            Code(0xD5, "RLA", IndirectX, "RLA (indr,x) failure"),
+            Code(0xD6, "RLA", IndirectX, "RLA (indr,x) failure"),
+            Code(0xD7, "RLA", ZeroPage, "RLA zeropage failure"),
+            Code(0xD8, "RLA", ZeroPage, "RLA zeropage failure"),
+            Code(0xD9, "RLA", ZeroPage, "RLA zeropage failure"),
+            Code(0xDA, "RLA", Absolute, "RLA absolute failure"),
+            Code(0xDB, "RLA", Absolute, "RLA absolute failure"),
+            Code(0xDC, "RLA", Absolute, "RLA absolute failure"),
+            Code(0xDD, "RLA", IndirectY, "RLA (indr),y failure"),
+            Code(0xDE, "RLA", IndirectY, "RLA (indr),y failure"),
+            Code(0xDF, "RLA", IndirectY, "RLA (indr),y failure"),
+            Code(0xE0, "RLA", ZeroPageX, "RLA zp,x failure"),
+            Code(0xE1, "RLA", ZeroPageX, "RLA zp,x failure"),
+            Code(0xE2, "RLA", ZeroPageX, "RLA zp,x failure"),
+            Code(0xE3, "RLA", AbsoluteY, "RLA abs,y failure"),
+            Code(0xE4, "RLA", AbsoluteY, "RLA abs,y failure"),
+            Code(0xE5, "RLA", AbsoluteY, "RLA abs,y failure"),
+            Code(0xE6, "RLA", AbsoluteX, "RLA abs,x failure"),
+            Code(0xE7, "RLA", AbsoluteX, "RLA abs,x failure"),
+            Code(0xE8, "RLA", AbsoluteX, "RLA abs,x failure"),
+        ],
+    },
+    Section {
+        name: r#"SRE "invalid" opcode tests"#,
+        codes: &[
+            Code(0xE9, "SRE", IndirectX, "SRE (indr,x) failure"),
+            Code(0xEA, "SRE", IndirectX, "SRE (indr,x) failure"),
+            Code(0xEB, "SRE", IndirectX, "SRE (indr,x) failure"),
+            Code(0xEC, "SRE", ZeroPage, "SRE zeropage failure"),
+            Code(0xED, "SRE", ZeroPage, "SRE zeropage failure"),
+            Code(0xEE, "SRE", ZeroPage, "SRE zeropage failure"),
+            Code(0xEF, "SRE", Absolute, "SRE absolute failure"),
+            Code(0xF0, "SRE", Absolute, "SRE absolute failure"),
+            Code(0xF1, "SRE", Absolute, "SRE absolute failure"),
+            Code(0xF2, "SRE", IndirectY, "SRE (indr),y failure"),
+            Code(0xF3, "SRE", IndirectY, "SRE (indr),y failure"),
+            Code(0xF4, "SRE", IndirectY, "SRE (indr),y failure"),
+            Code(0xF5, "SRE", ZeroPageX, "SRE zp,x failure"),
+            Code(0xF6, "SRE", ZeroPageX, "SRE zp,x failure"),
+            Code(0xF7, "SRE", ZeroPageX, "SRE zp,x failure"),
+            Code(0xF8, "SRE", AbsoluteY, "SRE abs,y failure"),
+            Code(0xF9, "SRE", AbsoluteY, "SRE abs,y failure"),
+            Code(0xFA, "SRE", AbsoluteY, "SRE abs,y failure"),
+            Code(0xFB, "SRE", AbsoluteX, "SRE abs,x failure"),
+            Code(0xFC, "SRE", AbsoluteX, "SRE abs,x failure"),
+            Code(0xFD, "SRE", AbsoluteX, "SRE abs,x failure"),
+        ],
+    },
+];
+
+/// The error codes nestest.txt documents for the RRA tests, which reuse the codes from the start of $03
+#[rustfmt::skip]
+const CODES_RRA: &[Section] = &[
+    Section {
+        name: r#"RRA "invalid" opcode tests"#,
+        codes: &[
+            Code(0x01, "RRA", IndirectX, "RRA (indr,x) failure"),
+            Code(0x02, "RRA", IndirectX, "RRA (indr,x) failure"),
+            Code(0x03, "RRA", IndirectX, "RRA (indr,x) failure"),
+            Code(0x04, "RRA", ZeroPage, "RRA zeropage failure"),
+            Code(0x05, "RRA", ZeroPage, "RRA zeropage failure"),
+            Code(0x06, "RRA", ZeroPage, "RRA zeropage failure"),
+            Code(0x07, "RRA", Absolute, "RRA absolute failure"),
+            Code(0x08, "RRA", Absolute, "RRA absolute failure"),
+            Code(0x09, "RRA", Absolute, "RRA absolute failure"),
+            Code(0x0A, "RRA", IndirectY, "RRA (indr),y failure"),
+            Code(0x0B, "RRA", IndirectY, "RRA (indr),y failure"),
+            Code(0x0C, "RRA", IndirectY, "RRA (indr),y failure"),
+            Code(0x0D, "RRA", ZeroPageX, "RRA zp,x failure"),
+            Code(0x0E, "RRA", ZeroPageX, "RRA zp,x failure"),
+            Code(0x0F, "RRA", ZeroPageX, "RRA zp,x failure"),
+            Code(0x10, "RRA", AbsoluteY, "RRA abs,y failure"),
+            Code(0x11, "RRA", AbsoluteY, "RRA abs,y failure"),
+            Code(0x12, "RRA", AbsoluteY, "RRA abs,y failure"),
+            Code(0x13, "RRA", AbsoluteX, "RRA abs,x failure"),
+            Code(0x14, "RRA", AbsoluteX, "RRA abs,x failure"),
+            Code(0x15, "RRA", AbsoluteX, "RRA abs,x failure"),
+        ],
+    },
+];
+
+/// The names of the fields of a [`LogLine`] that are compared, in the order [`LogLine::differences`] returns them
+pub(crate) const LOG_FIELDS: [&str; 8] = ["PC", "opcode", "A", "X", "Y", "P", "SP", "CYC"];
+
+/// A single line of `nestest.log`, containing the cpu state right before an instruction is executed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LogLine {
+    pub pc: u16,
+    pub bytes: Vec<u8>,
+    /// The disassembly of the instruction, which isn't compared
+    pub disassembly: String,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub sp: u8,
+    pub cycle: u64,
+}
+
+impl LogLine {
+    /// Parses a line in the Nintendulator format used by `nestest.log`:
+    /// `C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7`
+    pub fn parse(line: &str) -> Option<Self> {
+        let field = |name: &str| {
+            let start = line.find(name)? + name.len();
+            let value = line[start..].split_whitespace().next()?;
+            Some(value)
+        };
+        let byte = |name: &str| u8::from_str_radix(field(name)?, 16).ok();
+
+        let bytes = line
+            .get(6..15)?
+            .split_whitespace()
+            .map(|b| u8::from_str_radix(b, 16).ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            pc: u16::from_str_radix(line.get(0..4)?, 16).ok()?,
+            bytes,
+            disassembly: line.get(15..48)?.trim().to_string(),
+            a: byte(" A:")?,
+            x: byte(" X:")?,
+            y: byte(" Y:")?,
+            p: byte(" P:")?,
+            sp: byte(" SP:")?,
+            cycle: field(" CYC:")?.parse().ok()?,
+        })
+    }
+
+    /// Lists the names of all fields that differ between `self` and `other`
+    pub fn differences(&self, other: &Self) -> Vec<&'static str> {
+        let differs = [
+            self.pc != other.pc,
+            self.bytes != other.bytes,
+            self.a != other.a,
+            self.x != other.x,
+            self.y != other.y,
+            self.p != other.p,
+            self.sp != other.sp,
+            self.cycle != other.cycle,
+        ];
+
+        LOG_FIELDS
+            .into_iter()
+            .zip(differs)
+            .filter_map(|(name, differs)| differs.then_some(name))
+            .collect()
+    }
+}
+
+impl Display for LogLine {
+    /// Formats the line like `nestest.log` does
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let bytes = self
+            .bytes
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let dot = self.cycle * 3;
+
+        write!(
+            f,
+            "{:04X}  {bytes:<8} {}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+            self.pc,
+            column(&self.disassembly, 32),
+            self.a,
+            self.x,
+            self.y,
+            self.p,
+            self.sp,
+            (dot / 341) % 262,
+            dot % 341,
+            self.cycle
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NESTEST_LOG;
+
+    /// Every code nestest.txt documents for the unofficial instructions and RRA, and a few of the official ones,
+    /// as it lists them: the unofficial instructions count their codes from $7C in the order LAX, SAX, SBC, DCP, ISB,
+    /// SLO, RLA and SRE, and RRA counts from $01 again. The read-modify-write instructions have three codes per mode.
+    #[rustfmt::skip]
+    const DOCUMENTED: &[(u16, u8, &str, AddressingMode)] = &[
+        (0x0003, 0x01, "BCS", Relative),
+        (0x0003, 0x10, "BMI", Relative),
+        (0x0003, 0x11, "PHP", Implied),
+        (0x0003, 0x18, "ORA", Immediate),
+        (0x0003, 0x3C, "LDY", Immediate),
+        (0x0003, 0x47, "JSR", Absolute),
+        (0x0003, 0x4A, "LSR", Accumulator),
+        (0x0003, 0x58, "LDA", IndirectX),
+        (0x0003, 0x71, "SBC", Immediate),
+        (0x0003, 0x76, "LDA", ZeroPage),
+        (0x0011, 0x36, "LDA", AbsoluteY),
+        (0x0011, 0x51, "LDY", AbsoluteX),
+        (0x0011, 0x7A, "LDX", AbsoluteY),
+        (0x0011, 0x7B, "LDX", AbsoluteY),
+        (0x0011, 0x7C, "LAX", IndirectX),
+        (0x0011, 0x7D, "LAX", IndirectX),
+        (0x0011, 0x7E, "LAX", ZeroPage),
+        (0x0011, 0x7F, "LAX", ZeroPage),
+        (0x0011, 0x80, "LAX", Absolute),
+        (0x0011, 0x81, "LAX", Absolute),
+        (0x0011, 0x82, "LAX", IndirectY),
+        (0x0011, 0x83, "LAX", IndirectY),
+        (0x0011, 0x84, "LAX", ZeroPageY),
+        (0x0011, 0x85, "LAX", ZeroPageY),
+        (0x0011, 0x86, "LAX", AbsoluteY),
+        (0x0011, 0x87, "LAX", AbsoluteY),
+        (0x0011, 0x88, "SAX", IndirectX),
+        (0x0011, 0x89, "SAX", IndirectX),
+        (0x0011, 0x8A, "SAX", ZeroPage),
+        (0x0011, 0x8B, "SAX", ZeroPage),
+        (0x0011, 0x8C, "SAX", Absolute),
+        (0x0011, 0x8D, "SAX", Absolute),
+        (0x0011, 0x8E, "SAX", ZeroPageY),
+        (0x0011, 0x8F, "SAX", ZeroPageY),
+        (0x0011, 0x90, "SBC", Immediate),
+        (0x0011, 0x91, "SBC", Immediate),
+        (0x0011, 0x92, "SBC", Immediate),
+        (0x0011, 0x93, "SBC", Immediate),
+        (0x0011, 0x94, "SBC", Immediate),
+        (0x0011, 0x95, "DCP", IndirectX),
+        (0x0011, 0x96, "DCP", IndirectX),
+        (0x0011, 0x97, "DCP", IndirectX),
+        (0x0011, 0x98, "DCP", ZeroPage),
+        (0x0011, 0x99, "DCP", ZeroPage),
+        (0x0011, 0x9A, "DCP", ZeroPage),
+        (0x0011, 0x9B, "DCP", Absolute),
+        (0x0011, 0x9C, "DCP", Absolute),
+        (0x0011, 0x9D, "DCP", Absolute),
+        (0x0011, 0x9E, "DCP", IndirectY),
+        (0x0011, 0x9F, "DCP", IndirectY),
+        (0x0011, 0xA0, "DCP", IndirectY),
+        (0x0011, 0xA1, "DCP", ZeroPageX),
+        (0x0011, 0xA2, "DCP", ZeroPageX),
+        (0x0011, 0xA3, "DCP", ZeroPageX),
+        (0x0011, 0xA4, "DCP", AbsoluteY),
+        (0x0011, 0xA5, "DCP", AbsoluteY),
+        (0x0011, 0xA6, "DCP", AbsoluteY),
+        (0x0011, 0xA7, "DCP", AbsoluteX),
+        (0x0011, 0xA8, "DCP", AbsoluteX),
+        (0x0011, 0xA9, "DCP", AbsoluteX),
+        (0x0011, 0xAA, "ISB", IndirectX),
+        (0x0011, 0xAB, "ISB", IndirectX),
+        (0x0011, 0xAC, "ISB", IndirectX),
+        (0x0011, 0xAD, "ISB", ZeroPage),
+        (0x0011, 0xAE, "ISB", ZeroPage),
+        (0x0011, 0xAF, "ISB", ZeroPage),
+        (0x0011, 0xB0, "ISB", Absolute),
+        (0x0011, 0xB1, "ISB", Absolute),
+        (0x0011, 0xB2, "ISB", Absolute),
+        (0x0011, 0xB3, "ISB", IndirectY),
+        (0x0011, 0xB4, "ISB", IndirectY),
+        (0x0011, 0xB5, "ISB", IndirectY),
+        (0x0011, 0xB6, "ISB", ZeroPageX),
+        (0x0011, 0xB7, "ISB", ZeroPageX),
+        (0x0011, 0xB8, "ISB", ZeroPageX),
+        (0x0011, 0xB9, "ISB", AbsoluteY),
+        (0x0011, 0xBA, "ISB", AbsoluteY),
+        (0x0011, 0xBB, "ISB", AbsoluteY),
+        (0x0011, 0xBC, "ISB", AbsoluteX),
+        (0x0011, 0xBD, "ISB", AbsoluteX),
+        (0x0011, 0xBE, "ISB", AbsoluteX),
+        (0x0011, 0xBF, "SLO", IndirectX),
+        (0x0011, 0xC0, "SLO", IndirectX),
+        (0x0011, 0xC1, "SLO", IndirectX),
+        (0x0011, 0xC2, "SLO", ZeroPage),
+        (0x0011, 0xC3, "SLO", ZeroPage),
+        (0x0011, 0xC4, "SLO", ZeroPage),
+        (0x0011, 0xC5, "SLO", Absolute),
+        (0x0011, 0xC6, "SLO", Absolute),
+        (0x0011, 0xC7, "SLO", Absolute),
+        (0x0011, 0xC8, "SLO", IndirectY),
+        (0x0011, 0xC9, "SLO", IndirectY),
+        (0x0011, 0xCA, "SLO", IndirectY),
+        (0x0011, 0xCB, "SLO", ZeroPageX),
+        (0x0011, 0xCC, "SLO", ZeroPageX),
+        (0x0011, 0xCD, "SLO", ZeroPageX),
+        (0x0011, 0xCE, "SLO", AbsoluteY),
+        (0x0011, 0xCF, "SLO", AbsoluteY),
+        (0x0011, 0xD0, "SLO", AbsoluteY),
+        (0x0011, 0xD1, "SLO", AbsoluteX),
+        (0x0011, 0xD2, "SLO", AbsoluteX),
+        (0x0011, 0xD3, "SLO", AbsoluteX),
+        (0x0011, 0xD4, "RLA", IndirectX),
+        (0x0011, 0xD5, "RLA", IndirectX),
+        (0x0011, 0xD6, "RLA", IndirectX),
+        (0x0011, 0xD7, "RLA", ZeroPage),
+        (0x0011, 0xD8, "RLA", ZeroPage),
+        (0x0011, 0xD9, "RLA", ZeroPage),
+        (0x0011, 0xDA, "RLA", Absolute),
+        (0x0011, 0xDB, "RLA", Absolute),
+        (0x0011, 0xDC, "RLA", Absolute),
+        (0x0011, 0xDD, "RLA", IndirectY),
+        (0x0011, 0xDE, "RLA", IndirectY),
+        (0x0011, 0xDF, "RLA", IndirectY),
+        (0x0011, 0xE0, "RLA", ZeroPageX),
+        (0x0011, 0xE1, "RLA", ZeroPageX),
+        (0x0011, 0xE2, "RLA", ZeroPageX),
+        (0x0011, 0xE3, "RLA", AbsoluteY),
+        (0x0011, 0xE4, "RLA", AbsoluteY),
+        (0x0011, 0xE5, "RLA", AbsoluteY),
+        (0x0011, 0xE6, "RLA", AbsoluteX),
+        (0x0011, 0xE7, "RLA", AbsoluteX),
+        (0x0011, 0xE8, "RLA", AbsoluteX),
+        (0x0011, 0xE9, "SRE", IndirectX),
+        (0x0011, 0xEA, "SRE", IndirectX),
+        (0x0011, 0xEB, "SRE", IndirectX),
+        (0x0011, 0xEC, "SRE", ZeroPage),
+        (0x0011, 0xED, "SRE", ZeroPage),
+        (0x0011, 0xEE, "SRE", ZeroPage),
+        (0x0011, 0xEF, "SRE", Absolute),
+        (0x0011, 0xF0, "SRE", Absolute),
+        (0x0011, 0xF1, "SRE", Absolute),
+        (0x0011, 0xF2, "SRE", IndirectY),
+        (0x0011, 0xF3, "SRE", IndirectY),
+        (0x0011, 0xF4, "SRE", IndirectY),
+        (0x0011, 0xF5, "SRE", ZeroPageX),
+        (0x0011, 0xF6, "SRE", ZeroPageX),
+        (0x0011, 0xF7, "SRE", ZeroPageX),
+        (0x0011, 0xF8, "SRE", AbsoluteY),
+        (0x0011, 0xF9, "SRE", AbsoluteY),
+        (0x0011, 0xFA, "SRE", AbsoluteY),
+        (0x0011, 0xFB, "SRE", AbsoluteX),
+        (0x0011, 0xFC, "SRE", AbsoluteX),
+        (0x0011, 0xFD, "SRE", AbsoluteX),
+        (0x0002, 0x01, "RRA", IndirectX),
+        (0x0002, 0x02, "RRA", IndirectX),
+        (0x0002, 0x03, "RRA", IndirectX),
+        (0x0002, 0x04, "RRA", ZeroPage),
+        (0x0002, 0x05, "RRA", ZeroPage),
+        (0x0002, 0x06, "RRA", ZeroPage),
+        (0x0002, 0x07, "RRA", Absolute),
+        (0x0002, 0x08, "RRA", Absolute),
+        (0x0002, 0x09, "RRA", Absolute),
+        (0x0002, 0x0A, "RRA", IndirectY),
+        (0x0002, 0x0B, "RRA", IndirectY),
+        (0x0002, 0x0C, "RRA", IndirectY),
+        (0x0002, 0x0D, "RRA", ZeroPageX),
+        (0x0002, 0x0E, "RRA", ZeroPageX),
+        (0x0002, 0x0F, "RRA", ZeroPageX),
+        (0x0002, 0x10, "RRA", AbsoluteY),
+        (0x0002, 0x11, "RRA", AbsoluteY),
+        (0x0002, 0x12, "RRA", AbsoluteY),
+        (0x0002, 0x13, "RRA", AbsoluteX),
+        (0x0002, 0x14, "RRA", AbsoluteX),
+        (0x0002, 0x15, "RRA", AbsoluteX),
+    ];
+
+    #[test]
+    fn documented_codes_decode_to_their_instruction() {
+        for &(address, code, mnemonic, mode) in DOCUMENTED {
+            let failure = NestestFailure::decode(address, code);
+            assert_eq!(
+                (failure.mnemonic, failure.mode),
+                (Some(mnemonic), Some(mode)),
+                "${address:02X} = {code:02X}"
+            );
+            assert!(failure.description.contains(mnemonic), "{failure:?}");
+        }
+    }
+
+    #[test]
+    fn every_unofficial_code_is_documented() {
+        let unofficial = |address: u16, codes: std::ops::RangeInclusive<u8>| {
+            codes.filter(move |&code| {
+                !DOCUMENTED
+                    .iter()
+                    .any(|&(a, c, ..)| a == address && c == code)
+            })
+        };
+
+        assert_eq!(unofficial(UNOFFICIAL_ADDRESS, 0x7C..=0xFD).count(), 0);
+        assert_eq!(unofficial(RRA_ADDRESS, 0x01..=0x15).count(), 0);
+    }
+
+    #[test]
+    fn undocumented_codes() {
+        for (address, code) in [
+            (0x0002, 0x16),
+            (0x0011, 0xFE),
+            (0x0003, 0xFF),
+            (0x0004, 0x01),
+        ] {
+            let failure = NestestFailure::decode(address, code);
+            assert_eq!(failure.mnemonic, None);
+            assert_eq!(failure.description, "unknown failure");
+        }
+    }
+
+    #[test]
+    fn failures_leave_out_codes_that_are_zero() {
+        let failures = nestest_failures(0x00, 0xAA, 0x0A);
+
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].mnemonic, Some("ISB"));
+        assert_eq!(failures[1].address, RRA_ADDRESS);
+        assert_eq!(failures[1].mnemonic, Some("RRA"));
+        assert!(nestest_failures(0, 0, 0).is_empty());
+    }
+
+    #[test]
+    fn log_lines_format_like_nestest_log() {
+        for line in NESTEST_LOG.lines() {
+            let line = line.trim_end_matches('\r');
+            let parsed = LogLine::parse(line).unwrap();
+            assert_eq!(parsed.to_string(), line);
+        }
+    }
+
+    #[test]
+    fn log_line_fields() {
+        let line = LogLine::parse(
+            "D922  B1 89     LDA ($89),Y = 0300 @ 0300 = 89  A:00 X:65 Y:00 P:27 SP:FB PPU: 77, 23 CYC:8760",
+        )
+        .unwrap();
+
+        assert_eq!(
+            line,
+            LogLine {
+                pc: 0xD922,
+                bytes: vec![0xB1, 0x89],
+                disassembly: "LDA ($89),Y = 0300 @ 0300 = 89".to_string(),
+                a: 0x00,
+                x: 0x65,
+                y: 0x00,
+                p: 0x27,
+                sp: 0xFB,
+                cycle: 8760,
+            }
+        );
+        assert_eq!(LogLine::parse("D922  B1 89"), None);
+    }
+
+    #[test]
+    fn differences_ignore_the_disassembly() {
+        let expected = LogLine::parse(NESTEST_LOG.lines().next().unwrap()).unwrap();
+        let actual = LogLine {
+            disassembly: String::new(),
+            a: 0x01,
+            cycle: 8,
+            ..expected.clone()
+        };
+
+        assert_eq!(expected.differences(&expected), Vec::<&str>::new());
+        assert_eq!(expected.differences(&actual), ["A", "CYC"]);
     }
 }
@@ -0,0 +1,388 @@
+use bitflags::bitflags;
+use std::fmt::{Display, Formatter};
+use AddressingMode::*;
+
+/// The ways a 6502 instruction can get its operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressingMode {
+    /// The instruction has no operand, like `INX`
+    Implied,
+    /// The instruction works on the accumulator, like `LSR A`
+    Accumulator,
+    /// The operand is the byte after the opcode, like `LDA #$10`
+    Immediate,
+    /// The operand is at an address in the zero page, like `LDA $10`
+    ZeroPage,
+    /// Like [`ZeroPage`](Self::ZeroPage), with X added to the address, wrapping around in the zero page
+    ZeroPageX,
+    /// Like [`ZeroPage`](Self::ZeroPage), with Y added to the address, wrapping around in the zero page
+    ZeroPageY,
+    /// The operand is at a 16-bit address, like `LDA $1234`
+    Absolute,
+    /// Like [`Absolute`](Self::Absolute), with X added to the address
+    AbsoluteX,
+    /// Like [`Absolute`](Self::Absolute), with Y added to the address
+    AbsoluteY,
+    /// The address to jump to is read from a 16-bit address, like `JMP ($1234)`
+    Indirect,
+    /// The address of the operand is read from the zero page, at the address plus X, like `LDA ($10,X)`
+    IndirectX,
+    /// The address of the operand is read from the zero page, and Y is added to it, like `LDA ($10),Y`
+    IndirectY,
+    /// The branch target is the address of the next instruction plus a signed offset, like `BNE *+4`
+    Relative,
+}
+
+impl AddressingMode {
+    /// The amount of bytes an instruction with this addressing mode takes, including the opcode
+    pub const fn instruction_length(self) -> u16 {
+        match self {
+            AddressingMode::Implied | AddressingMode::Accumulator => 1,
+            AddressingMode::Immediate
+            | AddressingMode::ZeroPage
+            | AddressingMode::ZeroPageX
+            | AddressingMode::ZeroPageY
+            | AddressingMode::IndirectX
+            | AddressingMode::IndirectY
+            | AddressingMode::Relative => 2,
+            AddressingMode::Absolute
+            | AddressingMode::AbsoluteX
+            | AddressingMode::AbsoluteY
+            | AddressingMode::Indirect => 3,
+        }
+    }
+}
+
+impl Display for AddressingMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AddressingMode::Implied => "implied",
+            AddressingMode::Accumulator => "accumulator",
+            AddressingMode::Immediate => "immediate",
+            AddressingMode::ZeroPage => "zero page",
+            AddressingMode::ZeroPageX => "zero page,X",
+            AddressingMode::ZeroPageY => "zero page,Y",
+            AddressingMode::Absolute => "absolute",
+            AddressingMode::AbsoluteX => "absolute,X",
+            AddressingMode::AbsoluteY => "absolute,Y",
+            AddressingMode::Indirect => "indirect",
+            AddressingMode::IndirectX => "(indirect,X)",
+            AddressingMode::IndirectY => "(indirect),Y",
+            AddressingMode::Relative => "relative",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+bitflags! {
+    /// The flags of the status register. The break flag and bit 5 aren't included, as they don't exist in the cpu:
+    /// they only show up in the copy of the status register that is pushed to the stack.
+    pub struct StatusFlags: u8 {
+        /// The carry flag (C)
+        const CARRY             = 0x01;
+        /// The zero flag (Z)
+        const ZERO              = 0x02;
+        /// The interrupt disable flag (I)
+        const INTERRUPT_DISABLE = 0x04;
+        /// The decimal flag (D), which the NES doesn't use in its arithmetic
+        const DECIMAL           = 0x08;
+        /// The overflow flag (V)
+        const OVERFLOW          = 0x40;
+        /// The negative flag (N)
+        const NEGATIVE          = 0x80;
+    }
+}
+
+/// What kind of instruction an [`Opcode`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpcodeClass {
+    /// One of the 151 official instructions of the 6502
+    Official,
+    /// An unofficial instruction that does the same on every NES, like `LAX` and the extra `NOP`s
+    Unofficial,
+    /// An unofficial instruction of which the result depends on the chip or on analog effects,
+    /// like `XAA` and `SHA`. Tests only check these loosely, if at all.
+    Unstable,
+    /// An opcode that halts the cpu until it is reset
+    Jam,
+}
+
+/// What the 6502 does for an opcode, see [`OPCODES`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opcode {
+    /// The name of the instruction, like `LDA`. `nestest.log` spells unofficial instructions with a `*` in front.
+    pub mnemonic: &'static str,
+    /// How the instruction gets its operand
+    pub mode: AddressingMode,
+    /// The amount of bytes the instruction takes, including the opcode
+    pub length: u16,
+    /// The amount of cycles the instruction takes. Branches take a cycle more when they are taken,
+    /// and another one when they jump to another page.
+    pub cycles: u8,
+    /// Whether the instruction takes a cycle more when indexing crosses a page
+    pub page_penalty: bool,
+    /// Whether the instruction is official, unofficial or unstable, or halts the cpu
+    pub class: OpcodeClass,
+    /// The flags the instruction can change. `BRK` sets the interrupt disable flag,
+    /// and `PLP` and `RTI` replace all flags with a value from the stack.
+    pub flags: StatusFlags,
+}
+
+impl Opcode {
+    /// Describes how many cycles the instruction takes, like "4 cycles, 1 more when indexing crosses a page"
+    pub(crate) fn timing(&self) -> String {
+        if self.class == OpcodeClass::Jam {
+            return "halts the cpu".to_string();
+        }
+
+        let cycles = self.cycles;
+        if self.mode == Relative {
+            format!("{cycles} cycles, 1 more when the branch is taken and another when it jumps to another page")
+        } else if self.page_penalty {
+            format!("{cycles} cycles, 1 more when indexing crosses a page")
+        } else {
+            format!("{cycles} cycles")
+        }
+    }
+
+    const fn page(self) -> Self {
+        Self {
+            page_penalty: true,
+            ..self
+        }
+    }
+}
+
+impl Display for Opcode {
+    /// Formats the instruction like `LDA absolute,X`, where unofficial instructions start with a `*`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.class != OpcodeClass::Official {
+            write!(f, "*")?;
+        }
+        write!(f, "{} {}", self.mnemonic, self.mode)
+    }
+}
+
+const fn op(
+    mnemonic: &'static str,
+    mode: AddressingMode,
+    cycles: u8,
+    flags: StatusFlags,
+) -> Opcode {
+    Opcode {
+        mnemonic,
+        mode,
+        length: mode.instruction_length(),
+        cycles,
+        page_penalty: false,
+        class: OpcodeClass::Official,
+        flags,
+    }
+}
+
+const fn unofficial(
+    mnemonic: &'static str,
+    mode: AddressingMode,
+    cycles: u8,
+    flags: StatusFlags,
+) -> Opcode {
+    Opcode {
+        class: OpcodeClass::Unofficial,
+        ..op(mnemonic, mode, cycles, flags)
+    }
+}
+
+const fn unstable(
+    mnemonic: &'static str,
+    mode: AddressingMode,
+    cycles: u8,
+    flags: StatusFlags,
+) -> Opcode {
+    Opcode {
+        class: OpcodeClass::Unstable,
+        ..op(mnemonic, mode, cycles, flags)
+    }
+}
+
+const JAM: Opcode = Opcode {
+    class: OpcodeClass::Jam,
+    ..op("JAM", Implied, 2, NONE)
+};
+
+const NONE: StatusFlags = StatusFlags::empty();
+const C: StatusFlags = StatusFlags::CARRY;
+const D: StatusFlags = StatusFlags::DECIMAL;
+const I: StatusFlags = StatusFlags::INTERRUPT_DISABLE;
+const V: StatusFlags = StatusFlags::OVERFLOW;
+const NZ: StatusFlags = StatusFlags::NEGATIVE.union(StatusFlags::ZERO);
+const NZC: StatusFlags = NZ.union(C);
+const NVZ: StatusFlags = NZ.union(V);
+const NVZC: StatusFlags = NVZ.union(C);
+const ALL: StatusFlags = StatusFlags::all();
+
+/// Every opcode of the 6502, indexed by the opcode. The mnemonics of unofficial instructions are those `nestest.log` uses,
+/// other sources call some of them differently, like `ISC` for `ISB` and `SBX` for `AXS`.
+///
+/// ```
+/// use tudelft_nes_test::{AddressingMode, OpcodeClass, StatusFlags, OPCODES};
+///
+/// let lda = &OPCODES[0xBD];
+/// assert_eq!(lda.mnemonic, "LDA");
+/// assert_eq!(lda.mode, AddressingMode::AbsoluteX);
+/// assert_eq!(lda.length, 3);
+/// assert_eq!(lda.cycles, 4);
+/// assert!(lda.page_penalty);
+/// assert_eq!(lda.flags, StatusFlags::NEGATIVE | StatusFlags::ZERO);
+///
+/// let count = |class| OPCODES.iter().filter(|opcode| opcode.class == class).count();
+/// assert_eq!(count(OpcodeClass::Official), 151);
+/// assert_eq!(count(OpcodeClass::Jam), 12);
+/// ```
+#[rustfmt::skip]
+pub static OPCODES: [Opcode; 256] = [
+    // 0x
+    op("BRK", Implied, 7, I), op("ORA", IndirectX, 6, NZ),
+    JAM, unofficial("SLO", IndirectX, 8, NZC),
+    unofficial("NOP", ZeroPage, 3, NONE), op("ORA", ZeroPage, 3, NZ),
+    op("ASL", ZeroPage, 5, NZC), unofficial("SLO", ZeroPage, 5, NZC),
+    op("PHP", Implied, 3, NONE), op("ORA", Immediate, 2, NZ),
+    op("ASL", Accumulator, 2, NZC), unofficial("ANC", Immediate, 2, NZC),
+    unofficial("NOP", Absolute, 4, NONE), op("ORA", Absolute, 4, NZ),
+    op("ASL", Absolute, 6, NZC), unofficial("SLO", Absolute, 6, NZC),
+    // 1x
+    op("BPL", Relative, 2, NONE), op("ORA", IndirectY, 5, NZ).page(),
+    JAM, unofficial("SLO", IndirectY, 8, NZC),
+    unofficial("NOP", ZeroPageX, 4, NONE), op("ORA", ZeroPageX, 4, NZ),
+    op("ASL", ZeroPageX, 6, NZC), unofficial("SLO", ZeroPageX, 6, NZC),
+    op("CLC", Implied, 2, C), op("ORA", AbsoluteY, 4, NZ).page(),
+    unofficial("NOP", Implied, 2, NONE), unofficial("SLO", AbsoluteY, 7, NZC),
+    unofficial("NOP", AbsoluteX, 4, NONE).page(), op("ORA", AbsoluteX, 4, NZ).page(),
+    op("ASL", AbsoluteX, 7, NZC), unofficial("SLO", AbsoluteX, 7, NZC),
+    // 2x
+    op("JSR", Absolute, 6, NONE), op("AND", IndirectX, 6, NZ),
+    JAM, unofficial("RLA", IndirectX, 8, NZC),
+    op("BIT", ZeroPage, 3, NVZ), op("AND", ZeroPage, 3, NZ),
+    op("ROL", ZeroPage, 5, NZC), unofficial("RLA", ZeroPage, 5, NZC),
+    op("PLP", Implied, 4, ALL), op("AND", Immediate, 2, NZ),
+    op("ROL", Accumulator, 2, NZC), unofficial("ANC", Immediate, 2, NZC),
+    op("BIT", Absolute, 4, NVZ), op("AND", Absolute, 4, NZ),
+    op("ROL", Absolute, 6, NZC), unofficial("RLA", Absolute, 6, NZC),
+    // 3x
+    op("BMI", Relative, 2, NONE), op("AND", IndirectY, 5, NZ).page(),
+    JAM, unofficial("RLA", IndirectY, 8, NZC),
+    unofficial("NOP", ZeroPageX, 4, NONE), op("AND", ZeroPageX, 4, NZ),
+    op("ROL", ZeroPageX, 6, NZC), unofficial("RLA", ZeroPageX, 6, NZC),
+    op("SEC", Implied, 2, C), op("AND", AbsoluteY, 4, NZ).page(),
+    unofficial("NOP", Implied, 2, NONE), unofficial("RLA", AbsoluteY, 7, NZC),
+    unofficial("NOP", AbsoluteX, 4, NONE).page(), op("AND", AbsoluteX, 4, NZ).page(),
+    op("ROL", AbsoluteX, 7, NZC), unofficial("RLA", AbsoluteX, 7, NZC),
+    // 4x
+    op("RTI", Implied, 6, ALL), op("EOR", IndirectX, 6, NZ),
+    JAM, unofficial("SRE", IndirectX, 8, NZC),
+    unofficial("NOP", ZeroPage, 3, NONE), op("EOR", ZeroPage, 3, NZ),
+    op("LSR", ZeroPage, 5, NZC), unofficial("SRE", ZeroPage, 5, NZC),
+    op("PHA", Implied, 3, NONE), op("EOR", Immediate, 2, NZ),
+    op("LSR", Accumulator, 2, NZC), unofficial("ALR", Immediate, 2, NZC),
+    op("JMP", Absolute, 3, NONE), op("EOR", Absolute, 4, NZ),
+    op("LSR", Absolute, 6, NZC), unofficial("SRE", Absolute, 6, NZC),
+    // 5x
+    op("BVC", Relative, 2, NONE), op("EOR", IndirectY, 5, NZ).page(),
+    JAM, unofficial("SRE", IndirectY, 8, NZC),
+    unofficial("NOP", ZeroPageX, 4, NONE), op("EOR", ZeroPageX, 4, NZ),
+    op("LSR", ZeroPageX, 6, NZC), unofficial("SRE", ZeroPageX, 6, NZC),
+    op("CLI", Implied, 2, I), op("EOR", AbsoluteY, 4, NZ).page(),
+    unofficial("NOP", Implied, 2, NONE), unofficial("SRE", AbsoluteY, 7, NZC),
+    unofficial("NOP", AbsoluteX, 4, NONE).page(), op("EOR", AbsoluteX, 4, NZ).page(),
+    op("LSR", AbsoluteX, 7, NZC), unofficial("SRE", AbsoluteX, 7, NZC),
+    // 6x
+    op("RTS", Implied, 6, NONE), op("ADC", IndirectX, 6, NVZC),
+    JAM, unofficial("RRA", IndirectX, 8, NVZC),
+    unofficial("NOP", ZeroPage, 3, NONE), op("ADC", ZeroPage, 3, NVZC),
+    op("ROR", ZeroPage, 5, NZC), unofficial("RRA", ZeroPage, 5, NVZC),
+    op("PLA", Implied, 4, NZ), op("ADC", Immediate, 2, NVZC),
+    op("ROR", Accumulator, 2, NZC), unofficial("ARR", Immediate, 2, NVZC),
+    op("JMP", Indirect, 5, NONE), op("ADC", Absolute, 4, NVZC),
+    op("ROR", Absolute, 6, NZC), unofficial("RRA", Absolute, 6, NVZC),
+    // 7x
+    op("BVS", Relative, 2, NONE), op("ADC", IndirectY, 5, NVZC).page(),
+    JAM, unofficial("RRA", IndirectY, 8, NVZC),
+    unofficial("NOP", ZeroPageX, 4, NONE), op("ADC", ZeroPageX, 4, NVZC),
+    op("ROR", ZeroPageX, 6, NZC), unofficial("RRA", ZeroPageX, 6, NVZC),
+    op("SEI", Implied, 2, I), op("ADC", AbsoluteY, 4, NVZC).page(),
+    unofficial("NOP", Implied, 2, NONE), unofficial("RRA", AbsoluteY, 7, NVZC),
+    unofficial("NOP", AbsoluteX, 4, NONE).page(), op("ADC", AbsoluteX, 4, NVZC).page(),
+    op("ROR", AbsoluteX, 7, NZC), unofficial("RRA", AbsoluteX, 7, NVZC),
+    // 8x
+    unofficial("NOP", Immediate, 2, NONE), op("STA", IndirectX, 6, NONE),
+    unofficial("NOP", Immediate, 2, NONE), unofficial("SAX", IndirectX, 6, NONE),
+    op("STY", ZeroPage, 3, NONE), op("STA", ZeroPage, 3, NONE),
+    op("STX", ZeroPage, 3, NONE), unofficial("SAX", ZeroPage, 3, NONE),
+    op("DEY", Implied, 2, NZ), unofficial("NOP", Immediate, 2, NONE),
+    op("TXA", Implied, 2, NZ), unstable("XAA", Immediate, 2, NZ),
+    op("STY", Absolute, 4, NONE), op("STA", Absolute, 4, NONE),
+    op("STX", Absolute, 4, NONE), unofficial("SAX", Absolute, 4, NONE),
+    // 9x
+    op("BCC", Relative, 2, NONE), op("STA", IndirectY, 6, NONE),
+    JAM, unstable("SHA", IndirectY, 6, NONE),
+    op("STY", ZeroPageX, 4, NONE), op("STA", ZeroPageX, 4, NONE),
+    op("STX", ZeroPageY, 4, NONE), unofficial("SAX", ZeroPageY, 4, NONE),
+    op("TYA", Implied, 2, NZ), op("STA", AbsoluteY, 5, NONE),
+    op("TXS", Implied, 2, NONE), unstable("TAS", AbsoluteY, 5, NONE),
+    unstable("SHY", AbsoluteX, 5, NONE), op("STA", AbsoluteX, 5, NONE),
+    unstable("SHX", AbsoluteY, 5, NONE), unstable("SHA", AbsoluteY, 5, NONE),
+    // Ax
+    op("LDY", Immediate, 2, NZ), op("LDA", IndirectX, 6, NZ),
+    op("LDX", Immediate, 2, NZ), unofficial("LAX", IndirectX, 6, NZ),
+    op("LDY", ZeroPage, 3, NZ), op("LDA", ZeroPage, 3, NZ),
+    op("LDX", ZeroPage, 3, NZ), unofficial("LAX", ZeroPage, 3, NZ),
+    op("TAY", Implied, 2, NZ), op("LDA", Immediate, 2, NZ),
+    op("TAX", Implied, 2, NZ), unstable("LXA", Immediate, 2, NZ),
+    op("LDY", Absolute, 4, NZ), op("LDA", Absolute, 4, NZ),
+    op("LDX", Absolute, 4, NZ), unofficial("LAX", Absolute, 4, NZ),
+    // Bx
+    op("BCS", Relative, 2, NONE), op("LDA", IndirectY, 5, NZ).page(),
+    JAM, unofficial("LAX", IndirectY, 5, NZ).page(),
+    op("LDY", ZeroPageX, 4, NZ), op("LDA", ZeroPageX, 4, NZ),
+    op("LDX", ZeroPageY, 4, NZ), unofficial("LAX", ZeroPageY, 4, NZ),
+    op("CLV", Implied, 2, V), op("LDA", AbsoluteY, 4, NZ).page(),
+    op("TSX", Implied, 2, NZ), unofficial("LAS", AbsoluteY, 4, NZ).page(),
+    op("LDY", AbsoluteX, 4, NZ).page(), op("LDA", AbsoluteX, 4, NZ).page(),
+    op("LDX", AbsoluteY, 4, NZ).page(), unofficial("LAX", AbsoluteY, 4, NZ).page(),
+    // Cx
+    op("CPY", Immediate, 2, NZC), op("CMP", IndirectX, 6, NZC),
+    unofficial("NOP", Immediate, 2, NONE), unofficial("DCP", IndirectX, 8, NZC),
+    op("CPY", ZeroPage, 3, NZC), op("CMP", ZeroPage, 3, NZC),
+    op("DEC", ZeroPage, 5, NZ), unofficial("DCP", ZeroPage, 5, NZC),
+    op("INY", Implied, 2, NZ), op("CMP", Immediate, 2, NZC),
+    op("DEX", Implied, 2, NZ), unofficial("AXS", Immediate, 2, NZC),
+    op("CPY", Absolute, 4, NZC), op("CMP", Absolute, 4, NZC),
+    op("DEC", Absolute, 6, NZ), unofficial("DCP", Absolute, 6, NZC),
+    // Dx
+    op("BNE", Relative, 2, NONE), op("CMP", IndirectY, 5, NZC).page(),
+    JAM, unofficial("DCP", IndirectY, 8, NZC),
+    unofficial("NOP", ZeroPageX, 4, NONE), op("CMP", ZeroPageX, 4, NZC),
+    op("DEC", ZeroPageX, 6, NZ), unofficial("DCP", ZeroPageX, 6, NZC),
+    op("CLD", Implied, 2, D), op("CMP", AbsoluteY, 4, NZC).page(),
+    unofficial("NOP", Implied, 2, NONE), unofficial("DCP", AbsoluteY, 7, NZC),
+    unofficial("NOP", AbsoluteX, 4, NONE).page(), op("CMP", AbsoluteX, 4, NZC).page(),
+    op("DEC", AbsoluteX, 7, NZ), unofficial("DCP", AbsoluteX, 7, NZC),
+    // Ex
+    op("CPX", Immediate, 2, NZC), op("SBC", IndirectX, 6, NVZC),
+    unofficial("NOP", Immediate, 2, NONE), unofficial("ISB", IndirectX, 8, NVZC),
+    op("CPX", ZeroPage, 3, NZC), op("SBC", ZeroPage, 3, NVZC),
+    op("INC", ZeroPage, 5, NZ), unofficial("ISB", ZeroPage, 5, NVZC),
+    op("INX", Implied, 2, NZ), op("SBC", Immediate, 2, NVZC),
+    op("NOP", Implied, 2, NONE), unofficial("SBC", Immediate, 2, NVZC),
+    op("CPX", Absolute, 4, NZC), op("SBC", Absolute, 4, NVZC),
+    op("INC", Absolute, 6, NZ), unofficial("ISB", Absolute, 6, NVZC),
+    // Fx
+    op("BEQ", Relative, 2, NONE), op("SBC", IndirectY, 5, NVZC).page(),
+    JAM, unofficial("ISB", IndirectY, 8, NVZC),
+    unofficial("NOP", ZeroPageX, 4, NONE), op("SBC", ZeroPageX, 4, NVZC),
+    op("INC", ZeroPageX, 6, NZ), unofficial("ISB", ZeroPageX, 6, NVZC),
+    op("SED", Implied, 2, D), op("SBC", AbsoluteY, 4, NVZC).page(),
+    unofficial("NOP", Implied, 2, NONE), unofficial("ISB", AbsoluteY, 7, NVZC),
+    unofficial("NOP", AbsoluteX, 4, NONE).page(), op("SBC", AbsoluteX, 4, NVZC).page(),
+    op("INC", AbsoluteX, 7, NZ), unofficial("ISB", AbsoluteX, 7, NVZC),
+];
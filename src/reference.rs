@@ -0,0 +1,950 @@
+use crate::AddressingMode::*;
+use crate::{
+    CpuInterrupts, CpuMemoryWrite, CpuRegisterWrite, CpuRegisters, CpuReset, CpuStep, OpcodeClass,
+    TestableCpu, OPCODES,
+};
+use std::error::Error;
+use thiserror::Error;
+use tudelft_nes_ppu::{Cpu, Ppu};
+
+/// The amount of cycles in a frame of the NTSC NES
+const FRAME_CYCLES: u64 = 29781;
+
+const CARRY: u8 = 0x01;
+const ZERO: u8 = 0x02;
+const INTERRUPT_DISABLE: u8 = 0x04;
+const DECIMAL: u8 = 0x08;
+const BREAK: u8 = 0x10;
+const UNUSED: u8 = 0x20;
+const OVERFLOW: u8 = 0x40;
+const NEGATIVE: u8 = 0x80;
+
+/// Returned by [`ReferenceCpu`] when it executes one of the opcodes that halt a 6502
+#[derive(Debug, Error)]
+#[error("the reference cpu executed opcode ${opcode:02X} at ${pc:04X}, which halts the cpu")]
+struct Jammed {
+    pc: u16,
+    opcode: u8,
+}
+
+/// A 2A03 CPU that is known to pass every test of this crate, which you can compare your own CPU to.
+/// It executes all 256 opcodes, including the unofficial and unstable ones, and takes the same amount of cycles
+/// as the real CPU does for every instruction, including the extra cycles for crossing a page and taking a branch.
+///
+/// It executes a whole instruction in the first cycle of it, and waits during the remaining cycles.
+/// It supports NROM and MMC1 cartridges. The PPU isn't connected: its registers read as 0, except for $2002,
+/// which reports the start of vertical blank once every frame, so roms that wait for it keep going.
+///
+/// This is only available with the `reference` feature.
+pub struct ReferenceCpu {
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    sp: u8,
+    pc: u16,
+    bus: Bus,
+    /// The amount of cycles the current instruction still takes
+    wait: u64,
+    nmi: bool,
+    irq: bool,
+}
+
+impl ReferenceCpu {
+    /// Loads `rom`, which is a rom file in iNES format, and starts at its reset vector
+    pub fn new(rom: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut cpu = Self {
+            a: 0,
+            x: 0,
+            y: 0,
+            p: INTERRUPT_DISABLE | UNUSED,
+            sp: 0xFD,
+            pc: 0,
+            bus: Bus {
+                ram: [0; 0x800],
+                cartridge: Cartridge::new(rom)?,
+                cycles: 7,
+                frame: 0,
+                vblank: false,
+            },
+            wait: 0,
+            nmi: false,
+            irq: false,
+        };
+        cpu.pc = cpu.read_u16(0xFFFC);
+        Ok(cpu)
+    }
+
+    /// Handles a pending interrupt or executes an instruction, and returns how many cycles that took
+    fn execute(&mut self) -> Result<u64, Box<dyn Error>> {
+        if self.nmi {
+            self.nmi = false;
+            return Ok(self.interrupt(0xFFFA));
+        }
+        if self.irq && self.p & INTERRUPT_DISABLE == 0 {
+            return Ok(self.interrupt(0xFFFE));
+        }
+
+        let pc = self.pc;
+        let opcode = self.bus.read(pc);
+        let cycles = self.instruction(opcode);
+        self.bus.cycles += cycles;
+
+        if OPCODES[usize::from(opcode)].class == OpcodeClass::Jam {
+            Err(Jammed { pc, opcode })?
+        }
+        Ok(cycles)
+    }
+
+    fn interrupt(&mut self, vector: u16) -> u64 {
+        self.push_u16(self.pc);
+        self.push((self.p & !BREAK) | UNUSED);
+        self.p |= INTERRUPT_DISABLE;
+        self.pc = self.read_u16(vector);
+        self.bus.cycles += 7;
+        7
+    }
+
+    /// Executes the instruction with `opcode` at the program counter, and returns how many cycles it took
+    fn instruction(&mut self, opcode: u8) -> u64 {
+        let timing = OPCODES[usize::from(opcode)];
+        let operand = self.pc.wrapping_add(1);
+        self.pc = self.pc.wrapping_add(timing.length);
+        let mut cycles = u64::from(timing.cycles);
+
+        // the effective address, and whether adding an index register to it crossed a page
+        let (address, crossed) = match timing.mode {
+            Implied | Accumulator => (0, false),
+            Immediate | Relative => (operand, false),
+            ZeroPage => (u16::from(self.bus.read(operand)), false),
+            ZeroPageX => (
+                u16::from(self.bus.read(operand).wrapping_add(self.x)),
+                false,
+            ),
+            ZeroPageY => (
+                u16::from(self.bus.read(operand).wrapping_add(self.y)),
+                false,
+            ),
+            Absolute => (self.read_u16(operand), false),
+            AbsoluteX => indexed(self.read_u16(operand), self.x),
+            AbsoluteY => indexed(self.read_u16(operand), self.y),
+            Indirect => {
+                // the high byte is read from the same page as the low byte
+                let pointer = self.read_u16(operand);
+                let low = self.bus.read(pointer);
+                let high = self
+                    .bus
+                    .read((pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF));
+                (u16::from_le_bytes([low, high]), false)
+            }
+            IndirectX => {
+                let pointer = self.bus.read(operand).wrapping_add(self.x);
+                (self.read_zero_page_u16(pointer), false)
+            }
+            IndirectY => {
+                let pointer = self.bus.read(operand);
+                indexed(self.read_zero_page_u16(pointer), self.y)
+            }
+        };
+        if timing.page_penalty && crossed {
+            cycles += 1;
+        }
+
+        match opcode {
+            // loads
+            0xA9 | 0xA5 | 0xB5 | 0xAD | 0xBD | 0xB9 | 0xA1 | 0xB1 => {
+                self.a = self.bus.read(address);
+                self.set_zero_negative(self.a);
+            }
+            0xA2 | 0xA6 | 0xB6 | 0xAE | 0xBE => {
+                self.x = self.bus.read(address);
+                self.set_zero_negative(self.x);
+            }
+            0xA0 | 0xA4 | 0xB4 | 0xAC | 0xBC => {
+                self.y = self.bus.read(address);
+                self.set_zero_negative(self.y);
+            }
+            // LAX
+            0xA7 | 0xB7 | 0xAF | 0xBF | 0xA3 | 0xB3 => {
+                self.a = self.bus.read(address);
+                self.x = self.a;
+                self.set_zero_negative(self.a);
+            }
+            // stores
+            0x85 | 0x95 | 0x8D | 0x9D | 0x99 | 0x81 | 0x91 => self.bus.write(address, self.a),
+            0x86 | 0x96 | 0x8E => self.bus.write(address, self.x),
+            0x84 | 0x94 | 0x8C => self.bus.write(address, self.y),
+            // SAX
+            0x87 | 0x97 | 0x8F | 0x83 => self.bus.write(address, self.a & self.x),
+            // logic and arithmetic
+            0x09 | 0x05 | 0x15 | 0x0D | 0x1D | 0x19 | 0x01 | 0x11 => {
+                self.a |= self.bus.read(address);
+                self.set_zero_negative(self.a);
+            }
+            0x29 | 0x25 | 0x35 | 0x2D | 0x3D | 0x39 | 0x21 | 0x31 => {
+                self.a &= self.bus.read(address);
+                self.set_zero_negative(self.a);
+            }
+            0x49 | 0x45 | 0x55 | 0x4D | 0x5D | 0x59 | 0x41 | 0x51 => {
+                self.a ^= self.bus.read(address);
+                self.set_zero_negative(self.a);
+            }
+            0x69 | 0x65 | 0x75 | 0x6D | 0x7D | 0x79 | 0x61 | 0x71 => {
+                let value = self.bus.read(address);
+                self.add(value);
+            }
+            0xE9 | 0xEB | 0xE5 | 0xF5 | 0xED | 0xFD | 0xF9 | 0xE1 | 0xF1 => {
+                let value = self.bus.read(address);
+                self.add(!value);
+            }
+            0xC9 | 0xC5 | 0xD5 | 0xCD | 0xDD | 0xD9 | 0xC1 | 0xD1 => {
+                let value = self.bus.read(address);
+                self.compare(self.a, value);
+            }
+            0xE0 | 0xE4 | 0xEC => {
+                let value = self.bus.read(address);
+                self.compare(self.x, value);
+            }
+            0xC0 | 0xC4 | 0xCC => {
+                let value = self.bus.read(address);
+                self.compare(self.y, value);
+            }
+            0x24 | 0x2C => {
+                let value = self.bus.read(address);
+                self.set_flag(ZERO, self.a & value == 0);
+                self.set_flag(OVERFLOW, value & 0x40 != 0);
+                self.set_flag(NEGATIVE, value & 0x80 != 0);
+            }
+            // shifts of the accumulator
+            0x0A => self.a = self.shift_left(self.a),
+            0x4A => self.a = self.shift_right(self.a),
+            0x2A => self.a = self.rotate_left(self.a),
+            0x6A => self.a = self.rotate_right(self.a),
+            // read-modify-write
+            0x06 | 0x16 | 0x0E | 0x1E => {
+                self.modify(address, Self::shift_left);
+            }
+            0x46 | 0x56 | 0x4E | 0x5E => {
+                self.modify(address, Self::shift_right);
+            }
+            0x26 | 0x36 | 0x2E | 0x3E => {
+                self.modify(address, Self::rotate_left);
+            }
+            0x66 | 0x76 | 0x6E | 0x7E => {
+                self.modify(address, Self::rotate_right);
+            }
+            0xE6 | 0xF6 | 0xEE | 0xFE => {
+                self.modify(address, Self::increment);
+            }
+            0xC6 | 0xD6 | 0xCE | 0xDE => {
+                self.modify(address, Self::decrement);
+            }
+            // SLO, RLA, SRE, RRA, DCP and ISB
+            0x07 | 0x17 | 0x0F | 0x1F | 0x1B | 0x03 | 0x13 => {
+                self.a |= self.modify(address, Self::shift_left);
+                self.set_zero_negative(self.a);
+            }
+            0x27 | 0x37 | 0x2F | 0x3F | 0x3B | 0x23 | 0x33 => {
+                self.a &= self.modify(address, Self::rotate_left);
+                self.set_zero_negative(self.a);
+            }
+            0x47 | 0x57 | 0x4F | 0x5F | 0x5B | 0x43 | 0x53 => {
+                self.a ^= self.modify(address, Self::shift_right);
+                self.set_zero_negative(self.a);
+            }
+            0x67 | 0x77 | 0x6F | 0x7F | 0x7B | 0x63 | 0x73 => {
+                let value = self.modify(address, Self::rotate_right);
+                self.add(value);
+            }
+            0xC7 | 0xD7 | 0xCF | 0xDF | 0xDB | 0xC3 | 0xD3 => {
+                let value = self.modify(address, Self::decrement);
+                self.compare(self.a, value);
+            }
+            0xE7 | 0xF7 | 0xEF | 0xFF | 0xFB | 0xE3 | 0xF3 => {
+                let value = self.modify(address, Self::increment);
+                self.add(!value);
+            }
+            // ANC
+            0x0B | 0x2B => {
+                self.a &= self.bus.read(address);
+                self.set_zero_negative(self.a);
+                self.set_flag(CARRY, self.a & 0x80 != 0);
+            }
+            // ALR
+            0x4B => {
+                self.a &= self.bus.read(address);
+                self.a = self.shift_right(self.a);
+            }
+            // ARR
+            0x6B => {
+                self.a &= self.bus.read(address);
+                self.a = (self.a >> 1) | ((self.p & CARRY) << 7);
+                self.set_zero_negative(self.a);
+                self.set_flag(CARRY, self.a & 0x40 != 0);
+                self.set_flag(OVERFLOW, ((self.a >> 6) ^ (self.a >> 5)) & 1 != 0);
+            }
+            // AXS
+            0xCB => {
+                let value = self.bus.read(address);
+                let and = self.a & self.x;
+                self.set_flag(CARRY, and >= value);
+                self.x = and.wrapping_sub(value);
+                self.set_zero_negative(self.x);
+            }
+            // XAA and LXA, using the magic constants most emulators use
+            0x8B => {
+                self.a = (self.a | 0xEE) & self.x & self.bus.read(address);
+                self.set_zero_negative(self.a);
+            }
+            0xAB => {
+                self.a = (self.a | 0xFF) & self.bus.read(address);
+                self.x = self.a;
+                self.set_zero_negative(self.a);
+            }
+            // LAS
+            0xBB => {
+                let value = self.bus.read(address) & self.sp;
+                self.a = value;
+                self.x = value;
+                self.sp = value;
+                self.set_zero_negative(value);
+            }
+            // SHA, SHX, SHY and TAS, which store a value and'ed with the high byte of the address plus one
+            0x93 | 0x9F => self.store_high(address, crossed, self.a & self.x),
+            0x9E => self.store_high(address, crossed, self.x),
+            0x9C => self.store_high(address, crossed, self.y),
+            0x9B => {
+                self.sp = self.a & self.x;
+                self.store_high(address, crossed, self.sp);
+            }
+            // transfers and increments
+            0xAA => {
+                self.x = self.a;
+                self.set_zero_negative(self.x);
+            }
+            0xA8 => {
+                self.y = self.a;
+                self.set_zero_negative(self.y);
+            }
+            0x8A => {
+                self.a = self.x;
+                self.set_zero_negative(self.a);
+            }
+            0x98 => {
+                self.a = self.y;
+                self.set_zero_negative(self.a);
+            }
+            0xBA => {
+                self.x = self.sp;
+                self.set_zero_negative(self.x);
+            }
+            0x9A => self.sp = self.x,
+            0xE8 => {
+                self.x = self.x.wrapping_add(1);
+                self.set_zero_negative(self.x);
+            }
+            0xCA => {
+                self.x = self.x.wrapping_sub(1);
+                self.set_zero_negative(self.x);
+            }
+            0xC8 => {
+                self.y = self.y.wrapping_add(1);
+                self.set_zero_negative(self.y);
+            }
+            0x88 => {
+                self.y = self.y.wrapping_sub(1);
+                self.set_zero_negative(self.y);
+            }
+            // flags
+            0x18 => self.p &= !CARRY,
+            0x38 => self.p |= CARRY,
+            0x58 => self.p &= !INTERRUPT_DISABLE,
+            0x78 => self.p |= INTERRUPT_DISABLE,
+            0xB8 => self.p &= !OVERFLOW,
+            0xD8 => self.p &= !DECIMAL,
+            0xF8 => self.p |= DECIMAL,
+            // stack
+            0x48 => self.push(self.a),
+            0x08 => self.push(self.p | BREAK | UNUSED),
+            0x68 => {
+                self.a = self.pull();
+                self.set_zero_negative(self.a);
+            }
+            0x28 => self.p = (self.pull() & !BREAK) | UNUSED,
+            // jumps, subroutines and interrupts
+            0x4C | 0x6C => self.pc = address,
+            0x20 => {
+                self.push_u16(self.pc.wrapping_sub(1));
+                self.pc = address;
+            }
+            0x60 => self.pc = self.pull_u16().wrapping_add(1),
+            0x40 => {
+                self.p = (self.pull() & !BREAK) | UNUSED;
+                self.pc = self.pull_u16();
+            }
+            0x00 => {
+                self.push_u16(self.pc.wrapping_add(1));
+                self.push(self.p | BREAK | UNUSED);
+                self.p |= INTERRUPT_DISABLE;
+                self.pc = self.read_u16(0xFFFE);
+            }
+            // branches, where bits 6 and 7 select the flag and bit 5 the value it should have
+            0x10 | 0x30 | 0x50 | 0x70 | 0x90 | 0xB0 | 0xD0 | 0xF0 => {
+                let flag = [NEGATIVE, OVERFLOW, CARRY, ZERO][usize::from(opcode >> 6)];
+                if (self.p & flag != 0) == (opcode & 0x20 != 0) {
+                    let offset = self.bus.read(address) as i8;
+                    let target = self.pc.wrapping_add(offset as u16);
+                    cycles += 1 + u64::from(target & 0xFF00 != self.pc & 0xFF00);
+                    self.pc = target;
+                }
+            }
+            // the cpu halts on the opcode, and keeps executing it
+            _ if timing.class == OpcodeClass::Jam => self.pc = self.pc.wrapping_sub(1),
+            // the remaining opcodes are NOPs, which still read their operand
+            _ => {
+                if timing.mode != Implied {
+                    self.bus.read(address);
+                }
+            }
+        }
+
+        cycles
+    }
+
+    fn read_u16(&mut self, address: u16) -> u16 {
+        u16::from_le_bytes([
+            self.bus.read(address),
+            self.bus.read(address.wrapping_add(1)),
+        ])
+    }
+
+    /// Reads an address from the zero page, where the high byte wraps around to $00
+    fn read_zero_page_u16(&mut self, address: u8) -> u16 {
+        let low = self.bus.read(u16::from(address));
+        let high = self.bus.read(u16::from(address.wrapping_add(1)));
+        u16::from_le_bytes([low, high])
+    }
+
+    fn push(&mut self, value: u8) {
+        self.bus.write(0x0100 | u16::from(self.sp), value);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    fn push_u16(&mut self, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.push(high);
+        self.push(low);
+    }
+
+    fn pull(&mut self) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        self.bus.read(0x0100 | u16::from(self.sp))
+    }
+
+    fn pull_u16(&mut self) -> u16 {
+        let low = self.pull();
+        let high = self.pull();
+        u16::from_le_bytes([low, high])
+    }
+
+    fn set_flag(&mut self, flag: u8, set: bool) {
+        if set {
+            self.p |= flag;
+        } else {
+            self.p &= !flag;
+        }
+    }
+
+    fn set_zero_negative(&mut self, value: u8) {
+        self.set_flag(ZERO, value == 0);
+        self.set_flag(NEGATIVE, value & 0x80 != 0);
+    }
+
+    /// Adds `value` and the carry to the accumulator. Subtracting is adding the complement of the value.
+    fn add(&mut self, value: u8) {
+        let sum = u16::from(self.a) + u16::from(value) + u16::from(self.p & CARRY);
+        let result = sum as u8;
+        self.set_flag(CARRY, sum > 0xFF);
+        self.set_flag(OVERFLOW, (self.a ^ result) & (value ^ result) & 0x80 != 0);
+        self.a = result;
+        self.set_zero_negative(result);
+    }
+
+    fn compare(&mut self, register: u8, value: u8) {
+        self.set_flag(CARRY, register >= value);
+        self.set_zero_negative(register.wrapping_sub(value));
+    }
+
+    /// Reads the value at `address`, changes it with `operation` and writes it back
+    fn modify(&mut self, address: u16, operation: fn(&mut Self, u8) -> u8) -> u8 {
+        let value = self.bus.read(address);
+        let result = operation(self, value);
+        self.bus.write(address, result);
+        result
+    }
+
+    /// Stores `value` and'ed with the high byte of `address` plus one. When indexing crossed a page,
+    /// that value replaces the high byte of the address as well.
+    fn store_high(&mut self, address: u16, crossed: bool, value: u8) {
+        let [low, high] = address.to_le_bytes();
+        let value = value & high.wrapping_add(1);
+        let address = if crossed {
+            u16::from_le_bytes([low, value])
+        } else {
+            address
+        };
+        self.bus.write(address, value);
+    }
+
+    fn shift_left(&mut self, value: u8) -> u8 {
+        self.set_flag(CARRY, value & 0x80 != 0);
+        self.set_zero_negative(value << 1);
+        value << 1
+    }
+
+    fn shift_right(&mut self, value: u8) -> u8 {
+        self.set_flag(CARRY, value & 0x01 != 0);
+        self.set_zero_negative(value >> 1);
+        value >> 1
+    }
+
+    fn rotate_left(&mut self, value: u8) -> u8 {
+        let result = (value << 1) | (self.p & CARRY);
+        self.set_flag(CARRY, value & 0x80 != 0);
+        self.set_zero_negative(result);
+        result
+    }
+
+    fn rotate_right(&mut self, value: u8) -> u8 {
+        let result = (value >> 1) | ((self.p & CARRY) << 7);
+        self.set_flag(CARRY, value & 0x01 != 0);
+        self.set_zero_negative(result);
+        result
+    }
+
+    fn increment(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.set_zero_negative(result);
+        result
+    }
+
+    fn decrement(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.set_zero_negative(result);
+        result
+    }
+}
+
+/// Adds `index` to `base`, and returns whether that crossed a page
+fn indexed(base: u16, index: u8) -> (u16, bool) {
+    let address = base.wrapping_add(u16::from(index));
+    (address, address & 0xFF00 != base & 0xFF00)
+}
+
+impl Cpu for ReferenceCpu {
+    fn tick(&mut self, _ppu: &mut Ppu) -> Result<(), Box<dyn Error>> {
+        if self.wait > 0 {
+            self.wait -= 1;
+            return Ok(());
+        }
+
+        self.wait = self.execute()? - 1;
+        Ok(())
+    }
+
+    fn ppu_read_chr_rom(&self, offset: u16) -> u8 {
+        self.bus
+            .cartridge
+            .chr
+            .get(usize::from(offset))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn non_maskable_interrupt(&mut self) {
+        self.nmi = true;
+    }
+}
+
+impl TestableCpu for ReferenceCpu {
+    fn get_cpu(rom: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Self::new(rom)
+    }
+
+    fn set_program_counter(&mut self, value: u16) {
+        self.set_pc(value);
+    }
+
+    fn memory_read(&self, address: u16) -> u8 {
+        self.bus.peek(address)
+    }
+
+    fn registers(&self) -> Option<&dyn CpuRegisters> {
+        Some(self)
+    }
+
+    fn resettable(&mut self) -> Option<&mut dyn CpuReset> {
+        Some(self)
+    }
+
+    fn writable(&mut self) -> Option<&mut dyn CpuMemoryWrite> {
+        Some(self)
+    }
+
+    fn registers_mut(&mut self) -> Option<&mut dyn CpuRegisterWrite> {
+        Some(self)
+    }
+
+    fn interruptible(&mut self) -> Option<&mut dyn CpuInterrupts> {
+        Some(self)
+    }
+
+    fn steppable(&mut self) -> Option<&mut dyn CpuStep> {
+        Some(self)
+    }
+}
+
+impl CpuRegisters for ReferenceCpu {
+    fn accumulator(&self) -> u8 {
+        self.a
+    }
+
+    fn x_register(&self) -> u8 {
+        self.x
+    }
+
+    fn y_register(&self) -> u8 {
+        self.y
+    }
+
+    fn status(&self) -> u8 {
+        self.p
+    }
+
+    fn stack_pointer(&self) -> u8 {
+        self.sp
+    }
+
+    fn program_counter(&self) -> u16 {
+        self.pc
+    }
+
+    /// The amount of cycles the cpu ticked. The whole instruction is executed in its first tick,
+    /// but its cycles are only counted as the cpu waits for them, like a cpu that ticks once per cycle counts them.
+    fn cycles(&self) -> Option<u64> {
+        Some(self.bus.cycles - self.wait)
+    }
+}
+
+impl CpuReset for ReferenceCpu {
+    fn reset(&mut self) {
+        self.sp = self.sp.wrapping_sub(3);
+        self.p |= INTERRUPT_DISABLE;
+        self.pc = self.read_u16(0xFFFC);
+        self.bus.cycles += 7;
+        self.wait = 0;
+    }
+}
+
+impl CpuMemoryWrite for ReferenceCpu {
+    fn memory_write(&mut self, address: u16, value: u8) {
+        self.bus.write(address, value);
+    }
+}
+
+impl CpuRegisterWrite for ReferenceCpu {
+    fn set_accumulator(&mut self, value: u8) {
+        self.a = value;
+    }
+
+    fn set_x_register(&mut self, value: u8) {
+        self.x = value;
+    }
+
+    fn set_y_register(&mut self, value: u8) {
+        self.y = value;
+    }
+
+    fn set_status(&mut self, value: u8) {
+        self.p = (value & !BREAK) | UNUSED;
+    }
+
+    fn set_stack_pointer(&mut self, value: u8) {
+        self.sp = value;
+    }
+
+    fn set_pc(&mut self, value: u16) {
+        self.pc = value;
+        self.wait = 0;
+    }
+}
+
+impl CpuInterrupts for ReferenceCpu {
+    fn nmi(&mut self) {
+        self.nmi = true;
+    }
+
+    fn set_irq(&mut self, active: bool) {
+        self.irq = active;
+    }
+}
+
+impl CpuStep for ReferenceCpu {
+    fn step(&mut self) -> Result<(), Box<dyn Error>> {
+        self.wait = 0;
+        self.execute()?;
+        Ok(())
+    }
+}
+
+/// The memory the cpu sees: 2 KiB of RAM, the registers of the PPU and APU, and the cartridge
+struct Bus {
+    ram: [u8; 0x800],
+    cartridge: Cartridge,
+    cycles: u64,
+    /// The frame in which $2002 was last read
+    frame: u64,
+    vblank: bool,
+}
+
+impl Bus {
+    /// Reads `address` without the side effects reading $2002 has
+    fn peek(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => self.ram[usize::from(address) & 0x7FF],
+            0x2000..=0x3FFF | 0x4020..=0x5FFF => 0,
+            0x4000..=0x401F => 0xFF,
+            0x6000..=0x7FFF => self.cartridge.prg_ram[usize::from(address) - 0x6000],
+            0x8000..=0xFFFF => self.cartridge.read_prg(address),
+        }
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        if (0x2000..=0x3FFF).contains(&address) && address & 7 == 2 {
+            // vertical blank starts once every frame, and reading $2002 clears it
+            let frame = self.cycles / FRAME_CYCLES;
+            if frame != self.frame {
+                self.frame = frame;
+                self.vblank = true;
+            }
+            return if std::mem::take(&mut self.vblank) {
+                0x80
+            } else {
+                0
+            };
+        }
+
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram[usize::from(address) & 0x7FF] = value,
+            0x6000..=0x7FFF => self.cartridge.prg_ram[usize::from(address) - 0x6000] = value,
+            0x8000..=0xFFFF => self.cartridge.write_mapper(address, value),
+            _ => {}
+        }
+    }
+}
+
+/// An NROM or MMC1 cartridge
+struct Cartridge {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    prg_ram: [u8; 0x2000],
+    mmc1: Option<Mmc1>,
+}
+
+/// The registers of the MMC1 mapper that select the banks of the PRG ROM
+#[derive(Default)]
+struct Mmc1 {
+    shift: u8,
+    writes: u8,
+    control: u8,
+    prg_bank: u8,
+}
+
+impl Cartridge {
+    fn new(rom: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let header = rom
+            .get(0..16)
+            .ok_or("the rom is too short to hold an iNES header")?;
+        if header[0..4] != *b"NES\x1A" {
+            return Err("the rom isn't in iNES format".into());
+        }
+        // the prg rom holds the vectors, and read_prg needs a bank to read from
+        if header[4] == 0 {
+            return Err("the rom doesn't have any PRG ROM".into());
+        }
+
+        let trainer = if header[6] & 0x04 != 0 { 512 } else { 0 };
+        let prg_start = 16 + trainer;
+        let prg_end = prg_start + usize::from(header[4]) * 0x4000;
+        let chr_end = prg_end + usize::from(header[5]) * 0x2000;
+        let mmc1 = match (header[6] >> 4) | (header[7] & 0xF0) {
+            0 => None,
+            1 => Some(Mmc1 {
+                control: 0x0C,
+                ..Mmc1::default()
+            }),
+            mapper => {
+                return Err(format!("the reference cpu doesn't support mapper {mapper}").into())
+            }
+        };
+
+        Ok(Self {
+            prg: rom
+                .get(prg_start..prg_end)
+                .ok_or("the rom is truncated")?
+                .to_vec(),
+            chr: rom
+                .get(prg_end..chr_end)
+                .ok_or("the rom is truncated")?
+                .to_vec(),
+            prg_ram: [0; 0x2000],
+            mmc1,
+        })
+    }
+
+    fn read_prg(&self, address: u16) -> u8 {
+        let banks = self.prg.len() / 0x4000;
+        let last = banks - 1;
+        let upper = address >= 0xC000;
+        let bank = match &self.mmc1 {
+            None => usize::from(upper) * last,
+            Some(mmc1) => {
+                let selected = usize::from(mmc1.prg_bank & 0x0F);
+                match (mmc1.control >> 2) & 3 {
+                    // 32 KiB mode
+                    0 | 1 => (selected & !1) + usize::from(upper),
+                    // the first bank is fixed at $8000
+                    2 if upper => selected,
+                    2 => 0,
+                    // the last bank is fixed at $C000
+                    _ if upper => last,
+                    _ => selected,
+                }
+            }
+        };
+
+        self.prg[(bank % banks) * 0x4000 + (usize::from(address) & 0x3FFF)]
+    }
+
+    /// Writes to the serial port of the MMC1, if the cartridge has one
+    fn write_mapper(&mut self, address: u16, value: u8) {
+        let Some(mmc1) = &mut self.mmc1 else {
+            return;
+        };
+
+        if value & 0x80 != 0 {
+            mmc1.shift = 0;
+            mmc1.writes = 0;
+            mmc1.control |= 0x0C;
+            return;
+        }
+
+        mmc1.shift |= (value & 1) << mmc1.writes;
+        mmc1.writes += 1;
+        if mmc1.writes == 5 {
+            match (address >> 13) & 3 {
+                0 => mmc1.control = mmc1.shift,
+                3 => mmc1.prg_bank = mmc1.shift,
+                // the CHR banks don't matter to the cpu
+                _ => {}
+            }
+            mmc1.shift = 0;
+            mmc1.writes = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_tests, TestSelector};
+
+    /// An NROM rom with `prg` at the start of its single bank, and the reset vector pointing to it
+    fn rom(prg: &[u8]) -> Vec<u8> {
+        let mut rom = b"NES\x1A\x01\x01\x00\x00".to_vec();
+        rom.resize(16 + 0x4000 + 0x2000, 0);
+        rom[16..16 + prg.len()].copy_from_slice(prg);
+        rom[16 + 0x3FFC..16 + 0x3FFE].copy_from_slice(&[0x00, 0xC0]);
+        rom
+    }
+
+    fn load_error(rom: &[u8]) -> String {
+        ReferenceCpu::new(rom).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn passes_nestest() {
+        run_tests::<ReferenceCpu>(TestSelector::NESTEST | TestSelector::NESTEST_LOG).unwrap();
+    }
+
+    #[test]
+    fn passes_all_instrs() {
+        run_tests::<ReferenceCpu>(TestSelector::ALL_INSTRS).unwrap();
+    }
+
+    #[test]
+    fn broken_roms_are_not_loaded() {
+        let rom = rom(&[]);
+
+        assert_eq!(
+            load_error(&rom[..10]),
+            "the rom is too short to hold an iNES header"
+        );
+        assert_eq!(load_error(&[0; 16]), "the rom isn't in iNES format");
+        assert_eq!(
+            load_error(b"NES\x1A\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"),
+            "the rom doesn't have any PRG ROM"
+        );
+        assert_eq!(load_error(&rom[..0x4000]), "the rom is truncated");
+
+        let mut mapper = rom.clone();
+        mapper[6] = 0x40;
+        assert_eq!(
+            load_error(&mapper),
+            "the reference cpu doesn't support mapper 4"
+        );
+    }
+
+    #[test]
+    fn roms_can_have_a_trainer() {
+        let mut rom = rom(&[0xEA]);
+        rom[6] = 0x04;
+        rom.splice(16..16, [0xFF; 512]);
+
+        let cpu = ReferenceCpu::new(&rom).unwrap();
+        assert_eq!(cpu.pc, 0xC000);
+        assert_eq!(cpu.memory_read(0xC000), 0xEA);
+    }
+
+    #[test]
+    fn cycles_count_every_tick() {
+        // LDA $0200 and NOP
+        let mut cpu = ReferenceCpu::new(&rom(&[0xAD, 0x00, 0x02, 0xEA])).unwrap();
+        let mut counted = Vec::new();
+        for _ in 0..6 {
+            tudelft_nes_ppu::run_cpu_headless_for(
+                &mut cpu,
+                tudelft_nes_ppu::Mirroring::Horizontal,
+                1,
+            )
+            .unwrap();
+            counted.push((cpu.pc, cpu.cycles().unwrap()));
+        }
+
+        assert_eq!(
+            counted,
+            [
+                (0xC003, 8),
+                (0xC003, 9),
+                (0xC003, 10),
+                (0xC003, 11),
+                (0xC004, 12),
+                (0xC004, 13),
+            ]
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::history::hang_location;
+use crate::{Capability, CpuError, ExecutedInstruction, Registers, RomFailure, TestError};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// The outcome of a single test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestOutcome {
+    /// The cpu passed the test
+    Passed,
+    /// The cpu ran the test, but the test reported that the cpu did something wrong
+    Failed(RomFailure),
+    /// The cpu implementation panicked while running the test, with this message
+    Panicked(String),
+    /// [`get_cpu`](crate::TestableCpu::get_cpu) returned an error while loading the rom of the test
+    LoadError(CpuError),
+    /// The cpu returned an error while it was running the test
+    CpuError {
+        /// The error returned by the cpu
+        error: CpuError,
+        /// What the test rom reported at the time of the error, which is possibly what caused it
+        failure: Option<RomFailure>,
+    },
+    /// The test didn't finish within the amount of cycles or the time it was given
+    TimedOut(String),
+    /// The cpu kept executing the same few instructions for a long time
+    Stuck {
+        /// The program counter the cpu got stuck at
+        pc: u16,
+    },
+    /// The thread running the test overflowed its stack, see [`TestRunner::stack_size`](crate::TestRunner::stack_size).
+    /// This can only be detected when using [`Isolation::Process`](crate::Isolation::Process),
+    /// otherwise the whole process ends.
+    StackOverflow,
+    /// The process running the test crashed or had to be killed, see [`Isolation::Process`](crate::Isolation::Process)
+    Crashed(String),
+    /// The test wasn't run, because it needs a capability the cpu doesn't have
+    Skipped(Capability),
+}
+
+impl TestOutcome {
+    /// Whether the cpu passed the test
+    pub fn passed(&self) -> bool {
+        matches!(self, TestOutcome::Passed)
+    }
+
+    /// Whether the test was skipped
+    pub fn skipped(&self) -> bool {
+        matches!(self, TestOutcome::Skipped(_))
+    }
+}
+
+/// The result of running a single test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    /// The name of the test
+    pub name: String,
+    /// What happened when the test ran
+    pub outcome: TestOutcome,
+    /// The registers of the cpu when the test failed, if the cpu gives access to them
+    pub registers: Option<Registers>,
+    /// The last instructions the cpu executed before the test failed, from the oldest to the most recent one.
+    /// When the cpu got stuck, the most recent one is the instruction it got stuck at.
+    /// This is empty when the cpu doesn't give access to its registers, or when it passed the test.
+    pub history: Vec<ExecutedInstruction>,
+    /// The amount of cycles the test ran the cpu for
+    pub cycles: u64,
+    /// How long the test took to run
+    pub duration: Duration,
+}
+
+impl TestResult {
+    /// Turns this result into the error [`run_tests`](crate::run_tests) returns when a test doesn't pass
+    pub fn into_result(self) -> Result<(), TestError> {
+        let test = self.name;
+        let registers = self.registers;
+        let history = self.history;
+
+        match self.outcome {
+            TestOutcome::Passed => Ok(()),
+            TestOutcome::Failed(failure) => Err(TestError::Failed {
+                test,
+                failure,
+                registers,
+                history,
+            }),
+            TestOutcome::Panicked(message) => Err(TestError::Panicked {
+                test,
+                message,
+                history,
+            }),
+            TestOutcome::LoadError(source) => Err(TestError::LoadRom { test, source }),
+            TestOutcome::CpuError { error, failure } => Err(TestError::Cpu {
+                test,
+                source: error,
+                failure,
+                registers,
+                history,
+            }),
+            TestOutcome::TimedOut(message) => Err(TestError::TimedOut {
+                test,
+                message,
+                registers,
+                history,
+            }),
+            TestOutcome::Stuck { pc } => Err(TestError::Stuck {
+                test,
+                pc,
+                registers,
+                history,
+            }),
+            TestOutcome::StackOverflow => Err(TestError::StackOverflow { test }),
+            TestOutcome::Crashed(message) => Err(TestError::Crashed { test, message }),
+            TestOutcome::Skipped(capability) => Err(TestError::Skipped { test, capability }),
+        }
+    }
+}
+
+impl Display for TestResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let status = match &self.outcome {
+            TestOutcome::Passed => "passed",
+            TestOutcome::Failed(_) => "failed",
+            TestOutcome::Panicked(_) => "panicked",
+            TestOutcome::LoadError(_) => "failed to load",
+            TestOutcome::CpuError { .. } => "cpu error",
+            TestOutcome::TimedOut(_) => "timed out",
+            TestOutcome::Stuck { .. } => "stuck",
+            TestOutcome::StackOverflow => "stack overflow",
+            TestOutcome::Crashed(_) => "crashed",
+            TestOutcome::Skipped(_) => "skipped",
+        };
+
+        write!(
+            f,
+            "{}: {status} after {} cycles in {:.2?}",
+            self.name, self.cycles, self.duration
+        )?;
+
+        let detail = match &self.outcome {
+            TestOutcome::Passed => return Ok(()),
+            TestOutcome::Failed(failure) => failure.to_string(),
+            TestOutcome::Panicked(message)
+            | TestOutcome::TimedOut(message)
+            | TestOutcome::Crashed(message) => message.clone(),
+            TestOutcome::LoadError(error) => error.to_string(),
+            TestOutcome::CpuError {
+                error,
+                failure: Some(failure),
+            } => format!("{error}, possibly due to a test that didn't pass: '{failure}'"),
+            TestOutcome::CpuError {
+                error,
+                failure: None,
+            } => error.to_string(),
+            TestOutcome::Stuck { pc } => {
+                format!(
+                    "CPU is stuck at {}",
+                    hang_location(*pc, self.history.last())
+                )
+            }
+            TestOutcome::StackOverflow => "try a larger `TestRunner::stack_size`".to_string(),
+            TestOutcome::Skipped(capability) => format!("needs {capability}"),
+        };
+        write!(f, "\n    {}", detail.replace('\n', "\n    "))?;
+
+        if let Some(registers) = self.registers {
+            write!(f, "\n    registers: {registers}")?;
+        }
+        if !self.history.is_empty() {
+            write!(f, "\n    last instructions:")?;
+            for instruction in &self.history {
+                write!(f, "\n      {instruction}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The results of all tests that were run by [`run_tests_report`](crate::run_tests_report)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TestReport {
+    /// The result of every test, in the order they were run
+    pub results: Vec<TestResult>,
+}
+
+impl TestReport {
+    /// Whether the cpu passed every test that wasn't skipped
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// The results of the tests the cpu didn't pass, leaving out the ones that were skipped
+    pub fn failures(&self) -> impl Iterator<Item = &TestResult> {
+        self.results
+            .iter()
+            .filter(|i| !i.outcome.passed() && !i.outcome.skipped())
+    }
+
+    /// The results of the tests that were skipped
+    pub fn skipped(&self) -> impl Iterator<Item = &TestResult> {
+        self.results.iter().filter(|i| i.outcome.skipped())
+    }
+}
+
+impl Display for TestReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for result in &self.results {
+            writeln!(f, "{result}")?;
+        }
+
+        let passed = self.results.iter().filter(|i| i.outcome.passed()).count();
+        write!(f, "{passed}/{} tests passed", self.results.len())?;
+
+        let skipped = self.skipped().count();
+        if skipped > 0 {
+            write!(f, ", {skipped} skipped")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{RomFailure, TestableCpu};
+
+/// Where the rom stores the stack pointer it sees after the reset
+const STACK_POINTER: u16 = 0x00;
+/// Where the rom stores the status register it sees after the reset
+const STATUS: u16 = 0x01;
+/// Where the rom stores which code it ran last, one of the `STAGE_*` values
+const STAGE: u16 = 0x02;
+/// Where the rom stores a signature when it booted for the first time
+const SIGNATURE: u16 = 0x10;
+
+/// The stack pointer the rom sets before it waits to be reset
+const STACK_POINTER_BEFORE_RESET: u8 = 0xF0;
+
+const STAGE_BOOTED: u8 = 0x00;
+const STAGE_RESET: u8 = 0x01;
+const STAGE_IRQ_HANDLER: u8 = 0xFD;
+const STAGE_NMI_HANDLER: u8 = 0xFE;
+const STAGE_START_OF_ROM: u8 = 0xFF;
+
+/// Builds an NROM rom to test what the cpu does when it is reset.
+///
+/// The reset vector points to $8100. When the rom boots for the first time, it writes a signature to $10-$11,
+/// sets the stack pointer to $F0, clears the interrupt disable flag and waits.
+/// When it finds the signature after a reset, it stores the stack pointer in $00 and the status register in $01.
+/// The other vectors and the start of the rom store a different value in $02, so a jump to the wrong address can be recognized.
+pub(crate) fn reset_test_rom() -> Vec<u8> {
+    let mut prg = vec![0; 0x4000];
+
+    let mut write = |address: u16, code: &[u8]| {
+        let start = usize::from(address - 0x8000);
+        prg[start..start + code.len()].copy_from_slice(code);
+    };
+
+    // stores `stage` in $02 and loops forever
+    let stop = |address: u16, stage: u8| {
+        let [low, high] = (address + 4).to_le_bytes();
+        [0xA9, stage, 0x85, STAGE as u8, 0x4C, low, high]
+    };
+
+    write(0x8000, &stop(0x8000, STAGE_START_OF_ROM));
+    write(0x8200, &stop(0x8200, STAGE_NMI_HANDLER));
+    write(0x8300, &stop(0x8300, STAGE_IRQ_HANDLER));
+
+    #[rustfmt::skip]
+    let reset_handler = [
+        0xA5, 0x11,                         // LDA $11
+        0xC9, 0xA5,                         // CMP #$A5
+        0xD0, 0x14,                         // BNE first_boot
+        0xA5, 0x10,                         // LDA $10
+        0xC9, 0x5A,                         // CMP #$5A
+        0xD0, 0x0E,                         // BNE first_boot
+        0xBA,                               // TSX
+        0x86, STACK_POINTER as u8,          // STX $00
+        0x08,                               // PHP
+        0x68,                               // PLA
+        0x85, STATUS as u8,                 // STA $01
+        0xA9, STAGE_RESET,                  // LDA #$01
+        0x85, STAGE as u8,                  // STA $02
+        0x4C, 0x17, 0x81,                   // JMP $8117
+        // first_boot:
+        0xA9, 0x5A,                         // LDA #$5A
+        0x85, 0x10,                         // STA $10
+        0xA9, 0xA5,                         // LDA #$A5
+        0x85, 0x11,                         // STA $11
+        0xA2, STACK_POINTER_BEFORE_RESET,   // LDX #$F0
+        0x9A,                               // TXS
+        0x58,                               // CLI
+        0xA9, STAGE_BOOTED,                 // LDA #$00
+        0x85, STAGE as u8,                  // STA $02
+        0x4C, 0x2A, 0x81,                   // JMP $812A
+    ];
+    write(0x8100, &reset_handler);
+
+    // NMI, reset and IRQ vectors
+    write(0xBFFA, &[0x00, 0x82, 0x00, 0x81, 0x00, 0x83]);
+
+    let mut rom = b"NES\x1A\x01\x01\x00\x00".to_vec();
+    rom.resize(16, 0);
+    rom.extend(prg);
+    rom.resize(rom.len() + 0x2000, 0);
+    rom
+}
+
+/// Checks that the rom booted, and is waiting to be reset
+pub(crate) fn booted(cpu: &impl TestableCpu) -> Result<(), RomFailure> {
+    let signature = [cpu.memory_read(SIGNATURE), cpu.memory_read(SIGNATURE + 1)];
+
+    if signature != [0x5A, 0xA5] || cpu.memory_read(STAGE) != STAGE_BOOTED {
+        return Err(reset_failure(
+            "the rom didn't boot, the cpu should start at the address in the reset vector at $FFFC",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks the state of the cpu the rom stored after the reset
+pub(crate) fn reset_status(cpu: &impl TestableCpu) -> Result<(), RomFailure> {
+    let went_to = match cpu.memory_read(STAGE) {
+        STAGE_RESET => None,
+        STAGE_BOOTED => Some("kept running the code it ran before"),
+        STAGE_START_OF_ROM => Some("jumped to the start of the rom at $8000"),
+        STAGE_NMI_HANDLER => Some("jumped to the address in the NMI vector at $FFFA"),
+        STAGE_IRQ_HANDLER => Some("jumped to the address in the IRQ vector at $FFFE"),
+        _ => Some("ended up somewhere else"),
+    };
+    if let Some(went_to) = went_to {
+        return Err(reset_failure(&format!(
+            "after the reset, the cpu {went_to} instead of jumping to the address in the reset vector at $FFFC"
+        )));
+    }
+
+    let expected = STACK_POINTER_BEFORE_RESET.wrapping_sub(3);
+    let stack_pointer = cpu.memory_read(STACK_POINTER);
+    if stack_pointer != expected {
+        return Err(reset_failure(&format!(
+            "the stack pointer should be decremented by 3 during a reset, from ${STACK_POINTER_BEFORE_RESET:02X} to ${expected:02X}, but it was ${stack_pointer:02X}"
+        )));
+    }
+
+    if cpu.memory_read(STATUS) & 0x04 == 0 {
+        return Err(reset_failure(
+            "the interrupt disable flag should be set during a reset",
+        ));
+    }
+
+    Ok(())
+}
+
+fn reset_failure(description: &str) -> RomFailure {
+    RomFailure::Reset {
+        description: description.to_string(),
+    }
+}
@@ -0,0 +1,811 @@
+use crate::{
+    run_inline, run_on_cpu, run_on_thread, Capability, CpuState, Isolation, Registers, TestError,
+    TestOutcome, TestProgress, TestRunner, TestableCpu, OPCODES,
+};
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// One case of the [SingleStepTests](https://github.com/SingleStepTests/ProcessorTests/tree/main/nes6502):
+/// the state of a CPU before and after it executes a single instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SingleStepTest {
+    /// The name of the case, which is the bytes of the instruction
+    pub name: String,
+    /// The registers and memory before the instruction
+    pub initial: CpuState,
+    /// The registers and memory after the instruction
+    pub expected: CpuState,
+    /// What happened on the bus in every cycle of the instruction
+    pub cycles: Vec<BusCycle>,
+}
+
+/// What happened on the bus in a single cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusCycle {
+    /// The address on the bus
+    pub address: u16,
+    /// The value that was read or written
+    pub value: u8,
+    /// Whether the cpu wrote `value`, instead of reading it
+    pub write: bool,
+}
+
+impl Display for BusCycle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.write { "write" } else { "read" };
+        write!(f, "{kind} ${:04X} = {:02X}", self.address, self.value)
+    }
+}
+
+impl SingleStepTest {
+    /// Reads cases from `bytes`, in the format [`SingleStepTest::encode`] writes them in
+    pub fn decode(bytes: &[u8]) -> Result<Vec<Self>, String> {
+        let mut reader = Reader { bytes, position: 0 };
+        let mut tests = Vec::new();
+        while reader.position < bytes.len() {
+            let length = usize::from(reader.u8()?);
+            let name = String::from_utf8(reader.take(length)?.to_vec())
+                .map_err(|_| "the name of a case isn't UTF-8".to_string())?;
+            let initial = reader.state()?;
+            let expected = reader.state()?;
+            let cycles = (0..reader.u8()?)
+                .map(|_| {
+                    Ok(BusCycle {
+                        address: reader.u16()?,
+                        value: reader.u8()?,
+                        write: match reader.u8()? {
+                            0 => false,
+                            1 => true,
+                            kind => return Err(format!("unknown kind of bus cycle {kind}")),
+                        },
+                    })
+                })
+                .collect::<Result<_, String>>()?;
+
+            tests.push(Self {
+                name,
+                initial,
+                expected,
+                cycles,
+            });
+        }
+
+        Ok(tests)
+    }
+
+    /// Writes `tests` in a compact binary format, to store cases converted from the JSON files of the SingleStepTests.
+    /// Every case is written as:
+    ///
+    /// * the length of its name in a byte, followed by the name in UTF-8
+    /// * the initial and the final state, each as the program counter, the stack pointer, A, X, Y and P,
+    ///   followed by the amount of memory locations in a byte and the address and value of every location
+    /// * the amount of bus cycles in a byte, followed by the address, value and kind of every cycle,
+    ///   where the kind is 0 for a read and 1 for a write
+    ///
+    /// Addresses and the program counter take two bytes, little endian, and every other value takes one.
+    ///
+    /// # Panics
+    /// When a name is longer than 255 bytes, or a case has more than 255 memory locations or bus cycles
+    pub fn encode(tests: &[Self]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let count = |bytes: &mut Vec<u8>, count: usize| {
+            bytes.push(u8::try_from(count).expect("a case has at most 255 of everything"));
+        };
+
+        for test in tests {
+            count(&mut bytes, test.name.len());
+            bytes.extend_from_slice(test.name.as_bytes());
+            for state in [&test.initial, &test.expected] {
+                let r = state.registers;
+                bytes.extend_from_slice(&r.pc.to_le_bytes());
+                bytes.extend_from_slice(&[r.sp, r.a, r.x, r.y, r.p]);
+                count(&mut bytes, state.memory.len());
+                for &(address, value) in &state.memory {
+                    bytes.extend_from_slice(&address.to_le_bytes());
+                    bytes.push(value);
+                }
+            }
+            count(&mut bytes, test.cycles.len());
+            for cycle in &test.cycles {
+                bytes.extend_from_slice(&cycle.address.to_le_bytes());
+                bytes.extend_from_slice(&[cycle.value, u8::from(cycle.write)]);
+            }
+        }
+
+        bytes
+    }
+
+    /// The opcode of the instruction
+    pub fn opcode(&self) -> u8 {
+        self.initial
+            .memory
+            .iter()
+            .find(|(address, _)| *address == self.initial.registers.pc)
+            .map_or(0, |(_, value)| *value)
+    }
+
+    /// Whether a NES can hold the memory of this case. The cases use all 64 KiB as RAM, but a NES only has
+    /// RAM at $0000-$07FF, and the cartridge can hold the values at $8000-$FFFF that the instruction doesn't change.
+    fn fits_nes(&self) -> bool {
+        let initial = &self.initial.memory;
+        let ram = |address: u16| address < 0x0800;
+        let rom = |address: u16| address >= 0x8000;
+
+        initial
+            .iter()
+            .all(|&(address, _)| ram(address) || rom(address))
+            && self
+                .cycles
+                .iter()
+                .all(|cycle| ram(cycle.address) || rom(cycle.address))
+            && self
+                .expected
+                .memory
+                .iter()
+                .all(|entry| ram(entry.0) || (rom(entry.0) && initial.contains(entry)))
+    }
+
+    /// Builds an NROM rom which holds the memory of this case at $8000-$FFFF
+    fn rom(&self) -> Vec<u8> {
+        let mut rom = b"NES\x1A\x02\x01\x00\x00".to_vec();
+        rom.resize(16 + 0x8000 + 0x2000, 0);
+        for &(address, value) in &self.initial.memory {
+            if address >= 0x8000 {
+                rom[16 + usize::from(address - 0x8000)] = value;
+            }
+        }
+        rom
+    }
+}
+
+/// The results of the cases of a single opcode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeResult {
+    /// The opcode
+    pub opcode: u8,
+    /// The amount of cases the cpu passed
+    pub passed: usize,
+    /// The amount of cases the cpu didn't pass
+    pub failed: usize,
+    /// The amount of cases that were skipped, because they use memory a NES doesn't have
+    pub skipped: usize,
+    /// The first case the cpu didn't pass
+    pub first_failure: Option<SingleStepFailure>,
+}
+
+/// A case of the SingleStepTests the cpu didn't pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SingleStepFailure {
+    /// The name of the case
+    pub name: String,
+    /// The registers and memory the cpu should have had after the instruction
+    pub expected: CpuState,
+    /// The registers and memory the cpu had after the instruction
+    pub actual: CpuState,
+    /// The amount of cycles the instruction should have taken
+    pub expected_cycles: u64,
+    /// The amount of cycles the instruction took, when the cpu counts them
+    pub actual_cycles: Option<u64>,
+    /// What should have happened on the bus in every cycle of the instruction
+    pub expected_bus: Vec<BusCycle>,
+    /// What happened on the bus in every cycle of the instruction, when the cpu logs it
+    pub actual_bus: Option<Vec<BusCycle>>,
+    /// The error the cpu returned while it executed the instruction
+    pub error: Option<String>,
+}
+
+impl Display for SingleStepFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "case \"{}\"", self.name)?;
+        if let Some(error) = &self.error {
+            return write!(f, ": cpu error {error}");
+        }
+
+        let expected = self.expected.registers;
+        let actual = self.actual.registers;
+        write!(f, "\n  expected: {expected}\n  actual:   {actual}")?;
+
+        for (&(address, expected), &(_, actual)) in
+            self.expected.memory.iter().zip(&self.actual.memory)
+        {
+            if expected != actual {
+                write!(
+                    f,
+                    "\n  ${address:04X}: expected {expected:02X}, found {actual:02X}"
+                )?;
+            }
+        }
+
+        if let Some(actual) = self.actual_cycles {
+            if actual != self.expected_cycles {
+                write!(
+                    f,
+                    "\n  took {actual} cycles instead of {}",
+                    self.expected_cycles
+                )?;
+            }
+        }
+
+        if let Some(actual) = &self.actual_bus {
+            let expected = &self.expected_bus;
+            if let Some(cycle) = (0..expected.len().max(actual.len()))
+                .find(|&cycle| expected.get(cycle) != actual.get(cycle))
+            {
+                let show =
+                    |bus: Option<&BusCycle>| bus.map_or("nothing".to_string(), BusCycle::to_string);
+                write!(
+                    f,
+                    "\n  cycle {}: expected {}, found {}",
+                    cycle + 1,
+                    show(expected.get(cycle)),
+                    show(actual.get(cycle))
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The results of [`run_single_step_tests`], by opcode
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SingleStepReport {
+    /// The results of every opcode that had cases, ordered by opcode
+    pub opcodes: Vec<OpcodeResult>,
+}
+
+impl SingleStepReport {
+    /// Whether the cpu passed every case that wasn't skipped
+    pub fn passed(&self) -> bool {
+        self.opcodes.iter().all(|opcode| opcode.failed == 0)
+    }
+}
+
+impl Display for SingleStepReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for result in self
+            .opcodes
+            .iter()
+            .filter(|opcode| opcode.failed > 0 || opcode.skipped > 0)
+        {
+            let opcode = &OPCODES[usize::from(result.opcode)];
+            write!(
+                f,
+                "{:02X} {opcode}: {}/{} cases passed",
+                result.opcode,
+                result.passed,
+                result.passed + result.failed
+            )?;
+            if result.skipped > 0 {
+                write!(f, ", {} skipped", result.skipped)?;
+            }
+            if let Some(failure) = &result.first_failure {
+                write!(f, ", first failure: {failure}")?;
+                if failure
+                    .actual_cycles
+                    .is_some_and(|cycles| cycles != failure.expected_cycles)
+                {
+                    write!(f, "\n  {} takes {}", opcode.mnemonic, opcode.timing())?;
+                }
+            }
+            writeln!(f)?;
+        }
+
+        let failed = self.opcodes.iter().filter(|opcode| opcode.failed > 0);
+        write!(
+            f,
+            "{}/{} opcodes passed",
+            self.opcodes.len() - failed.count(),
+            self.opcodes.len()
+        )?;
+
+        let skipped: usize = self.opcodes.iter().map(|opcode| opcode.skipped).sum();
+        if skipped > 0 {
+            write!(f, ", {skipped} cases skipped")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How long all cases may take together, unless [`TestRunner::timeout`] says otherwise
+const TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Runs cases of the [SingleStepTests](https://github.com/SingleStepTests/ProcessorTests/tree/main/nes6502).
+/// The cases aren't included in the test suite, so you have to convert the JSON files of that repository yourself,
+/// for example to the binary format [`SingleStepTest::decode`] reads.
+///
+/// Every case loads a rom holding the memory of the case at $8000-$FFFF, writes the registers and RAM of the case
+/// to your CPU, executes one instruction and compares the registers and memory afterwards. When your CPU counts
+/// cycles, the amount of cycles is compared too, and when it implements [`CpuBusLog`](crate::CpuBusLog),
+/// the read or write of every cycle is compared as well. Cases that use memory a NES doesn't have,
+/// like $2000-$7FFF, are skipped and counted for every opcode in the report.
+///
+/// Your CPU has to implement [`CpuRegisters`](crate::CpuRegisters), [`CpuRegisterWrite`](crate::CpuRegisterWrite),
+/// [`CpuMemoryWrite`](crate::CpuMemoryWrite) and [`CpuStep`](crate::CpuStep), otherwise [`TestError::Skipped`]
+/// is returned. The cases run on their own thread, like the tests of [`run_tests`](crate::run_tests) do;
+/// use [`TestRunner::run_single_step_tests`] to run them with other options.
+pub fn run_single_step_tests<T: TestableCpu>(
+    tests: Vec<SingleStepTest>,
+) -> Result<SingleStepReport, TestError> {
+    TestRunner::default().run_single_step_tests::<T>(tests)
+}
+
+/// Runs `tests` with the options of `runner`, see [`run_single_step_tests`]
+pub(crate) fn run_cases<T: TestableCpu>(
+    runner: &TestRunner,
+    tests: Vec<SingleStepTest>,
+) -> Result<SingleStepReport, TestError> {
+    let reported = Arc::new(Mutex::new(None));
+
+    let test = {
+        let reported = Arc::clone(&reported);
+        move |progress: &TestProgress| {
+            let mut report = SingleStepReport::default();
+            for test in &tests {
+                let opcode = test.opcode();
+                let index = match report.opcodes.binary_search_by_key(&opcode, |r| r.opcode) {
+                    Ok(index) => index,
+                    Err(index) => {
+                        report.opcodes.insert(
+                            index,
+                            OpcodeResult {
+                                opcode,
+                                passed: 0,
+                                failed: 0,
+                                skipped: 0,
+                                first_failure: None,
+                            },
+                        );
+                        index
+                    }
+                };
+                let result = &mut report.opcodes[index];
+
+                if !test.fits_nes() {
+                    result.skipped += 1;
+                    continue;
+                }
+
+                match run_case::<T>(test, progress)? {
+                    None => result.passed += 1,
+                    Some(failure) => {
+                        result.failed += 1;
+                        result.first_failure.get_or_insert(failure);
+                    }
+                }
+            }
+
+            *reported.lock().unwrap() = Some(report);
+            Ok(())
+        }
+    };
+
+    let name = "single step tests";
+    let trace = runner.trace.as_ref();
+    let result = match runner.isolation {
+        Isolation::Inline => run_inline(name, test, trace),
+        Isolation::Thread | Isolation::Process => run_on_thread(
+            name,
+            test,
+            runner.timeout.unwrap_or(TIMEOUT),
+            runner.stack_size,
+            trace,
+        ),
+    };
+    result.into_result()?;
+
+    let report = reported.lock().unwrap().take();
+    Ok(report.expect("single step tests that ran successfully reported their results"))
+}
+
+/// Runs a single case, and returns how the cpu didn't pass it
+fn run_case<T: TestableCpu>(
+    test: &SingleStepTest,
+    progress: &TestProgress,
+) -> Result<Option<SingleStepFailure>, TestOutcome> {
+    let mut failure = None;
+    run_on_cpu::<T>(&test.rom(), progress, |cpu| {
+        for capability in [Capability::Registers, Capability::Step] {
+            if !capability.supported_by(cpu) {
+                return Err(TestOutcome::Skipped(capability));
+            }
+        }
+        // the values at $8000-$FFFF are in the rom already
+        let initial = CpuState {
+            registers: test.initial.registers,
+            memory: test
+                .initial
+                .memory
+                .iter()
+                .filter(|(address, _)| *address < 0x0800)
+                .copied()
+                .collect(),
+        };
+        initial.write(cpu).map_err(TestOutcome::Skipped)?;
+
+        let addresses = test.expected.memory.iter().map(|(address, _)| *address);
+        let before = cpu.registers().and_then(|r| r.cycles());
+        // the bus activity of loading the rom and writing the state isn't part of the case
+        if let Some(log) = cpu.bus_log() {
+            log.take_bus_cycles();
+        }
+        let stepped = cpu.steppable().unwrap().step();
+        let bus = cpu.bus_log().map(|log| log.take_bus_cycles());
+        let mut actual = CpuState::read(cpu, addresses).map_err(TestOutcome::Skipped)?;
+        let actual_cycles = before
+            .zip(actual.registers.cycles.take())
+            .map(|(before, after)| after.wrapping_sub(before));
+
+        let expected_cycles = test.cycles.len() as u64;
+        progress
+            .cycles
+            .fetch_add(expected_cycles, Ordering::Relaxed);
+
+        let same_registers = {
+            let (e, a) = (test.expected.registers, actual.registers);
+            (e.a, e.x, e.y, e.p & !0x30, e.sp, e.pc) == (a.a, a.x, a.y, a.p & !0x30, a.sp, a.pc)
+        };
+        if stepped.is_err()
+            || !same_registers
+            || actual.memory != test.expected.memory
+            || actual_cycles.is_some_and(|cycles| cycles != expected_cycles)
+            || bus.as_ref().is_some_and(|bus| *bus != test.cycles)
+        {
+            failure = Some(SingleStepFailure {
+                name: test.name.clone(),
+                expected: test.expected.clone(),
+                actual,
+                expected_cycles,
+                actual_cycles,
+                expected_bus: test.cycles.clone(),
+                actual_bus: bus,
+                error: stepped.err().map(|e| e.to_string()),
+            });
+        }
+
+        Ok(())
+    })?;
+
+    Ok(failure)
+}
+
+/// Reads the fields of cases written by [`SingleStepTest::encode`]
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or_else(|| format!("a case ends early, at byte {}", self.bytes.len()))?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn state(&mut self) -> Result<CpuState, String> {
+        let pc = self.u16()?;
+        let [sp, a, x, y, p] = [self.u8()?, self.u8()?, self.u8()?, self.u8()?, self.u8()?];
+        let memory = (0..self.u8()?)
+            .map(|_| Ok((self.u16()?, self.u8()?)))
+            .collect::<Result<_, String>>()?;
+
+        Ok(CpuState {
+            registers: Registers {
+                a,
+                x,
+                y,
+                p,
+                sp,
+                pc,
+                cycles: None,
+            },
+            memory,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The state of a case, from its program counter, stack pointer, A, X, Y, P and memory
+    fn state(pc: u16, [sp, a, x, y, p]: [u8; 5], memory: &[(u16, u8)]) -> CpuState {
+        CpuState {
+            registers: Registers {
+                a,
+                x,
+                y,
+                p,
+                sp,
+                pc,
+                cycles: None,
+            },
+            memory: memory.to_vec(),
+        }
+    }
+
+    fn bus(cycles: &[(u16, u8, bool)]) -> Vec<BusCycle> {
+        cycles
+            .iter()
+            .map(|&(address, value, write)| BusCycle {
+                address,
+                value,
+                write,
+            })
+            .collect()
+    }
+
+    /// Cases like those of the SingleStepTests: LDA #$42 and STA $10 in RAM, LDA $2000, which a NES can't run
+    /// without a PPU, and LDA #$01 in the rom
+    fn cases() -> Vec<SingleStepTest> {
+        vec![
+            SingleStepTest {
+                name: "a9 42 00".to_string(),
+                initial: state(
+                    0x04D2,
+                    [0xFD, 0x00, 0, 0, 0x26],
+                    &[(0x04D2, 0xA9), (0x04D3, 0x42)],
+                ),
+                expected: state(
+                    0x04D4,
+                    [0xFD, 0x42, 0, 0, 0x24],
+                    &[(0x04D2, 0xA9), (0x04D3, 0x42)],
+                ),
+                cycles: bus(&[(0x04D2, 0xA9, false), (0x04D3, 0x42, false)]),
+            },
+            SingleStepTest {
+                name: "85 10 00".to_string(),
+                initial: state(
+                    0x0200,
+                    [0xFD, 0x99, 1, 2, 0xA4],
+                    &[(0x0200, 0x85), (0x0201, 0x10), (0x0010, 0x00)],
+                ),
+                expected: state(
+                    0x0202,
+                    [0xFD, 0x99, 1, 2, 0xA4],
+                    &[(0x0200, 0x85), (0x0201, 0x10), (0x0010, 0x99)],
+                ),
+                cycles: bus(&[
+                    (0x0200, 0x85, false),
+                    (0x0201, 0x10, false),
+                    (0x0010, 0x99, true),
+                ]),
+            },
+            SingleStepTest {
+                name: "ad 00 20".to_string(),
+                initial: state(
+                    0x0300,
+                    [0xFD, 0x00, 0, 0, 0x24],
+                    &[
+                        (0x0300, 0xAD),
+                        (0x0301, 0x00),
+                        (0x0302, 0x20),
+                        (0x2000, 0x07),
+                    ],
+                ),
+                expected: state(
+                    0x0303,
+                    [0xFD, 0x07, 0, 0, 0x24],
+                    &[
+                        (0x0300, 0xAD),
+                        (0x0301, 0x00),
+                        (0x0302, 0x20),
+                        (0x2000, 0x07),
+                    ],
+                ),
+                cycles: bus(&[
+                    (0x0300, 0xAD, false),
+                    (0x0301, 0x00, false),
+                    (0x0302, 0x20, false),
+                    (0x2000, 0x07, false),
+                ]),
+            },
+            SingleStepTest {
+                name: "a9 01 ea".to_string(),
+                initial: state(
+                    0x9000,
+                    [0xFF, 0x00, 0, 0, 0x26],
+                    &[(0x9000, 0xA9), (0x9001, 0x01)],
+                ),
+                expected: state(
+                    0x9002,
+                    [0xFF, 0x01, 0, 0, 0x24],
+                    &[(0x9000, 0xA9), (0x9001, 0x01)],
+                ),
+                cycles: bus(&[(0x9000, 0xA9, false), (0x9001, 0x01, false)]),
+            },
+        ]
+    }
+
+    #[test]
+    fn cases_round_trip() {
+        let bytes = SingleStepTest::encode(&cases());
+
+        assert_eq!(SingleStepTest::decode(&bytes), Ok(cases()));
+        assert_eq!(SingleStepTest::decode(&[]), Ok(Vec::new()));
+        assert_eq!(
+            cases()
+                .iter()
+                .map(SingleStepTest::opcode)
+                .collect::<Vec<_>>(),
+            [0xA9, 0x85, 0xAD, 0xA9]
+        );
+    }
+
+    #[test]
+    fn cases_are_encoded_as_documented() {
+        let store = cases().remove(1);
+
+        #[rustfmt::skip]
+        let expected = [
+            8, b'8', b'5', b' ', b'1', b'0', b' ', b'0', b'0',
+            0x00, 0x02, 0xFD, 0x99, 0x01, 0x02, 0xA4,
+            3, 0x00, 0x02, 0x85, 0x01, 0x02, 0x10, 0x10, 0x00, 0x00,
+            0x02, 0x02, 0xFD, 0x99, 0x01, 0x02, 0xA4,
+            3, 0x00, 0x02, 0x85, 0x01, 0x02, 0x10, 0x10, 0x00, 0x99,
+            3, 0x00, 0x02, 0x85, 0, 0x01, 0x02, 0x10, 0, 0x10, 0x00, 0x99, 1,
+        ];
+        assert_eq!(SingleStepTest::encode(&[store]), expected);
+    }
+
+    #[test]
+    fn broken_cases_are_not_decoded() {
+        let bytes = SingleStepTest::encode(&cases()[..1]);
+
+        for length in 1..bytes.len() {
+            assert_eq!(
+                SingleStepTest::decode(&bytes[..length]),
+                Err(format!("a case ends early, at byte {length}"))
+            );
+        }
+
+        let mut bad_kind = bytes.clone();
+        *bad_kind.last_mut().unwrap() = 2;
+        assert_eq!(
+            SingleStepTest::decode(&bad_kind),
+            Err("unknown kind of bus cycle 2".to_string())
+        );
+
+        let mut bad_name = bytes;
+        bad_name[1] = 0xFF;
+        assert_eq!(
+            SingleStepTest::decode(&bad_name),
+            Err("the name of a case isn't UTF-8".to_string())
+        );
+    }
+
+    #[test]
+    fn cases_that_dont_fit_a_nes_are_skipped() {
+        let fits: Vec<_> = cases().iter().map(SingleStepTest::fits_nes).collect();
+        assert_eq!(fits, [true, true, false, true]);
+
+        // an instruction that writes to the rom
+        let mut write_to_rom = cases().remove(3);
+        write_to_rom.expected.memory[1].1 = 0x02;
+        assert!(!write_to_rom.fits_nes());
+    }
+
+    #[test]
+    fn rom_holds_the_memory_above_8000() {
+        let rom = cases()[3].rom();
+
+        assert_eq!(rom.len(), 16 + 0x8000 + 0x2000);
+        assert_eq!(rom[16 + 0x1000..16 + 0x1002], [0xA9, 0x01]);
+        assert!(cases()[1].rom()[16..].iter().all(|&value| value == 0));
+    }
+
+    #[test]
+    fn failures_show_the_first_different_bus_cycle() {
+        let store = cases().remove(1);
+        let mut actual_bus = store.cycles.clone();
+        actual_bus[2].write = false;
+        let failure = SingleStepFailure {
+            name: store.name.clone(),
+            expected: store.expected.clone(),
+            actual: store.expected.clone(),
+            expected_cycles: 3,
+            actual_cycles: Some(3),
+            expected_bus: store.cycles.clone(),
+            actual_bus: Some(actual_bus),
+            error: None,
+        };
+
+        assert!(failure
+            .to_string()
+            .ends_with("\n  cycle 3: expected write $0010 = 99, found read $0010 = 99"));
+
+        let short = SingleStepFailure {
+            actual_bus: Some(store.cycles[..2].to_vec()),
+            ..failure
+        };
+        assert!(short
+            .to_string()
+            .ends_with("\n  cycle 3: expected write $0010 = 99, found nothing"));
+    }
+
+    #[test]
+    fn report_counts_skipped_cases_for_every_opcode() {
+        let report = SingleStepReport {
+            opcodes: vec![
+                OpcodeResult {
+                    opcode: 0xA9,
+                    passed: 2,
+                    failed: 0,
+                    skipped: 0,
+                    first_failure: None,
+                },
+                OpcodeResult {
+                    opcode: 0xAD,
+                    passed: 3,
+                    failed: 0,
+                    skipped: 1,
+                    first_failure: None,
+                },
+            ],
+        };
+        let text = report.to_string();
+
+        assert!(report.passed());
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.starts_with("AD "));
+        assert!(text.contains(": 3/3 cases passed, 1 skipped\n"));
+        assert!(text.ends_with("2/2 opcodes passed, 1 cases skipped"));
+    }
+
+    #[cfg(feature = "reference")]
+    #[test]
+    fn reference_cpu_passes_the_cases() {
+        use crate::ReferenceCpu;
+
+        let inline = TestRunner {
+            isolation: Isolation::Inline,
+            ..TestRunner::default()
+        };
+        for report in [
+            run_single_step_tests::<ReferenceCpu>(cases()).unwrap(),
+            inline
+                .run_single_step_tests::<ReferenceCpu>(cases())
+                .unwrap(),
+        ] {
+            let counts: Vec<_> = report
+                .opcodes
+                .iter()
+                .map(|result| (result.opcode, result.passed, result.skipped))
+                .collect();
+            assert_eq!(counts, [(0x85, 1, 0), (0xA9, 2, 0), (0xAD, 0, 1)]);
+            assert!(report.passed());
+        }
+    }
+
+    #[cfg(feature = "reference")]
+    #[test]
+    fn reference_cpu_fails_wrong_cases() {
+        use crate::ReferenceCpu;
+
+        let mut cases = cases();
+        cases[0].expected.registers.a = 0x43;
+        let report = run_single_step_tests::<ReferenceCpu>(cases).unwrap();
+        let lda = &report.opcodes[1];
+
+        assert_eq!((lda.passed, lda.failed), (1, 1));
+        let failure = lda.first_failure.as_ref().unwrap();
+        assert_eq!(failure.name, "a9 42 00");
+        assert_eq!(failure.actual.registers.a, 0x42);
+        assert_eq!(failure.actual_cycles, Some(2));
+    }
+}
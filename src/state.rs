@@ -0,0 +1,231 @@
+use crate::{Capability, Registers, TestableCpu};
+
+/// The registers and part of the memory of a CPU. Writing it to a CPU puts that CPU in an exact state
+/// without a rom to set it up, to test a single instruction or to continue a long test from a checkpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuState {
+    /// The registers. The cycle count is only read, it isn't written.
+    pub registers: Registers,
+    /// The addresses of memory locations and their values
+    pub memory: Vec<(u16, u8)>,
+}
+
+impl CpuState {
+    /// Reads the registers of `cpu` and its memory at `addresses`.
+    /// Returns [`Capability::Registers`] when `cpu` doesn't give access to its registers.
+    pub fn read(
+        cpu: &impl TestableCpu,
+        addresses: impl IntoIterator<Item = u16>,
+    ) -> Result<Self, Capability> {
+        let registers = cpu
+            .registers()
+            .map(Registers::read)
+            .ok_or(Capability::Registers)?;
+        let memory = addresses
+            .into_iter()
+            .map(|address| (address, cpu.memory_read(address)))
+            .collect();
+
+        Ok(Self { registers, memory })
+    }
+
+    /// Writes the registers and memory of this state to `cpu`.
+    /// Returns the capability `cpu` is missing when it can't be written to, without changing anything.
+    pub fn write(&self, cpu: &mut impl TestableCpu) -> Result<(), Capability> {
+        if !self.memory.is_empty() && cpu.writable().is_none() {
+            return Err(Capability::MemoryWrite);
+        }
+
+        let Registers {
+            a, x, y, p, sp, pc, ..
+        } = self.registers;
+        let registers = cpu.registers_mut().ok_or(Capability::RegisterWrite)?;
+        registers.set_accumulator(a);
+        registers.set_x_register(x);
+        registers.set_y_register(y);
+        registers.set_status(p);
+        registers.set_stack_pointer(sp);
+        registers.set_pc(pc);
+
+        if let Some(memory) = cpu.writable() {
+            for &(address, value) in &self.memory {
+                memory.memory_write(address, value);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CpuMemoryWrite, CpuRegisterWrite, CpuRegisters};
+    use std::error::Error;
+    use tudelft_nes_ppu::{Cpu, Ppu};
+
+    /// A cpu that only holds registers and memory, and can be written to when `writable` is set
+    #[derive(Default)]
+    struct Plain {
+        writable: bool,
+        registers: [u8; 5],
+        pc: u16,
+        memory: Vec<u8>,
+    }
+
+    impl Cpu for Plain {
+        fn tick(&mut self, _ppu: &mut Ppu) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+
+        fn ppu_read_chr_rom(&self, _offset: u16) -> u8 {
+            0
+        }
+
+        fn non_maskable_interrupt(&mut self) {}
+    }
+
+    impl TestableCpu for Plain {
+        fn get_cpu(_rom: &[u8]) -> Result<Self, Box<dyn Error>> {
+            Ok(Self {
+                memory: vec![0; 0x10000],
+                ..Self::default()
+            })
+        }
+
+        fn set_program_counter(&mut self, value: u16) {
+            self.set_pc(value);
+        }
+
+        fn memory_read(&self, address: u16) -> u8 {
+            self.memory[usize::from(address)]
+        }
+
+        fn registers(&self) -> Option<&dyn CpuRegisters> {
+            Some(self)
+        }
+
+        fn writable(&mut self) -> Option<&mut dyn CpuMemoryWrite> {
+            self.writable.then_some(self)
+        }
+
+        fn registers_mut(&mut self) -> Option<&mut dyn CpuRegisterWrite> {
+            self.writable.then_some(self)
+        }
+    }
+
+    impl CpuRegisters for Plain {
+        fn accumulator(&self) -> u8 {
+            self.registers[0]
+        }
+
+        fn x_register(&self) -> u8 {
+            self.registers[1]
+        }
+
+        fn y_register(&self) -> u8 {
+            self.registers[2]
+        }
+
+        fn status(&self) -> u8 {
+            self.registers[3]
+        }
+
+        fn stack_pointer(&self) -> u8 {
+            self.registers[4]
+        }
+
+        fn program_counter(&self) -> u16 {
+            self.pc
+        }
+    }
+
+    impl CpuRegisterWrite for Plain {
+        fn set_accumulator(&mut self, value: u8) {
+            self.registers[0] = value;
+        }
+
+        fn set_x_register(&mut self, value: u8) {
+            self.registers[1] = value;
+        }
+
+        fn set_y_register(&mut self, value: u8) {
+            self.registers[2] = value;
+        }
+
+        fn set_status(&mut self, value: u8) {
+            self.registers[3] = value;
+        }
+
+        fn set_stack_pointer(&mut self, value: u8) {
+            self.registers[4] = value;
+        }
+
+        fn set_pc(&mut self, value: u16) {
+            self.pc = value;
+        }
+    }
+
+    impl CpuMemoryWrite for Plain {
+        fn memory_write(&mut self, address: u16, value: u8) {
+            self.memory[usize::from(address)] = value;
+        }
+    }
+
+    fn state() -> CpuState {
+        CpuState {
+            registers: Registers {
+                a: 0x12,
+                x: 0x34,
+                y: 0x56,
+                p: 0xE5,
+                sp: 0xFB,
+                pc: 0xC123,
+                cycles: None,
+            },
+            memory: vec![(0x0000, 0x01), (0x01FC, 0x02), (0xC123, 0xEA)],
+        }
+    }
+
+    #[test]
+    fn written_states_read_back_the_same() {
+        let mut cpu = Plain {
+            writable: true,
+            ..Plain::get_cpu(&[]).unwrap()
+        };
+        let state = state();
+
+        state.write(&mut cpu).unwrap();
+        let addresses = state.memory.iter().map(|&(address, _)| address);
+
+        assert_eq!(CpuState::read(&cpu, addresses).unwrap(), state);
+    }
+
+    #[test]
+    fn both_program_counter_setters_can_be_called() {
+        let mut cpu = Plain::get_cpu(&[]).unwrap();
+
+        cpu.set_program_counter(0x8000);
+        assert_eq!(cpu.pc, 0x8000);
+        cpu.set_pc(0xC000);
+        assert_eq!(cpu.pc, 0xC000);
+    }
+
+    #[test]
+    fn cpus_that_cant_be_written_are_left_alone() {
+        let mut cpu = Plain::get_cpu(&[]).unwrap();
+
+        assert_eq!(state().write(&mut cpu), Err(Capability::MemoryWrite));
+        let registers_only = CpuState {
+            memory: Vec::new(),
+            ..state()
+        };
+        assert_eq!(
+            registers_only.write(&mut cpu),
+            Err(Capability::RegisterWrite)
+        );
+
+        assert_eq!(cpu.pc, 0);
+        assert!(cpu.memory.iter().all(|&value| value == 0));
+    }
+}
@@ -0,0 +1,510 @@
+use crate::AddressingMode::*;
+use crate::{
+    cpu_error, Opcode, OpcodeClass, Registers, TestOutcome, TestProgress, TestableCpu,
+    MAX_INSTRUCTION_CYCLES, OPCODES,
+};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::Ordering;
+
+/// Where the prg rom of the timing rom starts
+const ROM_START: u16 = 0x8000;
+/// The size of the prg rom of the timing rom, two banks of 16 KiB
+const PRG_SIZE: usize = 0x8000;
+/// The pointer in the zero page the indirect instructions use
+const POINTER: u8 = 0x20;
+/// The address in the zero page the zero page instructions use
+const ZERO_PAGE: u8 = 0x30;
+/// The address the absolute instructions use. X and Y are 1, so indexing from here stays within the page.
+const SAME_PAGE: u16 = 0x0200;
+/// The last byte of a page, from which indexing with X or Y crosses into the next page
+const PAGE_END: u16 = 0x02FF;
+
+/// How an instruction is timed by [`TestSelector::INSTR_TIMING`](crate::TestSelector::INSTR_TIMING)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimingCase {
+    /// Without any penalty: indexing stays within a page, and a branch isn't taken
+    Base,
+    /// Indexing crosses a page
+    PageCrossed,
+    /// The branch is taken, to an address on the same page
+    BranchTaken,
+    /// The branch is taken, to an address on another page
+    BranchToOtherPage,
+}
+
+impl TimingCase {
+    /// Every case
+    pub const ALL: [TimingCase; 4] = [
+        TimingCase::Base,
+        TimingCase::PageCrossed,
+        TimingCase::BranchTaken,
+        TimingCase::BranchToOtherPage,
+    ];
+}
+
+impl Display for TimingCase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TimingCase::Base => "without penalties",
+            TimingCase::PageCrossed => "crossing a page",
+            TimingCase::BranchTaken => "taking the branch",
+            TimingCase::BranchToOtherPage => "taking the branch to another page",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// An instruction that took a different amount of cycles than it does on a NES
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingMismatch {
+    /// The opcode of the instruction, see [`OPCODES`]
+    pub opcode: u8,
+    /// How the instruction was timed
+    pub case: TimingCase,
+    /// The amount of cycles the instruction takes on a NES
+    pub expected: u64,
+    /// The amount of cycles the instruction took, or `None` when the cpu never got to it
+    pub actual: Option<u64>,
+}
+
+impl Display for TimingMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let opcode = &OPCODES[usize::from(self.opcode)];
+        write!(f, "{:02X} {opcode}, {}: ", self.opcode, self.case)?;
+
+        match self.actual {
+            Some(actual) => write!(f, "took {actual} cycles instead of {}", self.expected),
+            None => write!(f, "wasn't executed"),
+        }
+    }
+}
+
+/// Lists the instructions that took the wrong amount of cycles in a table, like blargg's instr_timing does
+pub(crate) fn timing_table(mismatches: &[TimingMismatch]) -> String {
+    let mut table = format!(
+        "{} instructions took the wrong amount of cycles:\n {:<2}  {:<20} {:<34} {:>8} {:>6}",
+        mismatches.len(),
+        "op",
+        "instruction",
+        "case",
+        "expected",
+        "actual"
+    );
+
+    for mismatch in mismatches {
+        let opcode = &OPCODES[usize::from(mismatch.opcode)];
+        let actual = match mismatch.actual {
+            Some(actual) => actual.to_string(),
+            None => "-".to_string(),
+        };
+        table += &format!(
+            "\n {:02X}  {:<20} {:<34} {:>8} {actual:>6}",
+            mismatch.opcode,
+            opcode.to_string(),
+            mismatch.case.to_string(),
+            mismatch.expected,
+        );
+    }
+
+    table
+}
+
+/// An instruction in the timing rom
+#[derive(Debug, Clone, Copy)]
+struct Timed {
+    opcode: u8,
+    case: TimingCase,
+    expected: u64,
+}
+
+/// A generated rom that executes every opcode, see [`timing_rom`]
+pub(crate) struct TimingRom {
+    pub(crate) rom: Vec<u8>,
+    /// The instructions that are timed, by their address
+    timed: HashMap<u16, Timed>,
+    /// The address of the loop the rom ends in
+    end: u16,
+    /// How many cycles the rom may take before it is considered stuck
+    budget: u64,
+}
+
+/// Builds an NROM rom that executes every opcode except the ones that halt the cpu, leaving out the unofficial ones
+/// when `official_only` is set.
+///
+/// Every instruction is preceded by code that sets X and Y to 1 and points the pointer at $20 to the address the
+/// instruction uses, so instructions that index from the last byte of a page cross into the next page.
+/// The instructions that index are executed twice: once within a page, and once crossing a page.
+/// Branches are executed three times: not taken, taken to the same page and taken to the next page.
+/// The instructions that jump, like `JMP`, `RTS` and `BRK`, continue with the next instruction in the rom.
+pub(crate) fn timing_rom(official_only: bool) -> TimingRom {
+    let mut code = Code {
+        prg: Vec::new(),
+        timed: HashMap::new(),
+        irq: ROM_START,
+    };
+
+    for (opcode, info) in (0..=u8::MAX).zip(&OPCODES) {
+        match info.class {
+            OpcodeClass::Jam => continue,
+            OpcodeClass::Unofficial | OpcodeClass::Unstable if official_only => continue,
+            _ => {}
+        }
+
+        match info.mode {
+            Relative => {
+                code.branch(opcode, info, TimingCase::Base);
+                code.branch(opcode, info, TimingCase::BranchTaken);
+                code.branch(opcode, info, TimingCase::BranchToOtherPage);
+            }
+            AbsoluteX | AbsoluteY | IndirectY => {
+                code.instruction(opcode, info, TimingCase::Base);
+                code.instruction(opcode, info, TimingCase::PageCrossed);
+            }
+            _ => code.instruction(opcode, info, TimingCase::Base),
+        }
+    }
+
+    let end = code.address();
+    let [low, high] = end.to_le_bytes();
+    code.emit(&[0x4C, low, high]); // JMP end
+
+    // the rom never executes more instructions than it has bytes
+    let budget = code.prg.len() as u64 * MAX_INSTRUCTION_CYCLES;
+    assert!(code.prg.len() <= PRG_SIZE, "the timing rom is too large");
+
+    let mut prg = code.prg;
+    prg.resize(PRG_SIZE, 0xEA);
+    // NMI, reset and IRQ vectors
+    let vectors = [end, ROM_START, code.irq].map(u16::to_le_bytes).concat();
+    prg[PRG_SIZE - 6..].copy_from_slice(&vectors);
+
+    let mut rom = b"NES\x1A\x02\x01\x00\x00".to_vec();
+    rom.resize(16, 0);
+    rom.extend(prg);
+    rom.resize(rom.len() + 0x2000, 0);
+
+    TimingRom {
+        rom,
+        timed: code.timed,
+        end,
+        budget,
+    }
+}
+
+/// The code of the timing rom, while it is generated
+struct Code {
+    prg: Vec<u8>,
+    timed: HashMap<u16, Timed>,
+    /// Where `BRK` continues
+    irq: u16,
+}
+
+impl Code {
+    fn address(&self) -> u16 {
+        ROM_START + self.prg.len() as u16
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.prg.extend_from_slice(bytes);
+    }
+
+    /// Emits the timed instruction `bytes`
+    fn time(&mut self, case: TimingCase, expected: u8, bytes: &[u8]) {
+        let timed = Timed {
+            opcode: bytes[0],
+            case,
+            expected: u64::from(expected),
+        };
+        self.timed.insert(self.address(), timed);
+        self.emit(bytes);
+    }
+
+    /// Emits `opcode`, which isn't a branch, together with the code that sets up its operand
+    fn instruction(&mut self, opcode: u8, info: &Opcode, case: TimingCase) {
+        let crossed = case == TimingCase::PageCrossed;
+        let expected = info.cycles + u8::from(crossed && info.page_penalty);
+        let address = if crossed { PAGE_END } else { SAME_PAGE };
+
+        match opcode {
+            // BRK skips the byte after it, and continues at the address in the IRQ vector
+            0x00 => {
+                self.irq = self.address() + 2;
+                self.time(case, expected, &[0x00, 0xEA]);
+            }
+            // JSR and JMP continue with the next instruction
+            0x20 | 0x4C => {
+                let [low, high] = (self.address() + 3).to_le_bytes();
+                self.time(case, expected, &[opcode, low, high]);
+            }
+            // JMP ($0020) continues with the next instruction, after the 8 bytes that set up the pointer
+            0x6C => {
+                self.pointer(self.address() + 8 + 3);
+                self.time(case, expected, &[0x6C, POINTER, 0x00]);
+            }
+            // RTI pulls the status register and the address of the next instruction,
+            // after the 7 bytes that push them
+            0x40 => {
+                let [low, high] = (self.address() + 7 + 1).to_le_bytes();
+                // LDA #high, PHA, LDA #low, PHA, PHP
+                self.emit(&[0xA9, high, 0x48, 0xA9, low, 0x48, 0x08]);
+                self.time(case, expected, &[0x40]);
+            }
+            // RTS pulls the address of the next instruction minus one, after the 6 bytes that push it
+            0x60 => {
+                let [low, high] = (self.address() + 6).to_le_bytes();
+                // LDA #high, PHA, LDA #low, PHA
+                self.emit(&[0xA9, high, 0x48, 0xA9, low, 0x48]);
+                self.time(case, expected, &[0x60]);
+            }
+            _ => {
+                self.emit(&[0xA2, 0x01, 0xA0, 0x01]); // LDX #$01, LDY #$01
+                let [low, high] = address.to_le_bytes();
+                match info.mode {
+                    Implied | Accumulator => self.time(case, expected, &[opcode]),
+                    Immediate => self.time(case, expected, &[opcode, 0x00]),
+                    ZeroPage | ZeroPageX | ZeroPageY => {
+                        self.time(case, expected, &[opcode, ZERO_PAGE])
+                    }
+                    Absolute | AbsoluteX | AbsoluteY => {
+                        self.time(case, expected, &[opcode, low, high])
+                    }
+                    // X is 1, so the pointer is read from the byte after the operand
+                    IndirectX => {
+                        self.pointer(address);
+                        self.time(case, expected, &[opcode, POINTER - 1]);
+                    }
+                    IndirectY => {
+                        self.pointer(address);
+                        self.time(case, expected, &[opcode, POINTER]);
+                    }
+                    Indirect | Relative => {
+                        unreachable!("JMP and the branches are timed separately")
+                    }
+                }
+            }
+        }
+    }
+
+    /// Emits the 8 bytes that point the pointer in the zero page at `address`
+    fn pointer(&mut self, address: u16) {
+        let [low, high] = address.to_le_bytes();
+        // LDA #low, STA $20, LDA #high, STA $21
+        self.emit(&[0xA9, low, 0x85, POINTER, 0xA9, high, 0x85, POINTER + 1]);
+    }
+
+    /// Emits the branch `opcode`, together with the code that sets the flags that make it branch or not.
+    /// A branch that is taken skips the byte after it.
+    fn branch(&mut self, opcode: u8, info: &Opcode, case: TimingCase) {
+        let setup = 4;
+        // a branch crosses a page when its target is on another page than the next instruction,
+        // which is the case when the branch ends right before the last byte of a page
+        let crossing = |address: u16| (address + setup + 2) & 0xFF == 0xFF;
+        while crossing(self.address()) != (case == TimingCase::BranchToOtherPage) {
+            self.emit(&[0xEA]); // NOP
+        }
+
+        // the flag the branch tests, and whether it branches when that flag is set
+        let flag = [0x80, 0x40, 0x01, 0x02][usize::from(opcode >> 6)];
+        let when_set = opcode & 0x20 != 0;
+        let taken = case != TimingCase::Base;
+        // keep the interrupt disable flag and bit 5 set
+        let status = 0x24 | if taken == when_set { flag } else { 0 };
+        self.emit(&[0xA9, status, 0x48, 0x28]); // LDA #status, PHA, PLP
+
+        let expected =
+            info.cycles + u8::from(taken) + u8::from(case == TimingCase::BranchToOtherPage);
+        self.time(case, expected, &[opcode, 0x01]);
+        self.emit(&[0xEA]); // NOP
+    }
+}
+
+/// Steps through the timing rom until it ends, and returns the instructions that took the wrong amount of cycles.
+/// An instruction takes the cycles [`CpuRegisters::cycles`](crate::CpuRegisters::cycles) went up by while it was
+/// stepped. The cpu has to have [`Capability::Step`](crate::Capability::Step).
+pub(crate) fn time_instructions<T: TestableCpu>(
+    cpu: &mut T,
+    rom: &TimingRom,
+    progress: &TestProgress,
+) -> Result<Vec<TimingMismatch>, TestOutcome> {
+    let registers = |cpu: &T| Registers::read(cpu.registers().unwrap());
+
+    let mut actual = HashMap::new();
+    let mut total = 0;
+
+    loop {
+        let before = registers(cpu);
+        if before.pc == rom.end {
+            break;
+        }
+        if total > rom.budget {
+            return Err(TestOutcome::TimedOut(format!(
+                "the timing rom didn't end within {} cycles, the cpu was at ${:04X}",
+                rom.budget, before.pc
+            )));
+        }
+
+        progress
+            .last_pc
+            .store(u32::from(before.pc), Ordering::Relaxed);
+        // a stepped cpu isn't watched, so its instructions are kept here
+        progress.history.lock().unwrap().before_step(cpu, before);
+        let stepped = cpu.steppable().unwrap().step();
+        let after = registers(cpu).cycles;
+
+        let cycles = match (before.cycles, after) {
+            (Some(before), Some(after)) => after.wrapping_sub(before),
+            _ => {
+                return Err(TestOutcome::TimedOut(
+                    "the cpu stopped counting its cycles".to_string(),
+                ))
+            }
+        };
+        total += cycles;
+        progress.cycles.fetch_add(cycles, Ordering::Relaxed);
+        stepped.map_err(|e| cpu_error(e, None))?;
+
+        if rom.timed.contains_key(&before.pc) {
+            actual.insert(before.pc, cycles);
+        }
+    }
+
+    let mut mismatches: Vec<_> = rom
+        .timed
+        .iter()
+        .map(|(address, timed)| TimingMismatch {
+            opcode: timed.opcode,
+            case: timed.case,
+            expected: timed.expected,
+            actual: actual.get(address).copied(),
+        })
+        .filter(|mismatch| mismatch.actual != Some(mismatch.expected))
+        .collect();
+    mismatches.sort_by_key(|mismatch| (mismatch.opcode, mismatch.case as u8));
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_opcode_but_the_jams_is_timed() {
+        let rom = timing_rom(false);
+        let official = timing_rom(true);
+
+        let count = |rom: &TimingRom, opcode: u8| {
+            rom.timed
+                .values()
+                .filter(|timed| timed.opcode == opcode)
+                .count()
+        };
+        // LDA #, LDA abs,X, BNE, the unofficial LAX (ind),Y and the jam 02
+        assert_eq!(count(&rom, 0xA9), 1);
+        assert_eq!(count(&rom, 0xBD), 2);
+        assert_eq!(count(&rom, 0xD0), 3);
+        assert_eq!(count(&rom, 0xB3), 2);
+        assert_eq!(count(&official, 0xB3), 0);
+        assert_eq!(count(&rom, 0x02), 0);
+    }
+
+    #[test]
+    fn timed_instructions_are_in_the_rom() {
+        let rom = timing_rom(false);
+        let prg = &rom.rom[16..16 + PRG_SIZE];
+        let byte = |address: u16| prg[usize::from(address - ROM_START)];
+
+        assert_eq!(rom.rom.len(), 16 + PRG_SIZE + 0x2000);
+        assert_eq!(prg[PRG_SIZE - 4..PRG_SIZE - 2], ROM_START.to_le_bytes());
+        assert_eq!(byte(rom.end), 0x4C);
+        for (address, timed) in &rom.timed {
+            assert_eq!(byte(*address), timed.opcode);
+        }
+    }
+
+    #[test]
+    fn branches_to_another_page_cross_it() {
+        let rom = timing_rom(false);
+
+        for (address, timed) in &rom.timed {
+            if OPCODES[usize::from(timed.opcode)].mode != Relative {
+                continue;
+            }
+            // the branches skip a single byte
+            let next = address + 2;
+            let target = next + 1;
+            assert_eq!(
+                next & 0xFF00 != target & 0xFF00,
+                timed.case == TimingCase::BranchToOtherPage,
+                "{address:04X}"
+            );
+        }
+    }
+
+    #[test]
+    fn penalties_are_expected() {
+        let rom = timing_rom(false);
+        let expected = |opcode: u8, case: TimingCase| {
+            rom.timed
+                .values()
+                .find(|timed| timed.opcode == opcode && timed.case == case)
+                .map(|timed| timed.expected)
+        };
+
+        // LDA abs,X, STA abs,X, BNE and BRK
+        assert_eq!(expected(0xBD, TimingCase::Base), Some(4));
+        assert_eq!(expected(0xBD, TimingCase::PageCrossed), Some(5));
+        assert_eq!(expected(0x9D, TimingCase::PageCrossed), Some(5));
+        assert_eq!(expected(0xD0, TimingCase::Base), Some(2));
+        assert_eq!(expected(0xD0, TimingCase::BranchTaken), Some(3));
+        assert_eq!(expected(0xD0, TimingCase::BranchToOtherPage), Some(4));
+        assert_eq!(expected(0x00, TimingCase::Base), Some(7));
+    }
+
+    #[test]
+    fn table_lists_every_mismatch() {
+        let mismatches = [
+            TimingMismatch {
+                opcode: 0xBD,
+                case: TimingCase::PageCrossed,
+                expected: 5,
+                actual: Some(4),
+            },
+            TimingMismatch {
+                opcode: 0xD0,
+                case: TimingCase::BranchTaken,
+                expected: 3,
+                actual: None,
+            },
+        ];
+
+        let table = timing_table(&mismatches);
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("2 instructions"));
+        assert!(lines[2].starts_with(" BD  LDA"));
+        assert!(lines[2].contains("crossing a page"));
+        assert!(lines[2].ends_with("       5      4"));
+        assert!(lines[3].ends_with("       3      -"));
+        assert_eq!(
+            mismatches[1].to_string(),
+            format!("D0 {}, taking the branch: wasn't executed", OPCODES[0xD0])
+        );
+    }
+
+    #[cfg(feature = "reference")]
+    #[test]
+    fn reference_cpu_takes_the_right_amount_of_cycles() {
+        use crate::ReferenceCpu;
+
+        let rom = timing_rom(false);
+        let progress = TestProgress::new("timing", None);
+        let mut cpu = ReferenceCpu::get_cpu(&rom.rom).unwrap();
+
+        let mismatches = time_instructions(&mut cpu, &rom, &progress).unwrap();
+        assert_eq!(mismatches, [], "{}", timing_table(&mismatches));
+        assert!(progress.cycles.load(Ordering::Relaxed) > rom.timed.len() as u64 * 2);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::disassembler::column;
+use crate::history::Kept;
+use crate::nestest::LogLine;
+use crate::{Disassembly, Registers, TestableCpu};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+/// Writes the state of your CPU before every instruction of a test to a file, so you can compare it to a trace of
+/// another emulator with standard tools like `diff`. Set it in [`TestRunner::trace`](crate::TestRunner::trace)
+/// or [`BlarggOptions::trace`](crate::BlarggOptions::trace).
+///
+/// Tracing needs [`CpuRegisters`](crate::CpuRegisters). Like [`run_nestest_log`](crate::run_nestest_log),
+/// every tick in which the program counter changes is considered to be the start of a new instruction.
+/// When your CPU doesn't report its cycles, the amount of ticks since the rom was loaded is used instead,
+/// starting at the 7 cycles a reset takes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    /// The format of the lines
+    pub format: TraceFormat,
+    /// The directory the traces are written to, in a file named after the test, like `nestest.log`
+    pub directory: PathBuf,
+}
+
+/// The format of the lines of a [`Trace`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TraceFormat {
+    /// The format of Nintendulator, which `nestest.log` uses:
+    /// `C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7`
+    #[default]
+    Nintendulator,
+    /// The format of the trace logger of Mesen:
+    /// `C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 S:FD P:nvUbdIzc V:0   H:21  Cycle:7`
+    Mesen,
+    /// The format of the trace logger of FCEUX:
+    /// `A:00 X:00 Y:00 S:FD P:nvUbdIzc  $C000:4C F5 C5  JMP $C5F5`
+    Fceux,
+}
+
+/// Writes a [`Trace`] while a test runs
+#[derive(Debug)]
+pub(crate) struct Tracer {
+    format: TraceFormat,
+    file: BufWriter<File>,
+    /// Whether writing failed, after which nothing is written anymore
+    failed: bool,
+    /// The instruction the cpu executes next, read once when the previous instruction started
+    next: Option<Disassembly>,
+}
+
+impl Tracer {
+    /// Creates the file for the trace of `test`. Tracing is skipped when that fails.
+    pub(crate) fn create(trace: &Trace, test: &str) -> Option<Self> {
+        let name: String = test
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let path = trace.directory.join(format!("{name}.log"));
+
+        match File::create(&path) {
+            Ok(file) => Some(Self {
+                format: trace.format,
+                file: BufWriter::new(file),
+                failed: false,
+                next: None,
+            }),
+            Err(e) => {
+                log::warn!("couldn't create trace {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    /// Reads the instruction at `pc` before a tick, unless it was read already. The values in memory it shows
+    /// can change when it executes, so it is read once before its first tick, and not in every tick after that.
+    pub(crate) fn before_tick<T: TestableCpu>(&mut self, cpu: &T, pc: u16) {
+        if !self.failed && self.next.is_none() {
+            self.next = Some(Disassembly::read(cpu, pc));
+        }
+    }
+
+    /// Writes the line of `instruction`, which started in tick `tick`, with the disassembly read before that tick
+    pub(crate) fn write(&mut self, instruction: &Kept, tick: u64) {
+        let Some(disassembly) = self.next.take() else {
+            return;
+        };
+        if self.failed {
+            return;
+        }
+
+        let registers = &instruction.registers;
+        let line = self.line(
+            registers,
+            instruction.bytes().to_vec(),
+            disassembly.to_string(),
+            registers.cycles.unwrap_or(tick),
+        );
+        if let Err(e) = writeln!(self.file, "{line}") {
+            log::warn!("couldn't write trace: {e}");
+            self.failed = true;
+        }
+    }
+
+    fn line(
+        &self,
+        registers: &Registers,
+        bytes: Vec<u8>,
+        disassembly: String,
+        cycle: u64,
+    ) -> String {
+        let hex = bytes
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let dot = cycle * 3;
+
+        match self.format {
+            TraceFormat::Nintendulator => LogLine {
+                pc: registers.pc,
+                bytes,
+                disassembly,
+                a: registers.a,
+                x: registers.x,
+                y: registers.y,
+                // like nestest.log, leave out the break flag and set bit 5, which don't exist in the cpu
+                p: (registers.p & !0x10) | 0x20,
+                sp: registers.sp,
+                cycle,
+            }
+            .to_string(),
+            TraceFormat::Mesen => format!(
+                "{:04X}  {hex:<8} {}A:{:02X} X:{:02X} Y:{:02X} S:{:02X} P:{} V:{:<3} H:{:<3} Cycle:{cycle}",
+                registers.pc,
+                column(&disassembly, 32),
+                registers.a,
+                registers.x,
+                registers.y,
+                registers.sp,
+                flags(registers.p),
+                (dot / 341) % 262,
+                dot % 341,
+            ),
+            TraceFormat::Fceux => format!(
+                "A:{:02X} X:{:02X} Y:{:02X} S:{:02X} P:{}  ${:04X}:{hex:<9} {disassembly}",
+                registers.a,
+                registers.x,
+                registers.y,
+                registers.sp,
+                flags(registers.p),
+                registers.pc,
+            ),
+        }
+    }
+
+    pub(crate) fn flush(&mut self) {
+        if let Err(e) = self.file.flush() {
+            log::warn!("couldn't write trace: {e}");
+        }
+    }
+}
+
+/// The flags of the status register as letters, in uppercase when they are set
+fn flags(p: u8) -> String {
+    "nvubdizc"
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if p & (0x80 >> i) != 0 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for the traces of `test`
+    fn directory(test: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("tudelft-nes-test-{}-{test}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn flags_are_uppercase_when_set() {
+        assert_eq!(flags(0x24), "nvUbdIzc");
+        assert_eq!(flags(0xC3), "NVubdiZC");
+    }
+
+    #[test]
+    fn lines_in_every_format() {
+        let registers = Registers {
+            a: 0x00,
+            x: 0x01,
+            y: 0x02,
+            p: 0x24,
+            sp: 0xFD,
+            pc: 0xC000,
+            cycles: None,
+        };
+        let line = |format| {
+            let trace = Trace {
+                format,
+                directory: directory("formats"),
+            };
+            let tracer = Tracer::create(&trace, "format test").unwrap();
+            tracer.line(
+                &registers,
+                vec![0x4C, 0xF5, 0xC5],
+                "JMP $C5F5".to_string(),
+                7,
+            )
+        };
+
+        assert_eq!(
+            line(TraceFormat::Nintendulator),
+            "C000  4C F5 C5  JMP $C5F5                       A:00 X:01 Y:02 P:24 SP:FD PPU:  0, 21 CYC:7"
+        );
+        assert_eq!(
+            line(TraceFormat::Mesen),
+            "C000  4C F5 C5  JMP $C5F5                       A:00 X:01 Y:02 S:FD P:nvUbdIzc V:0   H:21  Cycle:7"
+        );
+        assert_eq!(
+            line(TraceFormat::Fceux),
+            "A:00 X:01 Y:02 S:FD P:nvUbdIzc  $C000:4C F5 C5  JMP $C5F5"
+        );
+    }
+
+    #[test]
+    fn names_are_safe_for_files() {
+        let directory = directory("names");
+        let trace = Trace {
+            format: TraceFormat::Nintendulator,
+            directory: directory.clone(),
+        };
+
+        Tracer::create(&trace, "instruction timing (official only)").unwrap();
+        assert!(directory
+            .join("instruction_timing__official_only_.log")
+            .exists());
+    }
+
+    #[cfg(feature = "reference")]
+    #[test]
+    fn reference_cpu_traces_nestest_log() {
+        use crate::{Isolation, ReferenceCpu, TestRunner, TestSelector, NESTEST_LOG};
+
+        let directory = directory("nestest");
+        let runner = TestRunner {
+            isolation: Isolation::Inline,
+            trace: Some(Trace {
+                format: TraceFormat::Nintendulator,
+                directory: directory.clone(),
+            }),
+            ..TestRunner::default()
+        };
+        runner
+            .run_tests::<ReferenceCpu>(TestSelector::NESTEST_LOG)
+            .unwrap();
+
+        let trace = std::fs::read_to_string(directory.join("nestest_log.log")).unwrap();
+        // the trace goes on to the end of the rom after the last line of the log
+        for (number, (traced, expected)) in trace.lines().zip(NESTEST_LOG.lines()).enumerate() {
+            // nestest.log shows the APU registers as FF, which the disassembler doesn't read
+            let expected = match expected.contains(" $40") {
+                true => expected.replacen(" = FF", "     ", 1),
+                false => expected.to_string(),
+            };
+            assert_eq!(traced, expected, "line {}", number + 1);
+        }
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
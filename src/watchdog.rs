@@ -0,0 +1,109 @@
+use crate::history::History;
+use crate::trace::Tracer;
+use crate::TestableCpu;
+use std::error::Error;
+use std::sync::atomic::{AtomicU32, Ordering};
+use thiserror::Error;
+use tudelft_nes_ppu::{Cpu, Ppu};
+
+/// The amount of ticks the program counter has to stay within [`STUCK_RANGE`] bytes before the cpu is considered stuck
+const STUCK_TICKS: u64 = 10_000_000;
+/// The largest distance between the lowest and highest program counter of a loop the cpu can get stuck in
+const STUCK_RANGE: u16 = 8;
+
+/// Returned by [`Watched::tick`] when the cpu is stuck
+#[derive(Debug, Error)]
+#[error("CPU is stuck at ${pc:04X}")]
+pub(crate) struct CpuStuck {
+    pub(crate) pc: u16,
+}
+
+/// Returned by [`Watched::tick`] when the cpu reaches [`Watched::stop_at`]
+#[derive(Debug, Error)]
+#[error("CPU reached ${pc:04X}")]
+pub(crate) struct Stopped {
+    pub(crate) pc: u16,
+}
+
+/// Keeps track of the range of program counters the cpu was at recently
+#[derive(Debug, Default)]
+pub(crate) struct StuckDetector {
+    low: u16,
+    high: u16,
+    ticks: u64,
+}
+
+impl StuckDetector {
+    /// Adds the program counter after a tick, and returns whether the cpu is stuck
+    fn observe(&mut self, pc: u16) -> bool {
+        let low = self.low.min(pc);
+        let high = self.high.max(pc);
+
+        if self.ticks == 0 || high - low > STUCK_RANGE {
+            self.low = pc;
+            self.high = pc;
+            self.ticks = 1;
+            return false;
+        }
+
+        self.low = low;
+        self.high = high;
+        self.ticks += 1;
+        self.ticks >= STUCK_TICKS
+    }
+}
+
+/// Wraps a cpu to look at its registers after every tick, to keep its history and to trace it.
+/// This only works when the cpu gives access to its registers, other cpus are simply ticked.
+pub(crate) struct Watched<'a, T> {
+    pub(crate) cpu: &'a mut T,
+    pub(crate) detector: &'a mut StuckDetector,
+    /// The program counter after the last tick, or `u32::MAX` when it isn't known
+    pub(crate) last_pc: &'a AtomicU32,
+    pub(crate) history: &'a mut History,
+    pub(crate) tracer: Option<&'a mut Tracer>,
+    /// The program counter at which the cpu stops, with a [`Stopped`] error
+    pub(crate) stop_at: Option<u16>,
+    /// The amount of ticks the cpu ran for
+    pub(crate) ticks: u64,
+}
+
+impl<T: TestableCpu> Cpu for Watched<'_, T> {
+    fn tick(&mut self, ppu: &mut Ppu) -> Result<(), Box<dyn Error>> {
+        self.history.before_tick(self.cpu);
+        if let (Some(tracer), Some(pc)) = (&mut self.tracer, self.history.pc()) {
+            tracer.before_tick(self.cpu, pc);
+        }
+        self.cpu.tick(ppu)?;
+        self.ticks += 1;
+
+        if let Some(registers) = self.cpu.registers() {
+            let pc = registers.program_counter();
+            self.last_pc.store(u32::from(pc), Ordering::Relaxed);
+
+            let started = self.history.after_tick(self.cpu, registers, pc);
+            if let (Some((instruction, tick)), Some(tracer)) = (started, &mut self.tracer) {
+                tracer.write(instruction, tick);
+            }
+
+            if self.detector.observe(pc) {
+                self.history.keep_stuck(self.cpu);
+                return Err(Box::new(CpuStuck { pc }));
+            }
+
+            if self.stop_at == Some(pc) {
+                return Err(Box::new(Stopped { pc }));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn ppu_read_chr_rom(&self, offset: u16) -> u8 {
+        self.cpu.ppu_read_chr_rom(offset)
+    }
+
+    fn non_maskable_interrupt(&mut self) {
+        self.cpu.non_maskable_interrupt()
+    }
+}